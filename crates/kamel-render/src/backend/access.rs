@@ -0,0 +1,299 @@
+use ash::vk;
+
+//How a resource is used by a command, shared by the render graph and barrier generation
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Access {
+    Nothing,
+    IndirectBuffer,
+    IndexBuffer,
+    VertexBuffer,
+    //Sampled images, uniform buffers and read only storage buffers
+    VertexShaderRead,
+    FragmentShaderRead,
+    ComputeShaderRead,
+    //Storage images and buffers, images are kept in the general layout
+    ComputeShaderReadStorage,
+    FragmentShaderWrite,
+    ComputeShaderWrite,
+    ColorAttachmentWrite,
+    DepthStencilAttachmentRead,
+    DepthStencilAttachmentWrite,
+    TransferRead,
+    TransferWrite,
+    HostRead,
+    HostWrite,
+    Present,
+    General
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AccessInfo {
+    pub stage_mask: vk::PipelineStageFlags2,
+    pub access_mask: vk::AccessFlags2,
+    pub image_layout: vk::ImageLayout
+}
+
+impl AccessInfo {
+    //Combined info of a command that accesses a resource in multiple ways, images fall back to the general layout if the layouts differ
+    #[inline]
+    pub fn merge(self, other: AccessInfo) -> AccessInfo {
+        AccessInfo {
+            stage_mask: self.stage_mask | other.stage_mask,
+            access_mask: self.access_mask | other.access_mask,
+            image_layout: if self.image_layout == other.image_layout {
+                self.image_layout
+            } else {
+                vk::ImageLayout::GENERAL
+            }
+        }
+    }
+}
+
+impl Access {
+    pub fn info(self) -> AccessInfo {
+        let (stage_mask, access_mask, image_layout) = match self {
+            Self::Nothing => (vk::PipelineStageFlags2::NONE, vk::AccessFlags2::NONE, vk::ImageLayout::UNDEFINED),
+            Self::IndirectBuffer => (vk::PipelineStageFlags2::DRAW_INDIRECT, vk::AccessFlags2::INDIRECT_COMMAND_READ, vk::ImageLayout::UNDEFINED),
+            Self::IndexBuffer => (vk::PipelineStageFlags2::INDEX_INPUT, vk::AccessFlags2::INDEX_READ, vk::ImageLayout::UNDEFINED),
+            Self::VertexBuffer => {
+                (
+                    vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT,
+                    vk::AccessFlags2::VERTEX_ATTRIBUTE_READ,
+                    vk::ImageLayout::UNDEFINED
+                )
+            }
+            Self::VertexShaderRead => (vk::PipelineStageFlags2::VERTEX_SHADER, vk::AccessFlags2::SHADER_READ, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+            Self::FragmentShaderRead => (vk::PipelineStageFlags2::FRAGMENT_SHADER, vk::AccessFlags2::SHADER_READ, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+            Self::ComputeShaderRead => (vk::PipelineStageFlags2::COMPUTE_SHADER, vk::AccessFlags2::SHADER_READ, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+            Self::ComputeShaderReadStorage => (vk::PipelineStageFlags2::COMPUTE_SHADER, vk::AccessFlags2::SHADER_STORAGE_READ, vk::ImageLayout::GENERAL),
+            Self::FragmentShaderWrite => (vk::PipelineStageFlags2::FRAGMENT_SHADER, vk::AccessFlags2::SHADER_STORAGE_WRITE, vk::ImageLayout::GENERAL),
+            Self::ComputeShaderWrite => (vk::PipelineStageFlags2::COMPUTE_SHADER, vk::AccessFlags2::SHADER_STORAGE_WRITE, vk::ImageLayout::GENERAL),
+            Self::ColorAttachmentWrite => {
+                (
+                    vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                    vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+                )
+            }
+            Self::DepthStencilAttachmentRead => {
+                (
+                    vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                    vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ,
+                    vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+                )
+            }
+            Self::DepthStencilAttachmentWrite => {
+                (
+                    vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                    vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                    vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+                )
+            }
+            Self::TransferRead => (vk::PipelineStageFlags2::ALL_TRANSFER, vk::AccessFlags2::TRANSFER_READ, vk::ImageLayout::TRANSFER_SRC_OPTIMAL),
+            Self::TransferWrite => (vk::PipelineStageFlags2::ALL_TRANSFER, vk::AccessFlags2::TRANSFER_WRITE, vk::ImageLayout::TRANSFER_DST_OPTIMAL),
+            Self::HostRead => (vk::PipelineStageFlags2::HOST, vk::AccessFlags2::HOST_READ, vk::ImageLayout::GENERAL),
+            Self::HostWrite => (vk::PipelineStageFlags2::HOST, vk::AccessFlags2::HOST_WRITE, vk::ImageLayout::GENERAL),
+            //Presentation is synchronized by the semaphores, only the layout matters
+            Self::Present => (vk::PipelineStageFlags2::NONE, vk::AccessFlags2::NONE, vk::ImageLayout::PRESENT_SRC_KHR),
+            Self::General => {
+                (
+                    vk::PipelineStageFlags2::ALL_COMMANDS,
+                    vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+                    vk::ImageLayout::GENERAL
+                )
+            }
+        };
+
+        AccessInfo {
+            stage_mask,
+            access_mask,
+            image_layout
+        }
+    }
+
+    #[inline]
+    pub fn is_write(self) -> bool {
+        matches!(
+            self,
+            Self::FragmentShaderWrite | Self::ComputeShaderWrite | Self::ColorAttachmentWrite | Self::DepthStencilAttachmentWrite | Self::TransferWrite | Self::HostWrite | Self::General
+        )
+    }
+
+    pub fn image_usage(self) -> vk::ImageUsageFlags {
+        match self {
+            Self::VertexShaderRead | Self::FragmentShaderRead | Self::ComputeShaderRead => vk::ImageUsageFlags::SAMPLED,
+            Self::ComputeShaderReadStorage | Self::FragmentShaderWrite | Self::ComputeShaderWrite | Self::General => vk::ImageUsageFlags::STORAGE,
+            Self::ColorAttachmentWrite => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            Self::DepthStencilAttachmentRead | Self::DepthStencilAttachmentWrite => vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            Self::TransferRead => vk::ImageUsageFlags::TRANSFER_SRC,
+            Self::TransferWrite => vk::ImageUsageFlags::TRANSFER_DST,
+            _ => vk::ImageUsageFlags::empty()
+        }
+    }
+
+    pub fn buffer_usage(self) -> vk::BufferUsageFlags {
+        match self {
+            Self::IndirectBuffer => vk::BufferUsageFlags::INDIRECT_BUFFER,
+            Self::IndexBuffer => vk::BufferUsageFlags::INDEX_BUFFER,
+            Self::VertexBuffer => vk::BufferUsageFlags::VERTEX_BUFFER,
+            Self::VertexShaderRead
+            | Self::FragmentShaderRead
+            | Self::ComputeShaderRead
+            | Self::ComputeShaderReadStorage
+            | Self::FragmentShaderWrite
+            | Self::ComputeShaderWrite
+            | Self::General => vk::BufferUsageFlags::STORAGE_BUFFER,
+            Self::TransferRead => vk::BufferUsageFlags::TRANSFER_SRC,
+            Self::TransferWrite => vk::BufferUsageFlags::TRANSFER_DST,
+            _ => vk::BufferUsageFlags::empty()
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AccessBarrier {
+    pub src_stage_mask: vk::PipelineStageFlags2,
+    pub src_access_mask: vk::AccessFlags2,
+    pub dst_stage_mask: vk::PipelineStageFlags2,
+    pub dst_access_mask: vk::AccessFlags2,
+    pub old_layout: vk::ImageLayout,
+    pub new_layout: vk::ImageLayout
+}
+
+impl AccessBarrier {
+    #[inline]
+    pub fn image_memory_barrier(&self, image: vk::Image, subresource_range: vk::ImageSubresourceRange) -> vk::ImageMemoryBarrier2<'static> {
+        vk::ImageMemoryBarrier2::default()
+            .src_stage_mask(self.src_stage_mask)
+            .src_access_mask(self.src_access_mask)
+            .dst_stage_mask(self.dst_stage_mask)
+            .dst_access_mask(self.dst_access_mask)
+            .old_layout(self.old_layout)
+            .new_layout(self.new_layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+    }
+
+    #[inline]
+    pub fn buffer_memory_barrier(&self, buffer: vk::Buffer, offset: u64, size: u64) -> vk::BufferMemoryBarrier2<'static> {
+        vk::BufferMemoryBarrier2::default()
+            .src_stage_mask(self.src_stage_mask)
+            .src_access_mask(self.src_access_mask)
+            .dst_stage_mask(self.dst_stage_mask)
+            .dst_access_mask(self.dst_access_mask)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(buffer)
+            .offset(offset)
+            .size(size)
+    }
+}
+
+//Synchronization state of a resource, used to compute the minimal barrier for the next access
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AccessState {
+    //None for buffers
+    layout: Option<vk::ImageLayout>,
+    write_stage_mask: vk::PipelineStageFlags2,
+    write_access_mask: vk::AccessFlags2,
+    //Stages that read the resource since the last write and are already synchronized with it
+    read_stage_mask: vk::PipelineStageFlags2
+}
+
+impl AccessState {
+    #[inline]
+    fn new(layout: Option<vk::ImageLayout>, info: AccessInfo, is_write: bool) -> Self {
+        let (write_access_mask, read_stage_mask) = if is_write {
+            (info.access_mask, vk::PipelineStageFlags2::NONE)
+        } else {
+            (vk::AccessFlags2::NONE, info.stage_mask)
+        };
+
+        Self {
+            layout,
+            write_stage_mask: info.stage_mask,
+            write_access_mask,
+            read_stage_mask
+        }
+    }
+
+    #[inline]
+    pub fn image(access: Access) -> Self {
+        Self::new(Some(access.info().image_layout), access.info(), access.is_write())
+    }
+
+    #[inline]
+    pub fn buffer(access: Access) -> Self {
+        Self::new(None, access.info(), access.is_write())
+    }
+
+    #[inline]
+    pub fn layout(&self) -> Option<vk::ImageLayout> {
+        self.layout
+    }
+
+    //Makes the next access wait for every access of a resource that occupied the same memory before
+    #[inline]
+    pub fn alias(&mut self, previous: &AccessState) {
+        self.write_stage_mask |= previous.write_stage_mask | previous.read_stage_mask;
+        self.write_access_mask |= previous.write_access_mask;
+    }
+
+    //Returns None if the access is already synchronized with everything before it
+    #[inline]
+    pub fn transition(&mut self, access: Access) -> Option<AccessBarrier> {
+        self.transition_info(access.info(), access.is_write())
+    }
+
+    //Single barrier for a command that accesses the resource in multiple ways, like reading and writing a storage image
+    pub fn transition_all(&mut self, accesses: &[Access]) -> Option<AccessBarrier> {
+        let (first, rest) = accesses.split_first()?;
+        let info = rest.iter().fold(first.info(), |info, access| info.merge(access.info()));
+
+        self.transition_info(info, accesses.iter().any(|access| access.is_write()))
+    }
+
+    fn transition_info(&mut self, info: AccessInfo, is_write: bool) -> Option<AccessBarrier> {
+        let new_layout = self.layout.map(|_| info.image_layout);
+        let layout_transition = self.layout != new_layout;
+
+        if is_write || layout_transition {
+            //Writes and layout transitions have to wait for every previous access
+            let src_stage_mask = self.write_stage_mask | self.read_stage_mask;
+
+            let barrier = if src_stage_mask.is_empty() && !layout_transition {
+                None
+            } else {
+                Some(AccessBarrier {
+                    src_stage_mask,
+                    src_access_mask: self.write_access_mask,
+                    dst_stage_mask: info.stage_mask,
+                    dst_access_mask: info.access_mask,
+                    old_layout: self.layout.unwrap_or(vk::ImageLayout::UNDEFINED),
+                    new_layout: new_layout.unwrap_or(vk::ImageLayout::UNDEFINED)
+                })
+            };
+
+            *self = Self::new(new_layout, info, is_write);
+
+            barrier
+        } else if self.write_stage_mask.is_empty() || self.read_stage_mask.contains(info.stage_mask) {
+            self.read_stage_mask |= info.stage_mask;
+
+            None
+        } else {
+            self.read_stage_mask |= info.stage_mask;
+
+            Some(AccessBarrier {
+                src_stage_mask: self.write_stage_mask,
+                src_access_mask: self.write_access_mask,
+                dst_stage_mask: info.stage_mask,
+                dst_access_mask: info.access_mask,
+                old_layout: self.layout.unwrap_or(vk::ImageLayout::UNDEFINED),
+                new_layout: self.layout.unwrap_or(vk::ImageLayout::UNDEFINED)
+            })
+        }
+    }
+}
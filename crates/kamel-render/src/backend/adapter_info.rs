@@ -0,0 +1,175 @@
+use std::{ffi::CStr, fmt, os::raw::c_char};
+
+use ash::vk;
+use serde::{Deserialize, Serialize};
+
+use crate::backend::PhysicalDeviceCandidate;
+
+#[inline]
+unsafe fn string_from_array(array: &[c_char]) -> String {
+    CStr::from_ptr(array.as_ptr()).to_string_lossy().into_owned()
+}
+
+#[inline]
+fn vendor_name(vendor_id: u32) -> &'static str {
+    match vendor_id {
+        0x1002 => "AMD",
+        0x1010 => "Imagination Technologies",
+        0x106B => "Apple",
+        0x10DE => "NVIDIA",
+        0x13B5 => "ARM",
+        0x1414 => "Microsoft",
+        0x5143 => "Qualcomm",
+        0x8086 => "Intel",
+        0x10005 => "Mesa",
+        _ => "Unknown"
+    }
+}
+
+//Drivers encode their version in a vendor specific way
+fn driver_version_string(vendor_id: u32, driver_version: u32) -> String {
+    match vendor_id {
+        0x10DE => {
+            format!(
+                "{}.{}.{}.{}",
+                (driver_version >> 22) & 0x3ff,
+                (driver_version >> 14) & 0xff,
+                (driver_version >> 6) & 0xff,
+                driver_version & 0x3f
+            )
+        }
+        0x8086 if cfg!(windows) => format!("{}.{}", driver_version >> 14, driver_version & 0x3fff),
+        _ => ApiVersion::from(driver_version).to_string()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ApiVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32
+}
+
+impl From<u32> for ApiVersion {
+    #[inline]
+    fn from(version: u32) -> Self {
+        Self {
+            major: vk::api_version_major(version),
+            minor: vk::api_version_minor(version),
+            patch: vk::api_version_patch(version)
+        }
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdapterType {
+    Other,
+    IntegratedGpu,
+    DiscreteGpu,
+    VirtualGpu,
+    Cpu
+}
+
+impl From<vk::PhysicalDeviceType> for AdapterType {
+    #[inline]
+    fn from(device_type: vk::PhysicalDeviceType) -> Self {
+        match device_type {
+            vk::PhysicalDeviceType::INTEGRATED_GPU => Self::IntegratedGpu,
+            vk::PhysicalDeviceType::DISCRETE_GPU => Self::DiscreteGpu,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => Self::VirtualGpu,
+            vk::PhysicalDeviceType::CPU => Self::Cpu,
+            _ => Self::Other
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueueFamilyInfo {
+    pub index: u32,
+    pub queue_count: u32,
+    pub graphics: bool,
+    pub compute: bool,
+    pub transfer: bool,
+    pub sparse_binding: bool,
+    pub timestamp_valid_bits: u32
+}
+
+//Summary of a physical device that can be shown in a settings menu or attached to bug reports
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdapterInfo {
+    //Not serialized, only valid for the instance the info was queried from
+    #[serde(skip)]
+    pub physical_device: vk::PhysicalDevice,
+    //Position in the enumeration order of the instance, can be used as the value of KAMEL_GPU
+    pub index: usize,
+
+    pub name: String,
+    pub vendor: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub device_type: AdapterType,
+
+    pub driver_name: String,
+    pub driver_info: String,
+    pub driver_version: String,
+    pub driver_version_raw: u32,
+    pub api_version: ApiVersion,
+
+    //Sum of the sizes of all device local heaps in bytes
+    pub device_local_memory_size: u64,
+    pub queue_families: Vec<QueueFamilyInfo>,
+    pub extensions: Vec<String>
+}
+
+impl From<&PhysicalDeviceCandidate> for AdapterInfo {
+    fn from(candidate: &PhysicalDeviceCandidate) -> Self {
+        let properties = &candidate.properties.properties;
+        let properties_12 = &candidate.properties.properties_12;
+
+        let queue_families = candidate
+            .queue_family_properties
+            .queue_family_properties
+            .iter()
+            .enumerate()
+            .map(|(index, properties)| {
+                QueueFamilyInfo {
+                    index: index as u32,
+                    queue_count: properties.queue_count,
+                    graphics: properties.queue_flags.contains(vk::QueueFlags::GRAPHICS),
+                    compute: properties.queue_flags.contains(vk::QueueFlags::COMPUTE),
+                    transfer: properties.queue_flags.contains(vk::QueueFlags::TRANSFER),
+                    sparse_binding: properties.queue_flags.contains(vk::QueueFlags::SPARSE_BINDING),
+                    timestamp_valid_bits: properties.timestamp_valid_bits
+                }
+            })
+            .collect();
+
+        Self {
+            physical_device: candidate.physical_device,
+            index: candidate.index,
+
+            name: candidate.name(),
+            vendor: vendor_name(properties.vendor_id).to_owned(),
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            device_type: properties.device_type.into(),
+
+            driver_name: unsafe { string_from_array(&properties_12.driver_name) },
+            driver_info: unsafe { string_from_array(&properties_12.driver_info) },
+            driver_version: driver_version_string(properties.vendor_id, properties.driver_version),
+            driver_version_raw: properties.driver_version,
+            api_version: properties.api_version.into(),
+
+            device_local_memory_size: candidate.device_local_memory_size(),
+            queue_families,
+            extensions: candidate.extensions.supported().map(|name| name.to_string_lossy().into_owned()).collect()
+        }
+    }
+}
@@ -1,50 +1,503 @@
 use core::slice;
-use std::{ops::Deref, sync::Arc};
+use std::{any::Any, marker::PhantomData, mem, ops::Deref, sync::Arc};
 
-use anyhow::{Ok, Result};
-use ash::vk;
+use anyhow::Result;
+use ash::{prelude::VkResult, vk};
 
-use crate::backend::{command::CommandPool, util::debug_utils, Device};
+use crate::backend::{command::CommandPool, util::debug_utils, Access, BindlessHeap, Buffer, ComputePipeline, Device, GraphicsPipeline, Image, ImageView, PipelineLayout};
 
 #[derive(Copy, Clone, Debug)]
 pub struct CommandBufferDesc<'a> {
     pub label: Option<&'a str>
 }
 
-pub struct CommandBuffer {
+#[derive(Copy, Clone)]
+pub struct RenderingAttachment<'a> {
+    pub image_view: &'a ImageView,
+    pub layout: vk::ImageLayout,
+    pub load_op: vk::AttachmentLoadOp,
+    pub store_op: vk::AttachmentStoreOp,
+    pub clear_value: vk::ClearValue
+}
+
+impl RenderingAttachment<'_> {
+    #[inline]
+    fn rendering_attachment_info(&self) -> vk::RenderingAttachmentInfo<'static> {
+        vk::RenderingAttachmentInfo::default()
+            .image_view(**self.image_view)
+            .image_layout(self.layout)
+            .load_op(self.load_op)
+            .store_op(self.store_op)
+            .clear_value(self.clear_value)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct RenderingDesc<'a> {
+    pub render_area: vk::Rect2D,
+    pub layer_count: u32,
+    pub color_attachments: &'a [RenderingAttachment<'a>],
+    pub depth_attachment: Option<RenderingAttachment<'a>>,
+    pub stencil_attachment: Option<RenderingAttachment<'a>>
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+pub trait CommandBufferState: private::Sealed {}
+
+pub struct Initial;
+pub struct Recording;
+pub struct Executable;
+pub struct Invalid;
+
+impl private::Sealed for Initial {}
+impl private::Sealed for Recording {}
+impl private::Sealed for Executable {}
+impl private::Sealed for Invalid {}
+
+impl CommandBufferState for Initial {}
+impl CommandBufferState for Recording {}
+impl CommandBufferState for Executable {}
+impl CommandBufferState for Invalid {}
+
+struct RawCommandBuffer {
     command_buffer: vk::CommandBuffer,
+    one_time_submit: bool,
+    //Resources referenced by recorded commands that are released once the command buffer is reset or dropped
+    retained: Vec<Arc<dyn Any + Send + Sync>>,
     command_pool: Arc<CommandPool>,
     device: Device
 }
 
-impl CommandBuffer {
-    pub fn new(device: Device, command_pool: Arc<CommandPool>, desc: &CommandBufferDesc) -> Result<Self> {
-        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::default().command_pool(**command_pool).command_buffer_count(1);
+impl RawCommandBuffer {
+    fn reset(&mut self, flags: vk::CommandBufferResetFlags) -> Result<()> {
+        if !self.command_pool.flags().contains(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER) {
+            anyhow::bail!("Command buffers can only be reset individually if their pool was created with RESET_COMMAND_BUFFER");
+        }
 
-        let command_buffer = unsafe { device.loader().allocate_command_buffers(&command_buffer_allocate_info)? }[0];
+        unsafe { self.device.loader().reset_command_buffer(self.command_buffer, flags) }?;
+        self.retained.clear();
 
-        if let Some(label) = desc.label {
-            unsafe { debug_utils::set_object_name(&device, command_buffer, label) }?;
-        }
+        Ok(())
+    }
+}
 
-        Ok(Self {
+impl Drop for RawCommandBuffer {
+    fn drop(&mut self) {
+        self.command_pool.retire(self.command_buffer);
+    }
+}
+
+pub struct CommandBuffer<S: CommandBufferState = Initial> {
+    raw: RawCommandBuffer,
+    _state: PhantomData<S>
+}
+
+impl<S: CommandBufferState> CommandBuffer<S> {
+    #[inline]
+    fn into_state<T: CommandBufferState>(self) -> CommandBuffer<T> {
+        CommandBuffer { raw: self.raw, _state: PhantomData }
+    }
+
+    #[inline]
+    pub fn device(&self) -> &Device {
+        &self.raw.device
+    }
+}
+
+impl CommandBuffer<Initial> {
+    pub fn new(device: Device, command_pool: Arc<CommandPool>, desc: &CommandBufferDesc) -> Result<Self> {
+        let command_buffer = command_pool.allocate()?;
+
+        let raw = RawCommandBuffer {
             command_buffer,
+            one_time_submit: false,
+            retained: Vec::new(),
             command_pool,
             device
-        })
+        };
+
+        if let Some(label) = desc.label {
+            unsafe { debug_utils::set_object_name(&raw.device, command_buffer, label) }?;
+        }
+
+        Ok(Self { raw, _state: PhantomData })
+    }
+
+    #[inline]
+    pub fn begin(mut self, flags: vk::CommandBufferUsageFlags) -> VkResult<CommandBuffer<Recording>> {
+        unsafe {
+            self.raw
+                .device
+                .loader()
+                .begin_command_buffer(self.raw.command_buffer, &vk::CommandBufferBeginInfo::default().flags(flags))
+        }?;
+
+        self.raw.one_time_submit = flags.contains(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+        Ok(self.into_state())
+    }
+}
+
+impl CommandBuffer<Recording> {
+    //A command buffer that fails to end is invalid and gets freed
+    #[inline]
+    pub fn end(self) -> VkResult<CommandBuffer<Executable>> {
+        unsafe { self.raw.device.loader().end_command_buffer(self.raw.command_buffer) }?;
+
+        Ok(self.into_state())
+    }
+
+    //Keeps the resource alive until the command buffer is reset or dropped, which happens after the submission completed
+    #[inline]
+    pub fn retain(&mut self, resource: Arc<dyn Any + Send + Sync>) {
+        self.raw.retained.push(resource);
+    }
+
+    #[inline]
+    pub fn copy_buffer(&mut self, src: &Buffer, dst: &Buffer, regions: &[vk::BufferCopy]) {
+        unsafe { self.raw.device.loader().cmd_copy_buffer(self.raw.command_buffer, **src, **dst, regions) }
+    }
+
+    #[inline]
+    pub fn copy_buffer_to_image(&mut self, src: &Buffer, dst: &Image, dst_layout: vk::ImageLayout, regions: &[vk::BufferImageCopy]) {
+        unsafe { self.raw.device.loader().cmd_copy_buffer_to_image(self.raw.command_buffer, **src, **dst, dst_layout, regions) }
+    }
+
+    #[inline]
+    pub fn copy_image_to_buffer(&mut self, src: &Image, src_layout: vk::ImageLayout, dst: &Buffer, regions: &[vk::BufferImageCopy]) {
+        unsafe { self.raw.device.loader().cmd_copy_image_to_buffer(self.raw.command_buffer, **src, src_layout, **dst, regions) }
+    }
+
+    #[inline]
+    pub fn copy_image(&mut self, src: &Image, src_layout: vk::ImageLayout, dst: &Image, dst_layout: vk::ImageLayout, regions: &[vk::ImageCopy]) {
+        unsafe { self.raw.device.loader().cmd_copy_image(self.raw.command_buffer, **src, src_layout, **dst, dst_layout, regions) }
+    }
+
+    #[inline]
+    pub fn fill_buffer(&mut self, dst: &Buffer, offset: u64, size: u64, data: u32) {
+        unsafe { self.raw.device.loader().cmd_fill_buffer(self.raw.command_buffer, **dst, offset, size, data) }
+    }
+
+    #[inline]
+    pub fn clear_color_image(&mut self, image: &Image, layout: vk::ImageLayout, color: &vk::ClearColorValue, ranges: &[vk::ImageSubresourceRange]) {
+        unsafe { self.raw.device.loader().cmd_clear_color_image(self.raw.command_buffer, **image, layout, color, ranges) }
+    }
+
+    #[inline]
+    pub fn clear_depth_stencil_image(&mut self, image: &Image, layout: vk::ImageLayout, depth_stencil: &vk::ClearDepthStencilValue, ranges: &[vk::ImageSubresourceRange]) {
+        unsafe {
+            self.raw
+                .device
+                .loader()
+                .cmd_clear_depth_stencil_image(self.raw.command_buffer, **image, layout, depth_stencil, ranges)
+        }
+    }
+
+    #[inline]
+    pub fn dispatch(&mut self, group_count_x: u32, group_count_y: u32, group_count_z: u32) {
+        unsafe { self.raw.device.loader().cmd_dispatch(self.raw.command_buffer, group_count_x, group_count_y, group_count_z) }
+    }
+
+    #[inline]
+    pub fn dispatch_indirect(&mut self, buffer: &Buffer, offset: u64) {
+        unsafe { self.raw.device.loader().cmd_dispatch_indirect(self.raw.command_buffer, **buffer, offset) }
+    }
+
+    #[inline]
+    pub fn draw(&mut self, vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32) {
+        unsafe {
+            self.raw
+                .device
+                .loader()
+                .cmd_draw(self.raw.command_buffer, vertex_count, instance_count, first_vertex, first_instance)
+        }
+    }
+
+    #[inline]
+    pub fn draw_indexed(&mut self, index_count: u32, instance_count: u32, first_index: u32, vertex_offset: i32, first_instance: u32) {
+        unsafe {
+            self.raw
+                .device
+                .loader()
+                .cmd_draw_indexed(self.raw.command_buffer, index_count, instance_count, first_index, vertex_offset, first_instance)
+        }
+    }
+
+    #[inline]
+    pub fn draw_indirect(&mut self, buffer: &Buffer, offset: u64, draw_count: u32, stride: u32) {
+        unsafe { self.raw.device.loader().cmd_draw_indirect(self.raw.command_buffer, **buffer, offset, draw_count, stride) }
+    }
+
+    #[inline]
+    pub fn draw_indexed_indirect(&mut self, buffer: &Buffer, offset: u64, draw_count: u32, stride: u32) {
+        unsafe { self.raw.device.loader().cmd_draw_indexed_indirect(self.raw.command_buffer, **buffer, offset, draw_count, stride) }
+    }
+
+    //The image views of the rendering info have to be valid
+    #[inline]
+    pub unsafe fn begin_rendering_raw(&mut self, rendering_info: &vk::RenderingInfo) {
+        self.raw.device.loader().cmd_begin_rendering(self.raw.command_buffer, rendering_info)
+    }
+
+    pub fn begin_rendering(&mut self, desc: &RenderingDesc) {
+        let color_attachments: Vec<_> = desc.color_attachments.iter().map(RenderingAttachment::rendering_attachment_info).collect();
+        let depth_attachment = desc.depth_attachment.as_ref().map(RenderingAttachment::rendering_attachment_info);
+        let stencil_attachment = desc.stencil_attachment.as_ref().map(RenderingAttachment::rendering_attachment_info);
+
+        let mut rendering_info = vk::RenderingInfo::default()
+            .render_area(desc.render_area)
+            .layer_count(desc.layer_count)
+            .color_attachments(&color_attachments);
+        if let Some(depth_attachment) = &depth_attachment {
+            rendering_info = rendering_info.depth_attachment(depth_attachment);
+        }
+        if let Some(stencil_attachment) = &stencil_attachment {
+            rendering_info = rendering_info.stencil_attachment(stencil_attachment);
+        }
+
+        unsafe { self.begin_rendering_raw(&rendering_info) }
+    }
+
+    #[inline]
+    pub fn end_rendering(&mut self) {
+        unsafe { self.raw.device.loader().cmd_end_rendering(self.raw.command_buffer) }
+    }
+
+    //The resources referenced by the barriers have to be valid
+    #[inline]
+    pub unsafe fn pipeline_barrier2(&mut self, dependency_info: &vk::DependencyInfo) {
+        self.raw.device.loader().cmd_pipeline_barrier2(self.raw.command_buffer, dependency_info)
+    }
+
+    //The barriers are derived from the tracked state of the resource, so command buffers have to be submitted to one queue
+    //in the order they were recorded in
+    #[inline]
+    pub fn transition(&mut self, image: &Image, access: Access) {
+        self.record_image_barriers(&image.transition_range(image.full_range(), access));
+    }
+
+    //Fails if the range is not part of the image
+    pub fn transition_subresources(&mut self, image: &Image, subresource_range: vk::ImageSubresourceRange, access: Access) -> Result<()> {
+        self.record_image_barriers(&image.transition_barriers(subresource_range, access)?);
+        Ok(())
+    }
+
+    #[inline]
+    fn record_image_barriers(&mut self, image_memory_barriers: &[vk::ImageMemoryBarrier2]) {
+        if !image_memory_barriers.is_empty() {
+            unsafe { self.pipeline_barrier2(&vk::DependencyInfo::default().image_memory_barriers(image_memory_barriers)) };
+        }
+    }
+
+    #[inline]
+    pub fn transition_buffer(&mut self, buffer: &Buffer, access: Access) {
+        if let Some(buffer_memory_barrier) = buffer.transition_barrier(access) {
+            unsafe { self.pipeline_barrier2(&vk::DependencyInfo::default().buffer_memory_barriers(slice::from_ref(&buffer_memory_barrier))) };
+        }
+    }
+
+    //The range has to be covered by a push constant range of the layout with the same stages
+    pub fn push_constants<T: Copy>(&mut self, layout: &PipelineLayout, stage_flags: vk::ShaderStageFlags, offset: u32, data: &[T]) -> Result<()> {
+        let size = mem::size_of_val(data) as u64;
+        let is_covered = layout
+            .push_constant_ranges()
+            .iter()
+            .any(|range| range.stage_flags.contains(stage_flags) && offset >= range.offset && offset as u64 + size <= range.offset as u64 + range.size as u64);
+        if !is_covered {
+            anyhow::bail!("Push constants ({:?}, {}+{}) are not covered by the pipeline layout", stage_flags, offset, size);
+        }
+
+        unsafe {
+            let bytes = slice::from_raw_parts(data.as_ptr().cast::<u8>(), mem::size_of_val(data));
+            self.raw.device.loader().cmd_push_constants(self.raw.command_buffer, **layout, stage_flags, offset, bytes)
+        }
+
+        Ok(())
+    }
+
+    //The pipeline has to be valid
+    #[inline]
+    pub unsafe fn bind_pipeline(&mut self, bind_point: vk::PipelineBindPoint, pipeline: vk::Pipeline) {
+        self.raw.device.loader().cmd_bind_pipeline(self.raw.command_buffer, bind_point, pipeline)
+    }
+
+    #[inline]
+    pub fn bind_compute_pipeline(&mut self, pipeline: &ComputePipeline) {
+        unsafe { self.bind_pipeline(vk::PipelineBindPoint::COMPUTE, **pipeline) }
+    }
+
+    #[inline]
+    pub fn bind_graphics_pipeline(&mut self, pipeline: &GraphicsPipeline) {
+        unsafe { self.bind_pipeline(vk::PipelineBindPoint::GRAPHICS, **pipeline) }
+    }
+
+    //The layout and descriptor sets have to be valid and compatible
+    #[inline]
+    pub unsafe fn bind_descriptor_sets(
+        &mut self,
+        bind_point: vk::PipelineBindPoint,
+        layout: vk::PipelineLayout,
+        first_set: u32,
+        descriptor_sets: &[vk::DescriptorSet],
+        dynamic_offsets: &[u32]
+    ) {
+        self.raw
+            .device
+            .loader()
+            .cmd_bind_descriptor_sets(self.raw.command_buffer, bind_point, layout, first_set, descriptor_sets, dynamic_offsets)
+    }
+
+    pub fn bind_bindless_heap(&mut self, bind_point: vk::PipelineBindPoint, layout: &PipelineLayout, set: u32, bindless_heap: &BindlessHeap) -> Result<()> {
+        if !layout.uses_set_layout(set, bindless_heap.descriptor_set_layout()) {
+            anyhow::bail!("Set {} of the pipeline layout is not the layout of the bindless heap", set);
+        }
+
+        unsafe { self.bind_descriptor_sets(bind_point, **layout, set, slice::from_ref(&bindless_heap.descriptor_set()), &[]) };
+
+        Ok(())
+    }
+
+    pub fn bind_vertex_buffers(&mut self, first_binding: u32, buffers: &[(&Buffer, u64)]) {
+        let (buffers, offsets): (Vec<_>, Vec<_>) = buffers.iter().map(|(buffer, offset)| (***buffer, *offset)).unzip();
+
+        unsafe { self.raw.device.loader().cmd_bind_vertex_buffers(self.raw.command_buffer, first_binding, &buffers, &offsets) }
+    }
+
+    #[inline]
+    pub fn bind_index_buffer(&mut self, buffer: &Buffer, offset: u64, index_type: vk::IndexType) {
+        unsafe { self.raw.device.loader().cmd_bind_index_buffer(self.raw.command_buffer, **buffer, offset, index_type) }
+    }
+
+    #[inline]
+    pub fn set_viewport(&mut self, first_viewport: u32, viewports: &[vk::Viewport]) {
+        unsafe { self.raw.device.loader().cmd_set_viewport(self.raw.command_buffer, first_viewport, viewports) }
+    }
+
+    #[inline]
+    pub fn set_scissor(&mut self, first_scissor: u32, scissors: &[vk::Rect2D]) {
+        unsafe { self.raw.device.loader().cmd_set_scissor(self.raw.command_buffer, first_scissor, scissors) }
+    }
+}
+
+impl CommandBuffer<Executable> {
+    //Requires a command pool created with RESET_COMMAND_BUFFER
+    #[inline]
+    pub fn reset(mut self, flags: vk::CommandBufferResetFlags) -> Result<CommandBuffer<Initial>> {
+        self.raw.reset(flags)?;
+
+        Ok(self.into_state())
+    }
+}
+
+impl CommandBuffer<Invalid> {
+    //Requires a command pool created with RESET_COMMAND_BUFFER
+    #[inline]
+    pub fn reset(mut self, flags: vk::CommandBufferResetFlags) -> Result<CommandBuffer<Initial>> {
+        self.raw.reset(flags)?;
+
+        Ok(self.into_state())
     }
 }
 
-impl Deref for CommandBuffer {
+impl<S: CommandBufferState> Deref for CommandBuffer<S> {
     type Target = vk::CommandBuffer;
 
     fn deref(&self) -> &Self::Target {
-        &self.command_buffer
+        &self.raw.command_buffer
+    }
+}
+
+pub enum CompletedCommandBuffer {
+    Executable(CommandBuffer<Executable>),
+    //Command buffers recorded with ONE_TIME_SUBMIT become invalid once they completed
+    Invalid(CommandBuffer<Invalid>)
+}
+
+impl CompletedCommandBuffer {
+    #[inline]
+    fn new(command_buffer: CommandBuffer<Executable>) -> Self {
+        if command_buffer.raw.one_time_submit {
+            Self::Invalid(command_buffer.into_state())
+        } else {
+            Self::Executable(command_buffer)
+        }
+    }
+
+    #[inline]
+    pub fn reset(self, flags: vk::CommandBufferResetFlags) -> Result<CommandBuffer<Initial>> {
+        match self {
+            Self::Executable(command_buffer) => command_buffer.reset(flags),
+            Self::Invalid(command_buffer) => command_buffer.reset(flags)
+        }
     }
 }
 
-impl Drop for CommandBuffer {
+//Command buffers that were submitted to a queue, they are handed out again once the submission completed.
+//Dropping it blocks until the submission completed.
+pub struct PendingCommandBuffers {
+    command_buffers: Vec<CommandBuffer<Executable>>,
+    timeline_semaphore: vk::Semaphore,
+    submission_value: u64
+}
+
+impl PendingCommandBuffers {
+    #[inline]
+    pub(crate) fn new(command_buffers: Vec<CommandBuffer<Executable>>, timeline_semaphore: vk::Semaphore, submission_value: u64) -> Self {
+        Self {
+            command_buffers,
+            timeline_semaphore,
+            submission_value
+        }
+    }
+
+    #[inline]
+    pub fn submission_value(&self) -> u64 {
+        self.submission_value
+    }
+
+    pub fn is_complete(&self) -> VkResult<bool> {
+        match self.command_buffers.first() {
+            Some(command_buffer) => unsafe { Ok(command_buffer.raw.device.loader().get_semaphore_counter_value(self.timeline_semaphore)? >= self.submission_value) },
+            None => Ok(true)
+        }
+    }
+
+    unsafe fn wait_for(&self, timeout: u64) -> VkResult<()> {
+        match self.command_buffers.first() {
+            Some(command_buffer) => {
+                command_buffer.raw.device.loader().wait_semaphores(
+                    &vk::SemaphoreWaitInfo::default()
+                        .semaphores(slice::from_ref(&self.timeline_semaphore))
+                        .values(slice::from_ref(&self.submission_value)),
+                    timeout
+                )
+            }
+            None => Ok(())
+        }
+    }
+
+    //Hands the pending command buffers back together with the result if the wait failed,
+    //TIMEOUT if the submission did not complete within the timeout
+    pub fn wait(mut self, timeout: u64) -> Result<Vec<CompletedCommandBuffer>, (Self, vk::Result)> {
+        match unsafe { self.wait_for(timeout) } {
+            Ok(()) => Ok(mem::take(&mut self.command_buffers).into_iter().map(CompletedCommandBuffer::new).collect()),
+            Err(result) => Err((self, result))
+        }
+    }
+
+    #[inline]
+    pub fn try_complete(self) -> Result<Vec<CompletedCommandBuffer>, (Self, vk::Result)> {
+        self.wait(0)
+    }
+}
+
+impl Drop for PendingCommandBuffers {
     fn drop(&mut self) {
-        unsafe { self.device.loader().free_command_buffers(**self.command_pool, slice::from_ref(&self.command_buffer)) }
+        if !self.command_buffers.is_empty() {
+            let _ = unsafe { self.wait_for(u64::MAX) };
+        }
     }
 }
@@ -1,9 +1,9 @@
-use std::ops::Deref;
+use std::{ops::Deref, slice, sync::Mutex};
 
 use anyhow::Result;
-use ash::vk;
+use ash::{prelude::VkResult, vk};
 
-use crate::backend::{util::debug_utils, Device};
+use crate::backend::{deletion_queue::DeferredDestruction, util::debug_utils, Device, SubmissionValues};
 
 #[derive(Copy, Clone, Debug)]
 pub struct CommandPoolDesc<'a> {
@@ -12,8 +12,13 @@ pub struct CommandPoolDesc<'a> {
     pub label: Option<&'a str>
 }
 
+//Allocating and freeing command buffers is synchronized by the pool, recording command buffers of the same pool
+//on multiple threads at the same time is not allowed
 pub struct CommandPool {
     command_pool: vk::CommandPool,
+    flags: vk::CommandPoolCreateFlags,
+    //Dropped command buffers that may still be referenced by submitted work
+    retired: Mutex<Vec<(SubmissionValues, vk::CommandBuffer)>>,
     device: Device
 }
 
@@ -27,7 +32,61 @@ impl CommandPool {
             unsafe { debug_utils::set_object_name(&device, command_pool, label) }?;
         }
 
-        Ok(Self { command_pool, device })
+        Ok(Self {
+            command_pool,
+            flags: desc.flags,
+            retired: Mutex::new(Vec::new()),
+            device
+        })
+    }
+
+    #[inline]
+    pub fn flags(&self) -> vk::CommandPoolCreateFlags {
+        self.flags
+    }
+
+    unsafe fn free_completed(&self, retired: &mut Vec<(SubmissionValues, vk::CommandBuffer)>) -> VkResult<usize> {
+        if retired.is_empty() {
+            return Ok(0)
+        }
+
+        let completed = self.device.completed_submission_values()?;
+        let count = retired.len();
+
+        retired.retain(|(submission_values, command_buffer)| {
+            if submission_values.is_completed(&completed) {
+                self.device.loader().free_command_buffers(self.command_pool, slice::from_ref(command_buffer));
+                false
+            } else {
+                true
+            }
+        });
+
+        Ok(count - retired.len())
+    }
+
+    //Frees the retired command buffers whose submissions completed before allocating a new one
+    pub(crate) fn allocate(&self) -> VkResult<vk::CommandBuffer> {
+        let mut retired = self.retired.lock().unwrap();
+
+        unsafe {
+            self.free_completed(&mut retired)?;
+
+            let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::default().command_pool(self.command_pool).command_buffer_count(1);
+            Ok(self.device.loader().allocate_command_buffers(&command_buffer_allocate_info)?[0])
+        }
+    }
+
+    //The command buffer is freed by the pool once every submission made so far completed
+    #[inline]
+    pub(crate) fn retire(&self, command_buffer: vk::CommandBuffer) {
+        self.retired.lock().unwrap().push((self.device.last_submission_values(), command_buffer));
+    }
+
+    //Frees the dropped command buffers whose submissions completed, returns the number of freed command buffers
+    #[inline]
+    pub fn free_retired(&self) -> VkResult<usize> {
+        unsafe { self.free_completed(&mut self.retired.lock().unwrap()) }
     }
 }
 
@@ -41,9 +100,8 @@ impl Deref for CommandPool {
 }
 
 impl Drop for CommandPool {
+    //Retired command buffers are freed together with the pool
     fn drop(&mut self) {
-        unsafe {
-            self.device.loader().destroy_command_pool(self.command_pool, None);
-        }
+        self.device.defer_destruction(DeferredDestruction::CommandPool(self.command_pool));
     }
 }
@@ -1,4 +1,5 @@
 mod command_buffer;
 mod command_pool;
 
+pub use command_buffer::*;
 pub use command_pool::*;
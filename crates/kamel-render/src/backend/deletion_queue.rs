@@ -0,0 +1,89 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use ash::vk;
+use vk_mem_alloc::{Allocation, Allocator};
+
+use crate::backend::SubmissionValues;
+
+//Raw handles of dropped wrappers that may still be referenced by submitted work.
+//Nothing else references the handles, so they can be destroyed on any thread.
+pub(crate) enum DeferredDestruction {
+    Fence(vk::Fence),
+    Semaphore(vk::Semaphore),
+    //Frees the command buffers that are still allocated from the pool
+    CommandPool(vk::CommandPool),
+    //The allocation is freed together with the resource, aliased resources have none
+    Buffer(vk::Buffer, Option<Allocation>),
+    Image(vk::Image, Option<Allocation>),
+    ImageView(vk::ImageView),
+    Sampler(vk::Sampler),
+    Pipeline(vk::Pipeline),
+    //Memory without a resource, like memory blocks
+    Allocation(Allocation),
+    //Frees the descriptor sets that are still allocated from the pool
+    DescriptorPool(vk::DescriptorPool)
+}
+
+unsafe impl Send for DeferredDestruction {}
+
+impl DeferredDestruction {
+    unsafe fn destroy(self, loader: &ash::Device, allocator: Allocator) {
+        match self {
+            Self::Fence(fence) => loader.destroy_fence(fence, None),
+            Self::Semaphore(semaphore) => loader.destroy_semaphore(semaphore, None),
+            Self::CommandPool(command_pool) => loader.destroy_command_pool(command_pool, None),
+            Self::Buffer(buffer, Some(allocation)) => vk_mem_alloc::destroy_buffer(allocator, buffer, allocation),
+            Self::Buffer(buffer, None) => loader.destroy_buffer(buffer, None),
+            Self::Image(image, Some(allocation)) => vk_mem_alloc::destroy_image(allocator, image, allocation),
+            Self::Image(image, None) => loader.destroy_image(image, None),
+            Self::ImageView(image_view) => loader.destroy_image_view(image_view, None),
+            Self::Sampler(sampler) => loader.destroy_sampler(sampler, None),
+            Self::Pipeline(pipeline) => loader.destroy_pipeline(pipeline, None),
+            Self::Allocation(allocation) => vk_mem_alloc::free_memory(allocator, allocation),
+            Self::DescriptorPool(descriptor_pool) => loader.destroy_descriptor_pool(descriptor_pool, None)
+        }
+    }
+}
+
+//Entries are destroyed in the order they were parked
+pub(crate) struct DeletionQueue {
+    queue: Mutex<VecDeque<(SubmissionValues, DeferredDestruction)>>
+}
+
+impl DeletionQueue {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self { queue: Mutex::new(VecDeque::new()) }
+    }
+
+    #[inline]
+    pub(crate) fn push(&self, submission_values: SubmissionValues, destruction: DeferredDestruction) {
+        self.queue.lock().unwrap().push_back((submission_values, destruction));
+    }
+
+    //Destroys every entry whose submissions completed, returns the number of destroyed entries
+    pub(crate) unsafe fn process(&self, loader: &ash::Device, allocator: Allocator, completed: &SubmissionValues) -> usize {
+        let mut queue = self.queue.lock().unwrap();
+        let mut count = 0;
+
+        while queue.front().map_or(false, |(submission_values, _)| submission_values.is_completed(completed)) {
+            let (_, destruction) = queue.pop_front().unwrap();
+            destruction.destroy(loader, allocator);
+            count += 1;
+        }
+
+        count
+    }
+
+    //Only valid once the device is idle
+    pub(crate) unsafe fn flush(&self, loader: &ash::Device, allocator: Allocator) {
+        for (_, destruction) in self.queue.lock().unwrap().drain(..) {
+            destruction.destroy(loader, allocator);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+}
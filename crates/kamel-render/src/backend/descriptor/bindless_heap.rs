@@ -0,0 +1,368 @@
+use std::{collections::VecDeque, slice, sync::Mutex};
+
+use anyhow::Result;
+use ash::vk;
+
+use crate::backend::{deletion_queue::DeferredDestruction, util::debug_utils, Buffer, DescriptorSetLayoutBindingDesc, Device, ImageView, Sampler, SubmissionValues};
+
+pub const BINDLESS_SAMPLED_IMAGE_BINDING: u32 = 0;
+pub const BINDLESS_STORAGE_IMAGE_BINDING: u32 = 1;
+pub const BINDLESS_STORAGE_BUFFER_BINDING: u32 = 2;
+pub const BINDLESS_SAMPLER_BINDING: u32 = 3;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BindlessResourceType {
+    SampledImage,
+    StorageImage,
+    StorageBuffer,
+    Sampler
+}
+
+impl BindlessResourceType {
+    const ALL: [Self; 4] = [Self::SampledImage, Self::StorageImage, Self::StorageBuffer, Self::Sampler];
+
+    #[inline]
+    pub fn binding(self) -> u32 {
+        match self {
+            Self::SampledImage => BINDLESS_SAMPLED_IMAGE_BINDING,
+            Self::StorageImage => BINDLESS_STORAGE_IMAGE_BINDING,
+            Self::StorageBuffer => BINDLESS_STORAGE_BUFFER_BINDING,
+            Self::Sampler => BINDLESS_SAMPLER_BINDING
+        }
+    }
+
+    #[inline]
+    pub fn descriptor_type(self) -> vk::DescriptorType {
+        match self {
+            Self::SampledImage => vk::DescriptorType::SAMPLED_IMAGE,
+            Self::StorageImage => vk::DescriptorType::STORAGE_IMAGE,
+            Self::StorageBuffer => vk::DescriptorType::STORAGE_BUFFER,
+            Self::Sampler => vk::DescriptorType::SAMPLER
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BindlessHeapDesc<'a> {
+    pub sampled_image_count: u32,
+    pub storage_image_count: u32,
+    pub storage_buffer_count: u32,
+    pub sampler_count: u32,
+    pub label: Option<&'a str>
+}
+
+impl BindlessHeapDesc<'_> {
+    #[inline]
+    fn count(&self, resource_type: BindlessResourceType) -> u32 {
+        match resource_type {
+            BindlessResourceType::SampledImage => self.sampled_image_count,
+            BindlessResourceType::StorageImage => self.storage_image_count,
+            BindlessResourceType::StorageBuffer => self.storage_buffer_count,
+            BindlessResourceType::Sampler => self.sampler_count
+        }
+    }
+}
+
+struct Slots {
+    capacity: u32,
+    next: u32,
+    free: Vec<u32>,
+    //Indexed by slot, false once the slot has been freed
+    allocated: Vec<bool>,
+    //Freed indices that may still be accessed by submitted work
+    retired: VecDeque<(SubmissionValues, u32)>
+}
+
+impl Slots {
+    #[inline]
+    fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            next: 0,
+            free: Vec::new(),
+            allocated: Vec::new(),
+            retired: VecDeque::new()
+        }
+    }
+
+    #[inline]
+    fn allocate(&mut self) -> Option<u32> {
+        let index = self.free.pop().or_else(|| {
+            if self.next < self.capacity {
+                self.next += 1;
+                self.allocated.push(false);
+                Some(self.next - 1)
+            } else {
+                None
+            }
+        })?;
+
+        self.allocated[index as usize] = true;
+        Some(index)
+    }
+
+    fn retire(&mut self, submission_values: SubmissionValues, index: u32) -> Result<()> {
+        match self.allocated.get_mut(index as usize) {
+            Some(allocated) if *allocated => *allocated = false,
+            _ => anyhow::bail!("Bindless index {} is not allocated, it was either never allocated or freed twice", index)
+        }
+
+        self.retired.push_back((submission_values, index));
+        Ok(())
+    }
+
+    #[inline]
+    fn collect(&mut self, completed: &SubmissionValues) {
+        while let Some((values, index)) = self.retired.front() {
+            if !values.is_completed(completed) {
+                break
+            }
+
+            self.free.push(*index);
+            self.retired.pop_front();
+        }
+    }
+}
+
+//Checks the descriptor indexing features and limits a bindless heap with the given capacities relies on
+pub fn check_bindless_support(device: &Device, desc: &BindlessHeapDesc) -> Result<()> {
+    let features_12 = &device.enabled_features().features_12;
+    let properties_12 = &device.properties().properties_12;
+
+    let mut missing = Vec::new();
+    for (name, enabled) in [
+        ("descriptorIndexing", features_12.descriptor_indexing),
+        ("runtimeDescriptorArray", features_12.runtime_descriptor_array),
+        ("descriptorBindingPartiallyBound", features_12.descriptor_binding_partially_bound),
+        ("descriptorBindingSampledImageUpdateAfterBind", features_12.descriptor_binding_sampled_image_update_after_bind),
+        ("descriptorBindingStorageImageUpdateAfterBind", features_12.descriptor_binding_storage_image_update_after_bind),
+        ("descriptorBindingStorageBufferUpdateAfterBind", features_12.descriptor_binding_storage_buffer_update_after_bind),
+        ("shaderSampledImageArrayNonUniformIndexing", features_12.shader_sampled_image_array_non_uniform_indexing),
+        ("shaderStorageImageArrayNonUniformIndexing", features_12.shader_storage_image_array_non_uniform_indexing),
+        ("shaderStorageBufferArrayNonUniformIndexing", features_12.shader_storage_buffer_array_non_uniform_indexing)
+    ] {
+        if enabled == vk::FALSE {
+            missing.push(name);
+        }
+    }
+
+    if !missing.is_empty() {
+        anyhow::bail!("Bindless heap requires the following Vulkan 1.2 features to be enabled: {}", missing.join(", "));
+    }
+
+    let mut exceeded = Vec::new();
+    for (name, requested, limit) in [
+        (
+            "maxDescriptorSetUpdateAfterBindSampledImages",
+            desc.sampled_image_count,
+            properties_12.max_descriptor_set_update_after_bind_sampled_images
+        ),
+        (
+            "maxPerStageDescriptorUpdateAfterBindSampledImages",
+            desc.sampled_image_count,
+            properties_12.max_per_stage_descriptor_update_after_bind_sampled_images
+        ),
+        (
+            "maxDescriptorSetUpdateAfterBindStorageImages",
+            desc.storage_image_count,
+            properties_12.max_descriptor_set_update_after_bind_storage_images
+        ),
+        (
+            "maxPerStageDescriptorUpdateAfterBindStorageImages",
+            desc.storage_image_count,
+            properties_12.max_per_stage_descriptor_update_after_bind_storage_images
+        ),
+        (
+            "maxDescriptorSetUpdateAfterBindStorageBuffers",
+            desc.storage_buffer_count,
+            properties_12.max_descriptor_set_update_after_bind_storage_buffers
+        ),
+        (
+            "maxPerStageDescriptorUpdateAfterBindStorageBuffers",
+            desc.storage_buffer_count,
+            properties_12.max_per_stage_descriptor_update_after_bind_storage_buffers
+        ),
+        (
+            "maxDescriptorSetUpdateAfterBindSamplers",
+            desc.sampler_count,
+            properties_12.max_descriptor_set_update_after_bind_samplers
+        ),
+        (
+            "maxPerStageDescriptorUpdateAfterBindSamplers",
+            desc.sampler_count,
+            properties_12.max_per_stage_descriptor_update_after_bind_samplers
+        ),
+        (
+            "maxPerStageUpdateAfterBindResources",
+            desc.sampled_image_count
+                .saturating_add(desc.storage_image_count)
+                .saturating_add(desc.storage_buffer_count)
+                .saturating_add(desc.sampler_count),
+            properties_12.max_per_stage_update_after_bind_resources
+        )
+    ] {
+        if requested > limit {
+            exceeded.push(format!("{} ({} requested, {} supported)", name, requested, limit));
+        }
+    }
+
+    if !exceeded.is_empty() {
+        anyhow::bail!("Bindless heap exceeds the following device limits: {}", exceeded.join(", "));
+    }
+
+    Ok(())
+}
+
+pub struct BindlessHeap {
+    descriptor_pool: vk::DescriptorPool,
+    //Owned by the layout cache of the device, pipelines get it through explicit set layouts
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_set: vk::DescriptorSet,
+
+    //Also serializes descriptor writes, the descriptor set has to be externally synchronized
+    slots: Mutex<[Slots; 4]>,
+
+    device: Device
+}
+
+impl BindlessHeap {
+    pub fn new(device: Device, desc: &BindlessHeapDesc) -> Result<Self> {
+        check_bindless_support(&device, desc)?;
+
+        let bindings: Vec<_> = BindlessResourceType::ALL
+            .iter()
+            .map(|resource_type| {
+                DescriptorSetLayoutBindingDesc {
+                    binding: resource_type.binding(),
+                    descriptor_type: resource_type.descriptor_type(),
+                    count: desc.count(*resource_type),
+                    stages: vk::ShaderStageFlags::ALL,
+                    flags: vk::DescriptorBindingFlags::PARTIALLY_BOUND | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+                }
+            })
+            .collect();
+        let descriptor_set_layout = device.get_or_create_descriptor_set_layout(&bindings)?;
+
+        let mut heap = Self {
+            descriptor_pool: vk::DescriptorPool::null(),
+            descriptor_set_layout,
+            descriptor_set: vk::DescriptorSet::null(),
+
+            slots: Mutex::new(BindlessResourceType::ALL.map(|resource_type| Slots::new(desc.count(resource_type)))),
+
+            device
+        };
+
+        let loader = heap.device.loader();
+
+        let pool_sizes: Vec<_> = BindlessResourceType::ALL
+            .iter()
+            .filter(|resource_type| desc.count(**resource_type) > 0)
+            .map(|resource_type| vk::DescriptorPoolSize::default().ty(resource_type.descriptor_type()).descriptor_count(desc.count(*resource_type)))
+            .collect();
+
+        heap.descriptor_pool = unsafe {
+            loader.create_descriptor_pool(
+                &vk::DescriptorPoolCreateInfo::default()
+                    .flags(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND)
+                    .max_sets(1)
+                    .pool_sizes(&pool_sizes),
+                None
+            )
+        }?;
+
+        heap.descriptor_set = unsafe {
+            loader.allocate_descriptor_sets(
+                &vk::DescriptorSetAllocateInfo::default()
+                    .descriptor_pool(heap.descriptor_pool)
+                    .set_layouts(slice::from_ref(&heap.descriptor_set_layout))
+            )
+        }?[0];
+
+        if let Some(label) = desc.label {
+            unsafe { debug_utils::set_object_name(&heap.device, heap.descriptor_set, label) }?;
+        }
+
+        Ok(heap)
+    }
+
+    fn allocate(&self, resource_type: BindlessResourceType, write: impl FnOnce(vk::WriteDescriptorSet) -> vk::WriteDescriptorSet) -> Result<u32> {
+        let completed = unsafe { self.device.completed_submission_values() }?;
+
+        let mut slots = self.slots.lock().unwrap();
+        let slots = &mut slots[resource_type as usize];
+        slots.collect(&completed);
+
+        let index = slots
+            .allocate()
+            .ok_or_else(|| anyhow::anyhow!("Bindless heap is out of {:?} slots (capacity {})", resource_type, slots.capacity))?;
+
+        self.write(resource_type, index, write);
+
+        Ok(index)
+    }
+
+    #[inline]
+    fn write(&self, resource_type: BindlessResourceType, index: u32, write: impl FnOnce(vk::WriteDescriptorSet) -> vk::WriteDescriptorSet) {
+        let write_descriptor_set = write(
+            vk::WriteDescriptorSet::default()
+                .dst_set(self.descriptor_set)
+                .dst_binding(resource_type.binding())
+                .dst_array_element(index)
+                .descriptor_type(resource_type.descriptor_type())
+        );
+
+        unsafe { self.device.loader().update_descriptor_sets(slice::from_ref(&write_descriptor_set), &[]) }
+    }
+
+    //The image view has to stay alive as long as shaders may access the returned index
+    pub fn allocate_sampled_image(&self, image_view: &ImageView, layout: vk::ImageLayout) -> Result<u32> {
+        let image_info = vk::DescriptorImageInfo::default().image_view(**image_view).image_layout(layout);
+
+        self.allocate(BindlessResourceType::SampledImage, |write| write.image_info(slice::from_ref(&image_info)))
+    }
+
+    pub fn allocate_storage_image(&self, image_view: &ImageView) -> Result<u32> {
+        let image_info = vk::DescriptorImageInfo::default().image_view(**image_view).image_layout(vk::ImageLayout::GENERAL);
+
+        self.allocate(BindlessResourceType::StorageImage, |write| write.image_info(slice::from_ref(&image_info)))
+    }
+
+    pub fn allocate_storage_buffer(&self, buffer: &Buffer, offset: u64, range: u64) -> Result<u32> {
+        let buffer_info = vk::DescriptorBufferInfo::default().buffer(**buffer).offset(offset).range(range);
+
+        self.allocate(BindlessResourceType::StorageBuffer, |write| write.buffer_info(slice::from_ref(&buffer_info)))
+    }
+
+    pub fn allocate_sampler(&self, sampler: &Sampler) -> Result<u32> {
+        let image_info = vk::DescriptorImageInfo::default().sampler(**sampler);
+
+        self.allocate(BindlessResourceType::Sampler, |write| write.image_info(slice::from_ref(&image_info)))
+    }
+
+    //The index is recycled once all work submitted until now has completed, freeing an index that is not allocated fails
+    pub fn free(&self, resource_type: BindlessResourceType, index: u32) -> Result<()> {
+        let submission_values = self.device.last_submission_values();
+
+        let mut slots = self.slots.lock().unwrap();
+        slots[resource_type as usize].retire(submission_values, index)
+    }
+
+    #[inline]
+    pub fn descriptor_set(&self) -> vk::DescriptorSet {
+        self.descriptor_set
+    }
+
+    #[inline]
+    pub fn descriptor_set_layout(&self) -> vk::DescriptorSetLayout {
+        self.descriptor_set_layout
+    }
+}
+
+impl Drop for BindlessHeap {
+    fn drop(&mut self) {
+        if self.descriptor_pool != vk::DescriptorPool::null() {
+            self.device.defer_destruction(DeferredDestruction::DescriptorPool(self.descriptor_pool));
+        }
+    }
+}
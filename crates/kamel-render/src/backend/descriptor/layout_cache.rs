@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex
+    }
+};
+
+use ash::{prelude::VkResult, vk};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DescriptorSetLayoutBindingDesc {
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+    //Layouts with update after bind bindings can only be allocated from update after bind pools
+    pub flags: vk::DescriptorBindingFlags
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct PipelineLayoutKey {
+    set_layouts: Vec<vk::DescriptorSetLayout>,
+    push_constant_ranges: Vec<(vk::ShaderStageFlags, u32, u32)>
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LayoutCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize
+}
+
+struct LayoutCache<K, V> {
+    entries: Mutex<HashMap<K, V>>,
+    hits: AtomicU64,
+    misses: AtomicU64
+}
+
+impl<K: Eq + Hash, V: Copy> LayoutCache<K, V> {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0)
+        }
+    }
+
+    fn get_or_create(&self, key: K, create: impl FnOnce() -> VkResult<V>) -> VkResult<V> {
+        let mut entries = self.entries.lock().unwrap();
+
+        match entries.get(&key) {
+            Some(value) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Ok(*value)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+
+                let value = create()?;
+                entries.insert(key, value);
+                Ok(value)
+            }
+        }
+    }
+
+    #[inline]
+    fn stats(&self) -> LayoutCacheStats {
+        LayoutCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len()
+        }
+    }
+}
+
+pub struct DescriptorSetLayoutCache(LayoutCache<Vec<DescriptorSetLayoutBindingDesc>, vk::DescriptorSetLayout>);
+
+impl DescriptorSetLayoutCache {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self(LayoutCache::new())
+    }
+
+    pub(crate) unsafe fn get_or_create(&self, loader: &ash::Device, bindings: &[DescriptorSetLayoutBindingDesc]) -> VkResult<vk::DescriptorSetLayout> {
+        let mut key = bindings.to_vec();
+        key.sort_by_key(|binding| binding.binding);
+
+        self.0.get_or_create(key, || {
+            let binding_flags: Vec<_> = bindings.iter().map(|binding| binding.flags).collect();
+            let bindings: Vec<_> = bindings
+                .iter()
+                .map(|binding| {
+                    vk::DescriptorSetLayoutBinding::default()
+                        .binding(binding.binding)
+                        .descriptor_type(binding.descriptor_type)
+                        .descriptor_count(binding.count)
+                        .stage_flags(binding.stages)
+                })
+                .collect();
+
+            let mut flags = vk::DescriptorSetLayoutCreateFlags::empty();
+            if binding_flags.iter().any(|binding_flags| binding_flags.contains(vk::DescriptorBindingFlags::UPDATE_AFTER_BIND)) {
+                flags |= vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL;
+            }
+
+            let mut binding_flags_create_info = vk::DescriptorSetLayoutBindingFlagsCreateInfo::default().binding_flags(&binding_flags);
+            let mut descriptor_set_layout_create_info = vk::DescriptorSetLayoutCreateInfo::default().flags(flags).bindings(&bindings);
+            if binding_flags.iter().any(|binding_flags| !binding_flags.is_empty()) {
+                descriptor_set_layout_create_info = descriptor_set_layout_create_info.push_next(&mut binding_flags_create_info);
+            }
+
+            loader.create_descriptor_set_layout(&descriptor_set_layout_create_info, None)
+        })
+    }
+
+    pub(crate) unsafe fn destroy(&self, loader: &ash::Device) {
+        for (_, descriptor_set_layout) in self.0.entries.lock().unwrap().drain() {
+            loader.destroy_descriptor_set_layout(descriptor_set_layout, None);
+        }
+    }
+
+    #[inline]
+    pub fn stats(&self) -> LayoutCacheStats {
+        self.0.stats()
+    }
+}
+
+pub struct PipelineLayoutCache(LayoutCache<PipelineLayoutKey, vk::PipelineLayout>);
+
+impl PipelineLayoutCache {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self(LayoutCache::new())
+    }
+
+    pub(crate) unsafe fn get_or_create(
+        &self,
+        loader: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange]
+    ) -> VkResult<vk::PipelineLayout> {
+        let key = PipelineLayoutKey {
+            set_layouts: set_layouts.to_vec(),
+            push_constant_ranges: push_constant_ranges.iter().map(|range| (range.stage_flags, range.offset, range.size)).collect()
+        };
+
+        self.0.get_or_create(key, || {
+            let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::default().set_layouts(set_layouts).push_constant_ranges(push_constant_ranges);
+
+            loader.create_pipeline_layout(&pipeline_layout_create_info, None)
+        })
+    }
+
+    pub(crate) unsafe fn destroy(&self, loader: &ash::Device) {
+        for (_, pipeline_layout) in self.0.entries.lock().unwrap().drain() {
+            loader.destroy_pipeline_layout(pipeline_layout, None);
+        }
+    }
+
+    #[inline]
+    pub fn stats(&self) -> LayoutCacheStats {
+        self.0.stats()
+    }
+}
@@ -0,0 +1,5 @@
+mod bindless_heap;
+mod layout_cache;
+
+pub use bindless_heap::*;
+pub use layout_cache::*;
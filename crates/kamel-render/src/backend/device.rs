@@ -1,11 +1,40 @@
-use std::{os::raw::c_char, sync::Arc};
+use std::{
+    ffi::CStr,
+    ops::{Deref, DerefMut},
+    path::Path,
+    sync::Arc
+};
 
 use anyhow::Result;
 use ash::{extensions::khr::Swapchain, prelude::VkResult, vk};
 use kamel_bevy::ecs::{self as bevy_ecs, system::Resource};
 use vk_mem_alloc::{Allocator, AllocatorCreateFlags, AllocatorCreateInfo};
 
-use crate::backend::{Instance, Surface};
+use crate::backend::{
+    deletion_queue::{DeferredDestruction, DeletionQueue},
+    extension_registry::{ExtensionLoaders, DEVICE_EXTENSION_DEPENDENCIES},
+    DescriptorSetLayoutBindingDesc, DescriptorSetLayoutCache, DeviceExtensionLoader, DeviceFeature, DeviceFeatureReport, DeviceFeatureRequests, ExtensionRegistry, Instance,
+    PipelineCache, PipelineLayoutCache, Queue, QueueType, SubmissionValues, Surface
+};
+
+//Number of parked handles at which defer_destruction processes the deletion queue itself
+const DEFERRED_DESTRUCTION_THRESHOLD: usize = 64;
+
+//Every queue signals a timeline semaphore and submits through synchronization2, the allocator is created with buffer device addresses
+pub(crate) const DEVICE_REQUIRED_FEATURES: &[DeviceFeature] = &[
+    DeviceFeature::TimelineSemaphore,
+    DeviceFeature::Synchronization2,
+    DeviceFeature::BufferDeviceAddress,
+    DeviceFeature::Maintenance4
+];
+
+const KHR_PORTABILITY_SUBSET: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"VK_KHR_portability_subset\0") };
+
+//The structs of a core version can only be chained if the physical device supports that version, the others stay zeroed
+#[inline]
+unsafe fn physical_device_api_version(instance: &Instance, physical_device: vk::PhysicalDevice) -> u32 {
+    instance.loader().get_physical_device_properties(physical_device).api_version
+}
 
 pub struct DeviceProperties {
     pub properties: vk::PhysicalDeviceProperties,
@@ -16,15 +45,19 @@ pub struct DeviceProperties {
 
 impl DeviceProperties {
     #[inline]
-    unsafe fn new(instance: &Instance, physical_device: vk::PhysicalDevice) -> Self {
+    pub(crate) unsafe fn new(instance: &Instance, physical_device: vk::PhysicalDevice) -> Self {
         let mut properties_11 = vk::PhysicalDeviceVulkan11Properties::default();
         let mut properties_12 = vk::PhysicalDeviceVulkan12Properties::default();
         let mut properties_13 = vk::PhysicalDeviceVulkan13Properties::default();
 
-        let mut properties = vk::PhysicalDeviceProperties2::default()
-            .push_next(&mut properties_11)
-            .push_next(&mut properties_12)
-            .push_next(&mut properties_13);
+        let api_version = physical_device_api_version(instance, physical_device);
+        let mut properties = vk::PhysicalDeviceProperties2::default();
+        if api_version >= vk::API_VERSION_1_2 {
+            properties = properties.push_next(&mut properties_11).push_next(&mut properties_12);
+        }
+        if api_version >= vk::API_VERSION_1_3 {
+            properties = properties.push_next(&mut properties_13);
+        }
 
         instance.loader().get_physical_device_properties2(physical_device, &mut properties);
 
@@ -46,7 +79,7 @@ pub struct DeviceMemoryProperties {
 
 impl DeviceMemoryProperties {
     #[inline]
-    unsafe fn new(instance: &Instance, physical_device: vk::PhysicalDevice) -> Self {
+    pub(crate) unsafe fn new(instance: &Instance, physical_device: vk::PhysicalDevice) -> Self {
         let mut memory_properties = vk::PhysicalDeviceMemoryProperties2::default();
 
         instance.loader().get_physical_device_memory_properties2(physical_device, &mut memory_properties);
@@ -63,7 +96,7 @@ pub struct DeviceQueueFamilyProperties {
 
 impl DeviceQueueFamilyProperties {
     #[inline]
-    unsafe fn new(instance: &Instance, physical_device: vk::PhysicalDevice) -> Self {
+    pub(crate) unsafe fn new(instance: &Instance, physical_device: vk::PhysicalDevice) -> Self {
         let instance_loader = instance.loader();
 
         let mut queue_family_properties: Vec<_> = (0..instance_loader.get_physical_device_queue_family_properties2_len(physical_device))
@@ -91,14 +124,18 @@ pub struct DeviceFeatures {
 
 impl DeviceFeatures {
     #[inline]
-    unsafe fn new(instance: &Instance, physical_device: vk::PhysicalDevice) -> Self {
+    pub(crate) unsafe fn new(instance: &Instance, physical_device: vk::PhysicalDevice) -> Self {
         let mut features_11 = vk::PhysicalDeviceVulkan11Features::default();
         let mut features_12 = vk::PhysicalDeviceVulkan12Features::default();
         let mut features_13 = vk::PhysicalDeviceVulkan13Features::default();
-        let mut features = vk::PhysicalDeviceFeatures2::default()
-            .push_next(&mut features_11)
-            .push_next(&mut features_12)
-            .push_next(&mut features_13);
+        let api_version = physical_device_api_version(instance, physical_device);
+        let mut features = vk::PhysicalDeviceFeatures2::default();
+        if api_version >= vk::API_VERSION_1_2 {
+            features = features.push_next(&mut features_11).push_next(&mut features_12);
+        }
+        if api_version >= vk::API_VERSION_1_3 {
+            features = features.push_next(&mut features_13);
+        }
 
         instance.loader().get_physical_device_features2(physical_device, &mut features);
 
@@ -115,11 +152,7 @@ unsafe impl Send for DeviceFeatures {}
 unsafe impl Sync for DeviceFeatures {}
 
 pub struct DeviceExtensions {
-    supported: Vec<vk::ExtensionProperties>,
-    enabled: Vec<*const c_char>,
-
-    khr_portability_subset: bool,
-    khr_swapchain: bool
+    pub(crate) registry: ExtensionRegistry
 }
 
 impl DeviceExtensions {
@@ -127,32 +160,13 @@ impl DeviceExtensions {
         let supported = instance.loader().enumerate_device_extension_properties(physical_device)?;
 
         Ok(Self {
-            supported,
-            enabled: Vec::new(),
-
-            khr_portability_subset: false,
-            khr_swapchain: false
+            registry: ExtensionRegistry::new(&supported, DEVICE_EXTENSION_DEPENDENCIES, instance.extensions().enabled().map(CStr::to_owned).collect())
         })
     }
 
-    #[inline]
-    unsafe fn try_push(&mut self, name: *const c_char) -> bool {
-        if self.supported.iter().any(|e| libc::strcmp(e.extension_name.as_ptr(), name) == 0) {
-            self.enabled.push(name);
-            true
-        } else {
-            false
-        }
-    }
-
     #[inline]
     pub fn try_push_khr_portability_subset(&mut self) -> bool {
-        if unsafe { self.try_push(b"VK_KHR_portability_subset\0".as_ptr().cast()) } {
-            self.khr_portability_subset = true;
-            true
-        } else {
-            false
-        }
+        self.enable(KHR_PORTABILITY_SUBSET)
     }
 
     #[inline]
@@ -162,33 +176,48 @@ impl DeviceExtensions {
 
     #[inline]
     pub fn khr_portability_subset(&self) -> bool {
-        self.khr_portability_subset
+        self.is_enabled(KHR_PORTABILITY_SUBSET)
     }
 
     #[inline]
     pub fn try_push_khr_swapchain(&mut self) -> bool {
-        if unsafe { self.try_push(Swapchain::name().as_ptr()) } {
-            self.khr_swapchain = true;
-            true
-        } else {
-            false
-        }
+        self.enable(Swapchain::name())
     }
 
     #[inline]
     pub fn push_khr_swapchain(&mut self) {
         assert!(self.try_push_khr_swapchain());
     }
+
+    #[inline]
+    pub fn khr_swapchain(&self) -> bool {
+        self.is_enabled(Swapchain::name())
+    }
+}
+
+impl Deref for DeviceExtensions {
+    type Target = ExtensionRegistry;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.registry
+    }
 }
 
-unsafe impl Send for DeviceExtensions {}
-unsafe impl Sync for DeviceExtensions {}
+impl DerefMut for DeviceExtensions {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.registry
+    }
+}
 
 struct Inner {
     physical_device: vk::PhysicalDevice,
 
     loader: ash::Device,
-    swapchain_loader: Swapchain,
+    //Only attached if VK_KHR_swapchain is enabled
+    swapchain_loader: Option<Swapchain>,
+    extension_loaders: ExtensionLoaders,
     allocator: Allocator,
 
     extensions: DeviceExtensions,
@@ -199,14 +228,47 @@ struct Inner {
 
     supported_features: DeviceFeatures,
     enabled_features: DeviceFeatures,
+    feature_report: DeviceFeatureReport,
+
+    direct_queue: Arc<Queue>,
+    compute_queue: Arc<Queue>,
+    transfer_queue: Arc<Queue>,
+
+    pipeline_cache: PipelineCache,
+    descriptor_set_layout_cache: DescriptorSetLayoutCache,
+    pipeline_layout_cache: PipelineLayoutCache,
+
+    deletion_queue: DeletionQueue,
 
     instance: Instance,
-    surface: Surface
+    surface: Option<Surface>
 }
 
 impl Drop for Inner {
     fn drop(&mut self) {
         unsafe {
+            let _ = self.loader.device_wait_idle();
+
+            self.deletion_queue.flush(&self.loader, self.allocator);
+
+            self.direct_queue.destroy();
+            if !Arc::ptr_eq(&self.compute_queue, &self.direct_queue) {
+                self.compute_queue.destroy();
+            }
+            if !Arc::ptr_eq(&self.transfer_queue, &self.direct_queue) && !Arc::ptr_eq(&self.transfer_queue, &self.compute_queue) {
+                self.transfer_queue.destroy();
+            }
+
+            if self.pipeline_cache.directory().is_some() {
+                if let Err(error) = self.pipeline_cache.save(&self.loader) {
+                    log::warn!("Failed to save pipeline cache: {}", error);
+                }
+            }
+            self.pipeline_cache.destroy(&self.loader);
+
+            self.pipeline_layout_cache.destroy(&self.loader);
+            self.descriptor_set_layout_cache.destroy(&self.loader);
+
             vk_mem_alloc::destroy_allocator(self.allocator);
             self.loader.destroy_device(None);
         }
@@ -216,7 +278,55 @@ impl Drop for Inner {
 #[derive(Clone, Resource)]
 pub struct Device(Arc<Inner>);
 
-unsafe fn find_direct_queue_family_index(instance: &Instance, surface: &Surface, physical_device: vk::PhysicalDevice, properties: &[vk::QueueFamilyProperties]) -> Option<u32> {
+//Destroys the device and what was created from it so far if Device::new fails before Inner owns them
+struct DeviceCreationGuard<'a> {
+    loader: &'a ash::Device,
+    queues: Vec<Arc<Queue>>,
+    pipeline_cache: Option<vk::PipelineCache>,
+    armed: bool
+}
+
+impl<'a> DeviceCreationGuard<'a> {
+    #[inline]
+    fn new(loader: &'a ash::Device) -> Self {
+        Self {
+            loader,
+            queues: Vec::new(),
+            pipeline_cache: None,
+            armed: true
+        }
+    }
+
+    #[inline]
+    fn queue(&mut self, queue: Queue) -> Arc<Queue> {
+        let queue = Arc::new(queue);
+        self.queues.push(queue.clone());
+        queue
+    }
+
+    #[inline]
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for DeviceCreationGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            unsafe {
+                for queue in &self.queues {
+                    queue.destroy();
+                }
+                if let Some(pipeline_cache) = self.pipeline_cache {
+                    self.loader.destroy_pipeline_cache(pipeline_cache, None);
+                }
+                self.loader.destroy_device(None);
+            }
+        }
+    }
+}
+
+unsafe fn find_direct_queue_family_index(instance: &Instance, surface: Option<&Surface>, physical_device: vk::PhysicalDevice, properties: &[vk::QueueFamilyProperties]) -> Option<u32> {
     let mut queue_count: u32 = 0;
     let mut family_index: u32 = 0;
 
@@ -227,10 +337,11 @@ unsafe fn find_direct_queue_family_index(instance: &Instance, surface: &Surface,
 
         if (properties.queue_flags & direct_flags) == direct_flags
             && properties.queue_count > queue_count
-            && instance
-                .surface_loader()
-                .get_physical_device_surface_support(physical_device, i, *surface.surface())
-                .unwrap_or(false)
+            && surface.map_or(true, |surface| {
+                instance.surface_loader().map_or(false, |surface_loader| {
+                    surface_loader.get_physical_device_surface_support(physical_device, i, *surface.surface()).unwrap_or(false)
+                })
+            })
         {
             queue_count = properties.queue_count;
             family_index = i;
@@ -264,7 +375,12 @@ unsafe fn find_queue_family_index(properties: &[vk::QueueFamilyProperties], desi
     }
 }
 
-unsafe fn find_queue_family_indices(instance: &Instance, surface: &Surface, physical_device: vk::PhysicalDevice, properties: &[vk::QueueFamilyProperties]) -> Option<(u32, u32, u32)> {
+pub(crate) unsafe fn find_queue_family_indices(
+    instance: &Instance,
+    surface: Option<&Surface>,
+    physical_device: vk::PhysicalDevice,
+    properties: &[vk::QueueFamilyProperties]
+) -> Option<(u32, u32, u32)> {
     let direct_index = find_direct_queue_family_index(instance, surface, physical_device, properties)?;
     let compute_index = find_queue_family_index(properties, vk::QueueFlags::COMPUTE, vk::QueueFlags::GRAPHICS | vk::QueueFlags::TRANSFER)
         .or_else(|| find_queue_family_index(properties, vk::QueueFlags::COMPUTE, vk::QueueFlags::GRAPHICS))
@@ -282,9 +398,9 @@ unsafe fn find_queue_family_indices(instance: &Instance, surface: &Surface, phys
 impl Device {
     pub unsafe fn new(
         instance: Instance,
-        surface: Surface,
+        surface: Option<Surface>,
         physical_device: vk::PhysicalDevice,
-        callback: impl FnOnce(&DeviceProperties, &DeviceMemoryProperties, &DeviceQueueFamilyProperties, &mut DeviceExtensions, &DeviceFeatures, &mut DeviceFeatures) -> Result<()>
+        callback: impl FnOnce(&DeviceProperties, &DeviceMemoryProperties, &DeviceQueueFamilyProperties, &mut DeviceExtensions, &DeviceFeatures, &mut DeviceFeatureRequests) -> Result<()>
     ) -> Result<Self> {
         let mut extensions = DeviceExtensions::new(&instance, physical_device)?;
 
@@ -293,7 +409,7 @@ impl Device {
         let queue_family_properties = DeviceQueueFamilyProperties::new(&instance, physical_device);
 
         let supported_features = DeviceFeatures::new(&instance, physical_device);
-        let mut enabled_features = DeviceFeatures::default();
+        let mut feature_requests = DeviceFeatureRequests::default();
 
         callback(
             &properties,
@@ -301,12 +417,18 @@ impl Device {
             &queue_family_properties,
             &mut extensions,
             &supported_features,
-            &mut enabled_features
+            &mut feature_requests
         )?;
 
+        for feature in DEVICE_REQUIRED_FEATURES {
+            feature_requests.require(*feature);
+        }
+
+        let (enabled_features, feature_report) = feature_requests.negotiate(&supported_features)?;
+
         //Queue families
         let (direct_queue_family_index, compute_queue_family_index, transfer_queue_family_index) =
-            find_queue_family_indices(&instance, &surface, physical_device, &queue_family_properties.queue_family_properties)
+            find_queue_family_indices(&instance, surface.as_ref(), physical_device, &queue_family_properties.queue_family_properties)
                 .ok_or_else(|| anyhow::anyhow!("Failed to find queue family indices"))?;
 
         let queue_priorities = [1.0];
@@ -323,7 +445,7 @@ impl Device {
             );
         }
 
-        if transfer_queue_family_index != direct_queue_family_index {
+        if transfer_queue_family_index != direct_queue_family_index && transfer_queue_family_index != compute_queue_family_index {
             device_queue_create_infos.push(
                 vk::DeviceQueueCreateInfo::default()
                     .queue_family_index(transfer_queue_family_index)
@@ -341,14 +463,34 @@ impl Device {
             .push_next(&mut features_13);
 
         //Create device
+        let enabled_extension_names = extensions.enabled_names();
         let device_create_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(&device_queue_create_infos)
-            .enabled_extension_names(&extensions.enabled)
+            .enabled_extension_names(&enabled_extension_names)
             .push_next(&mut features);
 
         let instance_loader = instance.loader();
         let loader = instance_loader.create_device(physical_device, &device_create_info, None)?;
-        let swapchain_loader = Swapchain::new(instance_loader, &loader);
+        let mut guard = DeviceCreationGuard::new(&loader);
+        let swapchain_loader = extensions.khr_swapchain().then(|| Swapchain::new(instance_loader, &loader));
+
+        //Queues
+        let direct_queue = guard.queue(Queue::new(&loader, swapchain_loader.as_ref(), direct_queue_family_index)?);
+        let compute_queue = if compute_queue_family_index == direct_queue_family_index {
+            direct_queue.clone()
+        } else {
+            guard.queue(Queue::new(&loader, swapchain_loader.as_ref(), compute_queue_family_index)?)
+        };
+        let transfer_queue = if transfer_queue_family_index == direct_queue_family_index {
+            direct_queue.clone()
+        } else if transfer_queue_family_index == compute_queue_family_index {
+            compute_queue.clone()
+        } else {
+            guard.queue(Queue::new(&loader, swapchain_loader.as_ref(), transfer_queue_family_index)?)
+        };
+
+        let pipeline_cache = PipelineCache::new(&loader, &properties)?;
+        guard.pipeline_cache = Some(*pipeline_cache.handle());
 
         let allocator = vk_mem_alloc::create_allocator(
             instance_loader,
@@ -359,12 +501,14 @@ impl Device {
                 ..Default::default()
             })
         )?;
+        guard.disarm();
 
         Ok(Self(Arc::new(Inner {
             physical_device,
 
             loader,
             swapchain_loader,
+            extension_loaders: ExtensionLoaders::default(),
             allocator,
 
             extensions,
@@ -375,6 +519,17 @@ impl Device {
 
             supported_features,
             enabled_features,
+            feature_report,
+
+            direct_queue,
+            compute_queue,
+            transfer_queue,
+
+            pipeline_cache,
+            descriptor_set_layout_cache: DescriptorSetLayoutCache::new(),
+            pipeline_layout_cache: PipelineLayoutCache::new(),
+
+            deletion_queue: DeletionQueue::new(),
 
             instance,
             surface
@@ -392,8 +547,20 @@ impl Device {
     }
 
     #[inline]
-    pub fn swapchain_loader(&self) -> &Swapchain {
-        &self.0.swapchain_loader
+    pub fn swapchain_loader(&self) -> Option<&Swapchain> {
+        self.0.swapchain_loader.as_ref()
+    }
+
+    //Created on first use, None if the extension of the loader is not enabled
+    #[inline]
+    pub fn extension_loader<T: DeviceExtensionLoader>(&self) -> Option<Arc<T>> {
+        let instance = &self.0.instance;
+
+        self.0.extensions.is_enabled(T::extension_name()).then(|| {
+            self.0
+                .extension_loaders
+                .get_or_insert_with(|| unsafe { T::new(instance.entry_loader(), instance.loader(), &self.0.loader) })
+        })
     }
 
     #[inline]
@@ -431,13 +598,125 @@ impl Device {
         &self.0.enabled_features
     }
 
+    //Which of the requested optional features were granted
+    #[inline]
+    pub fn feature_report(&self) -> &DeviceFeatureReport {
+        &self.0.feature_report
+    }
+
+    #[inline]
+    pub fn direct_queue(&self) -> &Queue {
+        &self.0.direct_queue
+    }
+
+    #[inline]
+    pub fn compute_queue(&self) -> &Queue {
+        &self.0.compute_queue
+    }
+
+    #[inline]
+    pub fn transfer_queue(&self) -> &Queue {
+        &self.0.transfer_queue
+    }
+
+    #[inline]
+    pub fn queue(&self, queue_type: QueueType) -> &Queue {
+        match queue_type {
+            QueueType::Direct => self.direct_queue(),
+            QueueType::Compute => self.compute_queue(),
+            QueueType::Transfer => self.transfer_queue()
+        }
+    }
+
+    #[inline]
+    pub fn last_submission_values(&self) -> SubmissionValues {
+        SubmissionValues {
+            direct: self.0.direct_queue.last_submitted_value(),
+            compute: self.0.compute_queue.last_submitted_value(),
+            transfer: self.0.transfer_queue.last_submitted_value()
+        }
+    }
+
+    #[inline]
+    pub unsafe fn completed_submission_values(&self) -> VkResult<SubmissionValues> {
+        Ok(SubmissionValues {
+            direct: self.0.direct_queue.completed_value()?,
+            compute: self.0.compute_queue.completed_value()?,
+            transfer: self.0.transfer_queue.completed_value()?
+        })
+    }
+
+    //Parks the handle until every submission made so far completed, submissions are not tracked per resource.
+    //The queue is also processed here once it grows large so it stays bounded without a FrameContext.
+    pub(crate) fn defer_destruction(&self, destruction: DeferredDestruction) {
+        self.0.deletion_queue.push(self.last_submission_values(), destruction);
+
+        if self.0.deletion_queue.len() >= DEFERRED_DESTRUCTION_THRESHOLD {
+            if let Err(error) = self.process_deferred_destructions() {
+                log::warn!("Failed to process deferred destructions: {}", error);
+            }
+        }
+    }
+
+    //Destroys the dropped resources that are no longer in use, called by FrameContext::begin_frame.
+    //Safe to call from any thread. Returns the number of destroyed resources.
+    pub fn process_deferred_destructions(&self) -> VkResult<usize> {
+        let completed = unsafe { self.completed_submission_values() }?;
+
+        Ok(unsafe { self.0.deletion_queue.process(&self.0.loader, self.0.allocator, &completed) })
+    }
+
+    #[inline]
+    pub fn deferred_destruction_count(&self) -> usize {
+        self.0.deletion_queue.len()
+    }
+
+    //Passed to every pipeline creation on this device
+    #[inline]
+    pub fn pipeline_cache(&self) -> &PipelineCache {
+        &self.0.pipeline_cache
+    }
+
+    //Merges the cache stored in the directory into the pipeline cache of the device and saves it there once the device is destroyed.
+    //Should be called before any pipelines are created.
+    #[inline]
+    pub fn load_pipeline_cache(&self, directory: impl AsRef<Path>) -> Result<bool> {
+        unsafe { self.0.pipeline_cache.load(&self.0.loader, directory.as_ref()) }
+    }
+
+    #[inline]
+    pub fn save_pipeline_cache(&self) -> Result<()> {
+        unsafe { self.0.pipeline_cache.save(&self.0.loader) }
+    }
+
+    //Layouts are shared by everything with the same bindings and live as long as the device
+    #[inline]
+    pub fn get_or_create_descriptor_set_layout(&self, bindings: &[DescriptorSetLayoutBindingDesc]) -> Result<vk::DescriptorSetLayout> {
+        Ok(unsafe { self.0.descriptor_set_layout_cache.get_or_create(&self.0.loader, bindings) }?)
+    }
+
+    #[inline]
+    pub fn get_or_create_pipeline_layout(&self, set_layouts: &[vk::DescriptorSetLayout], push_constant_ranges: &[vk::PushConstantRange]) -> Result<vk::PipelineLayout> {
+        Ok(unsafe { self.0.pipeline_layout_cache.get_or_create(&self.0.loader, set_layouts, push_constant_ranges) }?)
+    }
+
+    #[inline]
+    pub fn descriptor_set_layout_cache(&self) -> &DescriptorSetLayoutCache {
+        &self.0.descriptor_set_layout_cache
+    }
+
+    #[inline]
+    pub fn pipeline_layout_cache(&self) -> &PipelineLayoutCache {
+        &self.0.pipeline_layout_cache
+    }
+
     #[inline]
     pub fn instance(&self) -> &Instance {
         &self.0.instance
     }
 
     #[inline]
-    pub fn surface(&self) -> &Surface {
-        &self.0.surface
+    pub fn surface(&self) -> Option<&Surface> {
+        self.0.surface.as_ref()
     }
 }
@@ -0,0 +1,427 @@
+use std::fmt;
+
+use ash::vk;
+
+use crate::backend::DeviceFeatures;
+
+macro_rules! device_features {
+    ($($set:ident { $($feature:ident => $field:ident),* })*) => {
+        //Feature of the Vulkan 1.0 to 1.3 feature structs
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum DeviceFeature {
+            $($($feature),*),*
+        }
+
+        impl DeviceFeature {
+            pub const ALL: &'static [Self] = &[$($(Self::$feature),*),*];
+
+            //Feature struct and field, e.g. "features_12.timeline_semaphore"
+            #[inline]
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($(Self::$feature => concat!(stringify!($set), ".", stringify!($field))),*),*
+                }
+            }
+        }
+
+        impl DeviceFeatures {
+            #[inline]
+            pub fn get(&self, feature: DeviceFeature) -> bool {
+                match feature {
+                    $($(DeviceFeature::$feature => self.$set.$field == vk::TRUE),*),*
+                }
+            }
+
+            #[inline]
+            pub fn set(&mut self, feature: DeviceFeature, enabled: bool) {
+                match feature {
+                    $($(DeviceFeature::$feature => self.$set.$field = enabled as vk::Bool32),*),*
+                }
+            }
+        }
+    };
+}
+
+device_features! {
+    features {
+        RobustBufferAccess => robust_buffer_access,
+        FullDrawIndexUint32 => full_draw_index_uint32,
+        ImageCubeArray => image_cube_array,
+        IndependentBlend => independent_blend,
+        GeometryShader => geometry_shader,
+        TessellationShader => tessellation_shader,
+        SampleRateShading => sample_rate_shading,
+        DualSrcBlend => dual_src_blend,
+        LogicOp => logic_op,
+        MultiDrawIndirect => multi_draw_indirect,
+        DrawIndirectFirstInstance => draw_indirect_first_instance,
+        DepthClamp => depth_clamp,
+        DepthBiasClamp => depth_bias_clamp,
+        FillModeNonSolid => fill_mode_non_solid,
+        DepthBounds => depth_bounds,
+        WideLines => wide_lines,
+        LargePoints => large_points,
+        AlphaToOne => alpha_to_one,
+        MultiViewport => multi_viewport,
+        SamplerAnisotropy => sampler_anisotropy,
+        TextureCompressionEtc2 => texture_compression_etc2,
+        TextureCompressionAstcLdr => texture_compression_astc_ldr,
+        TextureCompressionBc => texture_compression_bc,
+        OcclusionQueryPrecise => occlusion_query_precise,
+        PipelineStatisticsQuery => pipeline_statistics_query,
+        VertexPipelineStoresAndAtomics => vertex_pipeline_stores_and_atomics,
+        FragmentStoresAndAtomics => fragment_stores_and_atomics,
+        ShaderTessellationAndGeometryPointSize => shader_tessellation_and_geometry_point_size,
+        ShaderImageGatherExtended => shader_image_gather_extended,
+        ShaderStorageImageExtendedFormats => shader_storage_image_extended_formats,
+        ShaderStorageImageMultisample => shader_storage_image_multisample,
+        ShaderStorageImageReadWithoutFormat => shader_storage_image_read_without_format,
+        ShaderStorageImageWriteWithoutFormat => shader_storage_image_write_without_format,
+        ShaderUniformBufferArrayDynamicIndexing => shader_uniform_buffer_array_dynamic_indexing,
+        ShaderSampledImageArrayDynamicIndexing => shader_sampled_image_array_dynamic_indexing,
+        ShaderStorageBufferArrayDynamicIndexing => shader_storage_buffer_array_dynamic_indexing,
+        ShaderStorageImageArrayDynamicIndexing => shader_storage_image_array_dynamic_indexing,
+        ShaderClipDistance => shader_clip_distance,
+        ShaderCullDistance => shader_cull_distance,
+        ShaderFloat64 => shader_float64,
+        ShaderInt64 => shader_int64,
+        ShaderInt16 => shader_int16,
+        ShaderResourceResidency => shader_resource_residency,
+        ShaderResourceMinLod => shader_resource_min_lod,
+        SparseBinding => sparse_binding,
+        SparseResidencyBuffer => sparse_residency_buffer,
+        SparseResidencyImage2D => sparse_residency_image2_d,
+        SparseResidencyImage3D => sparse_residency_image3_d,
+        SparseResidency2Samples => sparse_residency2_samples,
+        SparseResidency4Samples => sparse_residency4_samples,
+        SparseResidency8Samples => sparse_residency8_samples,
+        SparseResidency16Samples => sparse_residency16_samples,
+        SparseResidencyAliased => sparse_residency_aliased,
+        VariableMultisampleRate => variable_multisample_rate,
+        InheritedQueries => inherited_queries
+    }
+    features_11 {
+        StorageBuffer16BitAccess => storage_buffer16_bit_access,
+        UniformAndStorageBuffer16BitAccess => uniform_and_storage_buffer16_bit_access,
+        StoragePushConstant16 => storage_push_constant16,
+        StorageInputOutput16 => storage_input_output16,
+        Multiview => multiview,
+        MultiviewGeometryShader => multiview_geometry_shader,
+        MultiviewTessellationShader => multiview_tessellation_shader,
+        VariablePointersStorageBuffer => variable_pointers_storage_buffer,
+        VariablePointers => variable_pointers,
+        ProtectedMemory => protected_memory,
+        SamplerYcbcrConversion => sampler_ycbcr_conversion,
+        ShaderDrawParameters => shader_draw_parameters
+    }
+    features_12 {
+        SamplerMirrorClampToEdge => sampler_mirror_clamp_to_edge,
+        DrawIndirectCount => draw_indirect_count,
+        StorageBuffer8BitAccess => storage_buffer8_bit_access,
+        UniformAndStorageBuffer8BitAccess => uniform_and_storage_buffer8_bit_access,
+        StoragePushConstant8 => storage_push_constant8,
+        ShaderBufferInt64Atomics => shader_buffer_int64_atomics,
+        ShaderSharedInt64Atomics => shader_shared_int64_atomics,
+        ShaderFloat16 => shader_float16,
+        ShaderInt8 => shader_int8,
+        DescriptorIndexing => descriptor_indexing,
+        ShaderInputAttachmentArrayDynamicIndexing => shader_input_attachment_array_dynamic_indexing,
+        ShaderUniformTexelBufferArrayDynamicIndexing => shader_uniform_texel_buffer_array_dynamic_indexing,
+        ShaderStorageTexelBufferArrayDynamicIndexing => shader_storage_texel_buffer_array_dynamic_indexing,
+        ShaderUniformBufferArrayNonUniformIndexing => shader_uniform_buffer_array_non_uniform_indexing,
+        ShaderSampledImageArrayNonUniformIndexing => shader_sampled_image_array_non_uniform_indexing,
+        ShaderStorageBufferArrayNonUniformIndexing => shader_storage_buffer_array_non_uniform_indexing,
+        ShaderStorageImageArrayNonUniformIndexing => shader_storage_image_array_non_uniform_indexing,
+        ShaderInputAttachmentArrayNonUniformIndexing => shader_input_attachment_array_non_uniform_indexing,
+        ShaderUniformTexelBufferArrayNonUniformIndexing => shader_uniform_texel_buffer_array_non_uniform_indexing,
+        ShaderStorageTexelBufferArrayNonUniformIndexing => shader_storage_texel_buffer_array_non_uniform_indexing,
+        DescriptorBindingUniformBufferUpdateAfterBind => descriptor_binding_uniform_buffer_update_after_bind,
+        DescriptorBindingSampledImageUpdateAfterBind => descriptor_binding_sampled_image_update_after_bind,
+        DescriptorBindingStorageImageUpdateAfterBind => descriptor_binding_storage_image_update_after_bind,
+        DescriptorBindingStorageBufferUpdateAfterBind => descriptor_binding_storage_buffer_update_after_bind,
+        DescriptorBindingUniformTexelBufferUpdateAfterBind => descriptor_binding_uniform_texel_buffer_update_after_bind,
+        DescriptorBindingStorageTexelBufferUpdateAfterBind => descriptor_binding_storage_texel_buffer_update_after_bind,
+        DescriptorBindingUpdateUnusedWhilePending => descriptor_binding_update_unused_while_pending,
+        DescriptorBindingPartiallyBound => descriptor_binding_partially_bound,
+        DescriptorBindingVariableDescriptorCount => descriptor_binding_variable_descriptor_count,
+        RuntimeDescriptorArray => runtime_descriptor_array,
+        SamplerFilterMinmax => sampler_filter_minmax,
+        ScalarBlockLayout => scalar_block_layout,
+        ImagelessFramebuffer => imageless_framebuffer,
+        UniformBufferStandardLayout => uniform_buffer_standard_layout,
+        ShaderSubgroupExtendedTypes => shader_subgroup_extended_types,
+        SeparateDepthStencilLayouts => separate_depth_stencil_layouts,
+        HostQueryReset => host_query_reset,
+        TimelineSemaphore => timeline_semaphore,
+        BufferDeviceAddress => buffer_device_address,
+        BufferDeviceAddressCaptureReplay => buffer_device_address_capture_replay,
+        BufferDeviceAddressMultiDevice => buffer_device_address_multi_device,
+        VulkanMemoryModel => vulkan_memory_model,
+        VulkanMemoryModelDeviceScope => vulkan_memory_model_device_scope,
+        VulkanMemoryModelAvailabilityVisibilityChains => vulkan_memory_model_availability_visibility_chains,
+        ShaderOutputViewportIndex => shader_output_viewport_index,
+        ShaderOutputLayer => shader_output_layer,
+        SubgroupBroadcastDynamicId => subgroup_broadcast_dynamic_id
+    }
+    features_13 {
+        RobustImageAccess => robust_image_access,
+        InlineUniformBlock => inline_uniform_block,
+        DescriptorBindingInlineUniformBlockUpdateAfterBind => descriptor_binding_inline_uniform_block_update_after_bind,
+        PipelineCreationCacheControl => pipeline_creation_cache_control,
+        PrivateData => private_data,
+        ShaderDemoteToHelperInvocation => shader_demote_to_helper_invocation,
+        ShaderTerminateInvocation => shader_terminate_invocation,
+        SubgroupSizeControl => subgroup_size_control,
+        ComputeFullSubgroups => compute_full_subgroups,
+        Synchronization2 => synchronization2,
+        TextureCompressionAstcHdr => texture_compression_astc_hdr,
+        ShaderZeroInitializeWorkgroupMemory => shader_zero_initialize_workgroup_memory,
+        DynamicRendering => dynamic_rendering,
+        ShaderIntegerDotProduct => shader_integer_dot_product,
+        Maintenance4 => maintenance4
+    }
+}
+
+impl DeviceFeature {
+    //Features that have to be enabled as well when enabling this one
+    pub fn dependencies(self) -> &'static [Self] {
+        match self {
+            Self::MultiviewGeometryShader => &[Self::Multiview, Self::GeometryShader],
+            Self::MultiviewTessellationShader => &[Self::Multiview, Self::TessellationShader],
+            Self::VariablePointers => &[Self::VariablePointersStorageBuffer],
+            Self::BufferDeviceAddressCaptureReplay | Self::BufferDeviceAddressMultiDevice => &[Self::BufferDeviceAddress],
+            Self::VulkanMemoryModelDeviceScope | Self::VulkanMemoryModelAvailabilityVisibilityChains => &[Self::VulkanMemoryModel],
+            _ => &[]
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FeatureRequirement {
+    //Device creation fails if the feature is not supported
+    Required,
+    //Enabled if the feature is supported
+    Optional
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DeviceFeatureRequests {
+    requests: Vec<(DeviceFeature, FeatureRequirement)>
+}
+
+impl DeviceFeatureRequests {
+    //Requesting a feature again keeps the stricter requirement, the dependencies of the feature are requested with the same requirement
+    pub fn request(&mut self, feature: DeviceFeature, requirement: FeatureRequirement) -> &mut Self {
+        match self.requests.iter_mut().find(|(requested, _)| *requested == feature) {
+            Some((_, current)) => {
+                if requirement == FeatureRequirement::Required {
+                    *current = FeatureRequirement::Required;
+                }
+            }
+            None => self.requests.push((feature, requirement))
+        }
+
+        for dependency in feature.dependencies() {
+            self.request(*dependency, requirement);
+        }
+
+        self
+    }
+
+    #[inline]
+    pub fn require(&mut self, feature: DeviceFeature) -> &mut Self {
+        self.request(feature, FeatureRequirement::Required)
+    }
+
+    #[inline]
+    pub fn request_optional(&mut self, feature: DeviceFeature) -> &mut Self {
+        self.request(feature, FeatureRequirement::Optional)
+    }
+
+    #[inline]
+    pub fn requirement(&self, feature: DeviceFeature) -> Option<FeatureRequirement> {
+        self.requests.iter().find(|(requested, _)| *requested == feature).map(|(_, requirement)| *requirement)
+    }
+
+    //Features to enable and the report of the optional ones, fails if any required feature is not supported.
+    //Optional features are denied if one of their dependencies is not supported.
+    pub fn negotiate(&self, supported: &DeviceFeatures) -> Result<(DeviceFeatures, DeviceFeatureReport), MissingFeaturesError> {
+        fn is_available(supported: &DeviceFeatures, feature: DeviceFeature) -> bool {
+            supported.get(feature) && feature.dependencies().iter().all(|dependency| is_available(supported, *dependency))
+        }
+
+        let mut enabled = DeviceFeatures::default();
+        let mut report = DeviceFeatureReport::default();
+        let mut missing = Vec::new();
+
+        for &(feature, requirement) in self.requests.iter() {
+            let is_available = is_available(supported, feature);
+
+            match requirement {
+                //Dependencies are required as well, so unsupported ones are reported themselves
+                FeatureRequirement::Required if !supported.get(feature) => missing.push(feature),
+                FeatureRequirement::Required if is_available => report.required.push(feature),
+                FeatureRequirement::Required => {}
+                FeatureRequirement::Optional if is_available => report.granted.push(feature),
+                FeatureRequirement::Optional => report.denied.push(feature)
+            }
+
+            if is_available {
+                enabled.set(feature, true);
+            }
+        }
+
+        if !missing.is_empty() {
+            return Err(MissingFeaturesError { missing })
+        }
+
+        Ok((enabled, report))
+    }
+}
+
+//Outcome of the feature negotiation of a device
+#[derive(Clone, Debug, Default)]
+pub struct DeviceFeatureReport {
+    required: Vec<DeviceFeature>,
+    granted: Vec<DeviceFeature>,
+    denied: Vec<DeviceFeature>
+}
+
+impl DeviceFeatureReport {
+    #[inline]
+    pub fn required(&self) -> &[DeviceFeature] {
+        &self.required
+    }
+
+    //Optional features that are supported and enabled
+    #[inline]
+    pub fn granted(&self) -> &[DeviceFeature] {
+        &self.granted
+    }
+
+    //Optional features that are not supported and stay disabled
+    #[inline]
+    pub fn denied(&self) -> &[DeviceFeature] {
+        &self.denied
+    }
+
+    #[inline]
+    pub fn is_enabled(&self, feature: DeviceFeature) -> bool {
+        self.required.contains(&feature) || self.granted.contains(&feature)
+    }
+}
+
+//Returned by Device::new, can be recovered from the anyhow error with downcast_ref
+#[derive(Clone, Debug)]
+pub struct MissingFeaturesError {
+    pub missing: Vec<DeviceFeature>
+}
+
+impl fmt::Display for MissingFeaturesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Device does not support the required features ")?;
+
+        for (i, feature) in self.missing.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", feature.name())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for MissingFeaturesError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supported(features: &[DeviceFeature]) -> DeviceFeatures {
+        let mut supported = DeviceFeatures::default();
+        for feature in features {
+            supported.set(*feature, true);
+        }
+
+        supported
+    }
+
+    #[test]
+    fn enables_required_features() {
+        let mut requests = DeviceFeatureRequests::default();
+        requests.require(DeviceFeature::TimelineSemaphore);
+
+        let (enabled, report) = requests.negotiate(&supported(&[DeviceFeature::TimelineSemaphore, DeviceFeature::ShaderInt64])).unwrap();
+        assert!(enabled.get(DeviceFeature::TimelineSemaphore));
+        assert!(!enabled.get(DeviceFeature::ShaderInt64));
+        assert_eq!(report.required(), &[DeviceFeature::TimelineSemaphore]);
+    }
+
+    #[test]
+    fn reports_missing_required_features() {
+        let mut requests = DeviceFeatureRequests::default();
+        requests
+            .require(DeviceFeature::ShaderInt64)
+            .require(DeviceFeature::ShaderFloat64)
+            .require(DeviceFeature::ShaderInt16);
+
+        let error = requests.negotiate(&supported(&[DeviceFeature::ShaderInt16])).unwrap_err();
+        assert_eq!(error.missing, vec![DeviceFeature::ShaderInt64, DeviceFeature::ShaderFloat64]);
+        assert_eq!(error.to_string(), "Device does not support the required features features.shader_int64, features.shader_float64");
+    }
+
+    #[test]
+    fn grants_and_denies_optional_features() {
+        let mut requests = DeviceFeatureRequests::default();
+        requests.request_optional(DeviceFeature::SamplerAnisotropy).request_optional(DeviceFeature::WideLines);
+
+        let (enabled, report) = requests.negotiate(&supported(&[DeviceFeature::SamplerAnisotropy])).unwrap();
+        assert!(enabled.get(DeviceFeature::SamplerAnisotropy));
+        assert!(!enabled.get(DeviceFeature::WideLines));
+        assert_eq!(report.granted(), &[DeviceFeature::SamplerAnisotropy]);
+        assert_eq!(report.denied(), &[DeviceFeature::WideLines]);
+    }
+
+    #[test]
+    fn keeps_the_stricter_requirement() {
+        let mut requests = DeviceFeatureRequests::default();
+        requests
+            .request_optional(DeviceFeature::ShaderInt64)
+            .require(DeviceFeature::ShaderInt64)
+            .request_optional(DeviceFeature::ShaderInt64);
+
+        assert_eq!(requests.requirement(DeviceFeature::ShaderInt64), Some(FeatureRequirement::Required));
+        assert!(requests.negotiate(&supported(&[])).is_err());
+    }
+
+    #[test]
+    fn requests_dependencies() {
+        let mut requests = DeviceFeatureRequests::default();
+        requests
+            .request_optional(DeviceFeature::MultiviewGeometryShader)
+            .require(DeviceFeature::BufferDeviceAddressCaptureReplay)
+            .require(DeviceFeature::DescriptorBindingPartiallyBound);
+
+        assert_eq!(requests.requirement(DeviceFeature::Multiview), Some(FeatureRequirement::Optional));
+        assert_eq!(requests.requirement(DeviceFeature::BufferDeviceAddress), Some(FeatureRequirement::Required));
+        assert_eq!(requests.requirement(DeviceFeature::DescriptorIndexing), None);
+
+        let error = requests
+            .negotiate(&supported(&[DeviceFeature::BufferDeviceAddressCaptureReplay, DeviceFeature::DescriptorBindingPartiallyBound]))
+            .unwrap_err();
+        assert_eq!(error.missing, vec![DeviceFeature::BufferDeviceAddress]);
+    }
+
+    #[test]
+    fn denies_optional_features_without_dependencies() {
+        let mut requests = DeviceFeatureRequests::default();
+        requests.request_optional(DeviceFeature::MultiviewGeometryShader);
+
+        let (enabled, report) = requests.negotiate(&supported(&[DeviceFeature::MultiviewGeometryShader, DeviceFeature::GeometryShader])).unwrap();
+        assert!(!enabled.get(DeviceFeature::MultiviewGeometryShader));
+        assert!(enabled.get(DeviceFeature::GeometryShader));
+        assert!(report.denied().contains(&DeviceFeature::MultiviewGeometryShader));
+        assert!(report.denied().contains(&DeviceFeature::Multiview));
+    }
+}
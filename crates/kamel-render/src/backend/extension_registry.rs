@@ -0,0 +1,393 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    sync::{Arc, Mutex}
+};
+
+use anyhow::Result;
+use ash::{
+    extensions::{
+        ext::{CalibratedTimestamps, DebugUtils},
+        khr::{GetSurfaceCapabilities2, Surface, Swapchain}
+    },
+    vk, Entry
+};
+
+//Extension and the extensions it depends on, dependencies promoted to Vulkan 1.3 are left out since it is always the core version
+pub(crate) struct ExtensionDependencies {
+    name: &'static CStr,
+    dependencies: &'static [&'static CStr],
+    //Instance extensions a device extension depends on, they have to be enabled when creating the instance
+    instance_dependencies: &'static [&'static CStr]
+}
+
+const fn name(bytes: &'static [u8]) -> &'static CStr {
+    unsafe { CStr::from_bytes_with_nul_unchecked(bytes) }
+}
+
+const KHR_SURFACE: &CStr = name(b"VK_KHR_surface\0");
+const KHR_GET_SURFACE_CAPABILITIES2: &CStr = name(b"VK_KHR_get_surface_capabilities2\0");
+const EXT_SURFACE_MAINTENANCE1: &CStr = name(b"VK_EXT_surface_maintenance1\0");
+const KHR_SWAPCHAIN: &CStr = name(b"VK_KHR_swapchain\0");
+const KHR_PRESENT_ID: &CStr = name(b"VK_KHR_present_id\0");
+const KHR_DEFERRED_HOST_OPERATIONS: &CStr = name(b"VK_KHR_deferred_host_operations\0");
+const KHR_ACCELERATION_STRUCTURE: &CStr = name(b"VK_KHR_acceleration_structure\0");
+
+pub(crate) const INSTANCE_EXTENSION_DEPENDENCIES: &[ExtensionDependencies] = &[
+    ExtensionDependencies {
+        name: KHR_GET_SURFACE_CAPABILITIES2,
+        dependencies: &[KHR_SURFACE],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_EXT_swapchain_colorspace\0"),
+        dependencies: &[KHR_SURFACE],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: EXT_SURFACE_MAINTENANCE1,
+        dependencies: &[KHR_SURFACE, KHR_GET_SURFACE_CAPABILITIES2],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_KHR_win32_surface\0"),
+        dependencies: &[KHR_SURFACE],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_KHR_xlib_surface\0"),
+        dependencies: &[KHR_SURFACE],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_KHR_xcb_surface\0"),
+        dependencies: &[KHR_SURFACE],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_KHR_wayland_surface\0"),
+        dependencies: &[KHR_SURFACE],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_KHR_android_surface\0"),
+        dependencies: &[KHR_SURFACE],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_EXT_metal_surface\0"),
+        dependencies: &[KHR_SURFACE],
+        instance_dependencies: &[]
+    }
+];
+
+pub(crate) const DEVICE_EXTENSION_DEPENDENCIES: &[ExtensionDependencies] = &[
+    ExtensionDependencies {
+        name: KHR_SWAPCHAIN,
+        dependencies: &[],
+        instance_dependencies: &[KHR_SURFACE]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_KHR_swapchain_mutable_format\0"),
+        dependencies: &[KHR_SWAPCHAIN],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: KHR_PRESENT_ID,
+        dependencies: &[KHR_SWAPCHAIN],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_KHR_present_wait\0"),
+        dependencies: &[KHR_SWAPCHAIN, KHR_PRESENT_ID],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_EXT_swapchain_maintenance1\0"),
+        dependencies: &[KHR_SWAPCHAIN],
+        instance_dependencies: &[EXT_SURFACE_MAINTENANCE1]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_EXT_full_screen_exclusive\0"),
+        dependencies: &[KHR_SWAPCHAIN],
+        instance_dependencies: &[KHR_GET_SURFACE_CAPABILITIES2]
+    },
+    ExtensionDependencies {
+        name: KHR_ACCELERATION_STRUCTURE,
+        dependencies: &[KHR_DEFERRED_HOST_OPERATIONS],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_KHR_ray_tracing_pipeline\0"),
+        dependencies: &[KHR_ACCELERATION_STRUCTURE],
+        instance_dependencies: &[]
+    },
+    ExtensionDependencies {
+        name: name(b"VK_KHR_ray_query\0"),
+        dependencies: &[KHR_ACCELERATION_STRUCTURE],
+        instance_dependencies: &[]
+    }
+];
+
+//Typed function loader of an instance extension, attached by Instance::extension_loader once the extension is enabled.
+//Loaders of other crates can be wrapped in a local type that implements this.
+pub trait InstanceExtensionLoader: Send + Sync + 'static {
+    fn extension_name() -> &'static CStr;
+
+    unsafe fn new(entry: &Entry, instance: &ash::Instance) -> Self;
+}
+
+//Typed function loader of a device extension, attached by Device::extension_loader once the extension is enabled
+pub trait DeviceExtensionLoader: Send + Sync + 'static {
+    fn extension_name() -> &'static CStr;
+
+    unsafe fn new(entry: &Entry, instance: &ash::Instance, device: &ash::Device) -> Self;
+}
+
+macro_rules! instance_extension_loaders {
+    ($($loader:ty),*) => {
+        $(
+            impl InstanceExtensionLoader for $loader {
+                #[inline]
+                fn extension_name() -> &'static CStr {
+                    <$loader>::name()
+                }
+
+                #[inline]
+                unsafe fn new(entry: &Entry, instance: &ash::Instance) -> Self {
+                    <$loader>::new(entry, instance)
+                }
+            }
+        )*
+    };
+}
+
+instance_extension_loaders!(DebugUtils, GetSurfaceCapabilities2, Surface);
+
+impl DeviceExtensionLoader for Swapchain {
+    #[inline]
+    fn extension_name() -> &'static CStr {
+        Swapchain::name()
+    }
+
+    #[inline]
+    unsafe fn new(_entry: &Entry, instance: &ash::Instance, device: &ash::Device) -> Self {
+        Swapchain::new(instance, device)
+    }
+}
+
+//Loaded through the instance, the device handle is passed to its commands
+impl DeviceExtensionLoader for CalibratedTimestamps {
+    #[inline]
+    fn extension_name() -> &'static CStr {
+        CalibratedTimestamps::name()
+    }
+
+    #[inline]
+    unsafe fn new(entry: &Entry, instance: &ash::Instance, _device: &ash::Device) -> Self {
+        CalibratedTimestamps::new(entry, instance)
+    }
+}
+
+//Loaders created on first use, keyed by their type
+#[derive(Default)]
+pub(crate) struct ExtensionLoaders(Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>);
+
+impl ExtensionLoaders {
+    pub(crate) fn get_or_insert_with<T: Send + Sync + 'static>(&self, create: impl FnOnce() -> T) -> Arc<T> {
+        let loader = self.0.lock().unwrap().entry(TypeId::of::<T>()).or_insert_with(|| Arc::new(create())).clone();
+
+        //Entries are only inserted under the TypeId of their own type
+        loader.downcast().unwrap()
+    }
+}
+
+//Supported and enabled extensions keyed by name
+pub struct ExtensionRegistry {
+    supported: Vec<(CString, u32)>,
+    enabled: Vec<CString>,
+
+    dependencies: &'static [ExtensionDependencies],
+    //Empty for instance extensions
+    instance_extensions: Vec<CString>
+}
+
+impl ExtensionRegistry {
+    pub(crate) fn new(supported: &[vk::ExtensionProperties], dependencies: &'static [ExtensionDependencies], instance_extensions: Vec<CString>) -> Self {
+        let mut supported: Vec<_> = supported
+            .iter()
+            .map(|properties| (unsafe { CStr::from_ptr(properties.extension_name.as_ptr()) }.to_owned(), properties.spec_version))
+            .collect();
+        //Layers may report extensions that are also provided by the implementation
+        supported.sort();
+        supported.dedup_by(|a, b| a.0 == b.0);
+
+        Self {
+            supported,
+            enabled: Vec::new(),
+
+            dependencies,
+            instance_extensions
+        }
+    }
+
+    #[inline]
+    fn find_dependencies(&self, name: &CStr) -> Option<&'static ExtensionDependencies> {
+        self.dependencies.iter().find(|dependencies| dependencies.name == name)
+    }
+
+    #[inline]
+    fn dependencies(&self, name: &CStr) -> &'static [&'static CStr] {
+        self.find_dependencies(name).map_or(&[], |dependencies| dependencies.dependencies)
+    }
+
+    #[inline]
+    fn instance_dependencies(&self, name: &CStr) -> &'static [&'static CStr] {
+        self.find_dependencies(name).map_or(&[], |dependencies| dependencies.instance_dependencies)
+    }
+
+    fn can_enable(&self, name: &CStr) -> bool {
+        self.is_enabled(name)
+            || (self.is_supported(name)
+                && self
+                    .instance_dependencies(name)
+                    .iter()
+                    .all(|dependency| self.instance_extensions.iter().any(|enabled| enabled.as_c_str() == *dependency))
+                && self.dependencies(name).iter().all(|dependency| self.can_enable(dependency)))
+    }
+
+    fn enable_unchecked(&mut self, name: &CStr) {
+        if self.is_enabled(name) {
+            return
+        }
+
+        for dependency in self.dependencies(name) {
+            self.enable_unchecked(dependency);
+        }
+
+        self.enabled.push(name.to_owned());
+    }
+
+    #[inline]
+    pub fn is_supported(&self, name: &CStr) -> bool {
+        self.supported.iter().any(|(supported, _)| supported.as_c_str() == name)
+    }
+
+    #[inline]
+    pub fn spec_version(&self, name: &CStr) -> Option<u32> {
+        self.supported.iter().find(|(supported, _)| supported.as_c_str() == name).map(|(_, spec_version)| *spec_version)
+    }
+
+    //Enables the extension and the extensions it depends on, nothing is enabled if any of them is not supported
+    //or an instance extension they depend on was not enabled
+    pub fn enable(&mut self, name: &CStr) -> bool {
+        if self.can_enable(name) {
+            self.enable_unchecked(name);
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    pub fn require(&mut self, name: &CStr) -> Result<()> {
+        if !self.enable(name) {
+            anyhow::bail!("Extension {} or one of its dependencies is not supported or misses an instance extension", name.to_string_lossy());
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_enabled(&self, name: &CStr) -> bool {
+        self.enabled.iter().any(|enabled| enabled.as_c_str() == name)
+    }
+
+    #[inline]
+    pub fn supported(&self) -> impl Iterator<Item = &CStr> {
+        self.supported.iter().map(|(supported, _)| supported.as_c_str())
+    }
+
+    //In the order they were enabled, dependencies come before the extensions that depend on them
+    #[inline]
+    pub fn enabled(&self) -> impl Iterator<Item = &CStr> {
+        self.enabled.iter().map(CString::as_c_str)
+    }
+
+    //The pointers stay valid as long as the registry is alive
+    #[inline]
+    pub(crate) fn enabled_names(&self) -> Vec<*const c_char> {
+        self.enabled.iter().map(|enabled| enabled.as_ptr()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KHR_RAY_QUERY: &CStr = name(b"VK_KHR_ray_query\0");
+
+    fn properties(name: &CStr) -> vk::ExtensionProperties {
+        let mut properties = vk::ExtensionProperties::default();
+        for (dst, src) in properties.extension_name.iter_mut().zip(name.to_bytes()) {
+            *dst = *src as c_char;
+        }
+
+        properties
+    }
+
+    fn registry(supported: &[&CStr], instance_extensions: &[&CStr]) -> ExtensionRegistry {
+        let supported: Vec<_> = supported.iter().copied().map(properties).collect();
+
+        ExtensionRegistry::new(&supported, DEVICE_EXTENSION_DEPENDENCIES, instance_extensions.iter().map(|name| (*name).to_owned()).collect())
+    }
+
+    #[test]
+    fn enables_dependencies_first() {
+        let mut registry = registry(&[KHR_RAY_QUERY, KHR_ACCELERATION_STRUCTURE, KHR_DEFERRED_HOST_OPERATIONS], &[]);
+
+        assert!(registry.can_enable(KHR_RAY_QUERY));
+        registry.enable_unchecked(KHR_RAY_QUERY);
+        assert_eq!(
+            registry.enabled().collect::<Vec<_>>(),
+            vec![KHR_DEFERRED_HOST_OPERATIONS, KHR_ACCELERATION_STRUCTURE, KHR_RAY_QUERY]
+        );
+    }
+
+    #[test]
+    fn enables_extensions_once() {
+        let mut registry = registry(&[KHR_ACCELERATION_STRUCTURE, KHR_DEFERRED_HOST_OPERATIONS], &[]);
+
+        assert!(registry.enable(KHR_DEFERRED_HOST_OPERATIONS));
+        assert!(registry.enable(KHR_ACCELERATION_STRUCTURE));
+        assert!(registry.enable(KHR_ACCELERATION_STRUCTURE));
+        assert_eq!(registry.enabled().count(), 2);
+    }
+
+    #[test]
+    fn rejects_unsupported_dependencies() {
+        let mut registry = registry(&[KHR_RAY_QUERY, KHR_ACCELERATION_STRUCTURE], &[]);
+
+        assert!(!registry.can_enable(KHR_RAY_QUERY));
+        assert!(!registry.enable(KHR_RAY_QUERY));
+        assert!(registry.require(KHR_RAY_QUERY).is_err());
+        assert_eq!(registry.enabled().count(), 0);
+    }
+
+    #[test]
+    fn requires_instance_dependencies() {
+        assert!(!registry(&[KHR_SWAPCHAIN], &[]).can_enable(KHR_SWAPCHAIN));
+        assert!(registry(&[KHR_SWAPCHAIN], &[KHR_SURFACE]).can_enable(KHR_SWAPCHAIN));
+        assert!(!registry(&[KHR_SWAPCHAIN, KHR_PRESENT_ID], &[]).can_enable(KHR_PRESENT_ID));
+    }
+
+    #[test]
+    fn deduplicates_supported_extensions() {
+        let registry = registry(&[KHR_SWAPCHAIN, KHR_SWAPCHAIN], &[KHR_SURFACE]);
+
+        assert_eq!(registry.supported().count(), 1);
+        assert!(!registry.is_supported(KHR_PRESENT_ID));
+    }
+}
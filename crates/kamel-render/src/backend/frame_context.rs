@@ -0,0 +1,243 @@
+use std::{mem, sync::Arc};
+
+use anyhow::Result;
+use ash::vk;
+use kamel_bevy::ecs::{self as bevy_ecs, system::Resource};
+
+use crate::backend::{
+    sync::{BinarySemaphore, BinarySemaphoreDesc, TimelineSemaphore, TimelineSemaphoreDesc},
+    CommandBuffer, CommandBufferDesc, CommandPool, CommandPoolDesc, Device, Executable, Initial, PendingCommandBuffers, QueueType, SemaphoreSubmit, SubmitDesc
+};
+
+#[derive(Copy, Clone, Debug)]
+pub struct FrameContextDesc<'a> {
+    pub frames_in_flight: u32,
+    //Queue the frames are submitted to
+    pub queue_type: QueueType,
+    pub label: Option<&'a str>
+}
+
+pub struct Frame {
+    command_pool: Arc<CommandPool>,
+    acquire_semaphore: BinarySemaphore,
+    present_semaphore: BinarySemaphore,
+
+    //Value of the frame context's timeline semaphore signaled by the last submission of the frame
+    timeline_value: u64,
+    command_buffers: Vec<CommandBuffer<Initial>>,
+    pending_command_buffers: Vec<PendingCommandBuffers>
+}
+
+impl Frame {
+    fn new(device: &Device, family_index: u32, label: Option<&str>, index: usize) -> Result<Self> {
+        let frame_label = |name: &str| label.map(|label| format!("{} {} {}", label, name, index));
+
+        let command_pool = CommandPool::new(
+            device.clone(),
+            &CommandPoolDesc {
+                flags: vk::CommandPoolCreateFlags::TRANSIENT | vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+                family_index,
+                label: frame_label("command pool").as_deref()
+            }
+        )?;
+
+        let acquire_semaphore = BinarySemaphore::new(
+            device.clone(),
+            &BinarySemaphoreDesc {
+                label: frame_label("acquire semaphore").as_deref()
+            }
+        )?;
+
+        let present_semaphore = BinarySemaphore::new(
+            device.clone(),
+            &BinarySemaphoreDesc {
+                label: frame_label("present semaphore").as_deref()
+            }
+        )?;
+
+        Ok(Self {
+            command_pool: Arc::new(command_pool),
+            acquire_semaphore,
+            present_semaphore,
+
+            timeline_value: 0,
+            command_buffers: Vec::new(),
+            pending_command_buffers: Vec::new()
+        })
+    }
+
+    #[inline]
+    pub fn command_pool(&self) -> &Arc<CommandPool> {
+        &self.command_pool
+    }
+
+    //Signaled by Swapchain::acquire_next_image
+    #[inline]
+    pub fn acquire_semaphore(&self) -> &BinarySemaphore {
+        &self.acquire_semaphore
+    }
+
+    //Has to be signaled by the submission that renders to the swapchain image, by passing it in SubmitDesc::signal_semaphores,
+    //and waited on by Swapchain::present. FrameContext::submit does not signal it on its own.
+    #[inline]
+    pub fn present_semaphore(&self) -> &BinarySemaphore {
+        &self.present_semaphore
+    }
+
+    #[inline]
+    pub fn timeline_value(&self) -> u64 {
+        self.timeline_value
+    }
+}
+
+//Rotates the per frame resources of N frames in flight, the CPU waits for the GPU to finish a frame before its resources are reused
+#[derive(Resource)]
+pub struct FrameContext {
+    frames: Vec<Frame>,
+    frame_index: usize,
+    frame_count: u64,
+
+    timeline_semaphore: TimelineSemaphore,
+    timeline_value: u64,
+
+    queue_type: QueueType,
+    device: Device
+}
+
+impl FrameContext {
+    pub fn new(device: Device, desc: &FrameContextDesc) -> Result<Self> {
+        if desc.frames_in_flight == 0 {
+            anyhow::bail!("A frame context needs at least one frame in flight");
+        }
+
+        let family_index = device.queue(desc.queue_type).family_index();
+
+        let frames = (0..desc.frames_in_flight as usize)
+            .map(|index| Frame::new(&device, family_index, desc.label, index))
+            .collect::<Result<Vec<_>>>()?;
+
+        let timeline_semaphore = TimelineSemaphore::new(
+            device.clone(),
+            &TimelineSemaphoreDesc {
+                initial_value: 0,
+                label: desc.label.map(|label| format!("{} timeline semaphore", label)).as_deref()
+            }
+        )?;
+
+        Ok(Self {
+            frames,
+            frame_index: 0,
+            frame_count: 0,
+
+            timeline_semaphore,
+            timeline_value: 0,
+
+            queue_type: desc.queue_type,
+            device
+        })
+    }
+
+    //Waits until the GPU finished the last frame that used the next frame's resources, recycles its command buffers
+    //and destroys dropped resources that are no longer in use. The current frame stays the same if the frame could not be recycled.
+    pub fn begin_frame(&mut self, timeout: u64) -> Result<()> {
+        let frame_index = (self.frame_count % self.frames.len() as u64) as usize;
+        let frame = &mut self.frames[frame_index];
+
+        unsafe { self.timeline_semaphore.wait_for_value(frame.timeline_value, timeout) }?;
+
+        let mut pending_command_buffers = mem::take(&mut frame.pending_command_buffers).into_iter();
+        while let Some(pending) = pending_command_buffers.next() {
+            let completed_command_buffers = match pending.try_complete() {
+                Ok(completed_command_buffers) => completed_command_buffers,
+                Err((pending, result)) => {
+                    //Kept so they are recycled by the next attempt instead of blocking in their drop
+                    frame.pending_command_buffers.push(pending);
+                    frame.pending_command_buffers.extend(pending_command_buffers);
+
+                    anyhow::bail!("Command buffers of frame {} could not be recycled: {}", frame_index, result)
+                }
+            };
+
+            for completed_command_buffer in completed_command_buffers {
+                frame.command_buffers.push(completed_command_buffer.reset(vk::CommandBufferResetFlags::empty())?);
+            }
+        }
+
+        self.frame_index = frame_index;
+        self.frame_count += 1;
+
+        frame.command_pool.free_retired()?;
+        self.device.process_deferred_destructions()?;
+
+        Ok(())
+    }
+
+    //Recycled command buffer of the current frame, they are returned to the frame by submit
+    pub fn command_buffer(&mut self) -> Result<CommandBuffer<Initial>> {
+        let frame = &mut self.frames[self.frame_index];
+
+        match frame.command_buffers.pop() {
+            Some(command_buffer) => Ok(command_buffer),
+            None => CommandBuffer::new(self.device.clone(), frame.command_pool.clone(), &CommandBufferDesc { label: None })
+        }
+    }
+
+    //Every submission signals the next timeline value, the frame is reused once the value of its last submission is reached
+    pub fn submit(&mut self, command_buffers: Vec<CommandBuffer<Executable>>, desc: &SubmitDesc) -> Result<()> {
+        self.timeline_value += 1;
+
+        let mut signal_semaphores = desc.signal_semaphores.to_vec();
+        signal_semaphores.push(SemaphoreSubmit::Timeline {
+            semaphore: &self.timeline_semaphore,
+            value: self.timeline_value,
+            stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS
+        });
+
+        let pending_command_buffers = unsafe {
+            self.device.queue(self.queue_type).submit(
+                command_buffers,
+                &SubmitDesc {
+                    signal_semaphores: &signal_semaphores,
+                    ..*desc
+                }
+            )
+        }?;
+
+        let frame = &mut self.frames[self.frame_index];
+        frame.timeline_value = self.timeline_value;
+        frame.pending_command_buffers.push(pending_command_buffers);
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn frame(&self) -> &Frame {
+        &self.frames[self.frame_index]
+    }
+
+    #[inline]
+    pub fn frame_index(&self) -> usize {
+        self.frame_index
+    }
+
+    #[inline]
+    pub fn frames_in_flight(&self) -> usize {
+        self.frames.len()
+    }
+
+    //Number of frames begun so far
+    #[inline]
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    #[inline]
+    pub fn timeline_semaphore(&self) -> &TimelineSemaphore {
+        &self.timeline_semaphore
+    }
+
+    #[inline]
+    pub fn queue_type(&self) -> QueueType {
+        self.queue_type
+    }
+}
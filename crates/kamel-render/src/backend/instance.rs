@@ -1,5 +1,6 @@
 use std::{
     ffi::CStr,
+    ops::{Deref, DerefMut},
     os::raw::{c_char, c_void},
     sync::Arc
 };
@@ -16,7 +17,14 @@ use ash::{
 use kamel_bevy::ecs::{self as bevy_ecs, system::Resource};
 use raw_window_handle::HasRawWindowHandle;
 
-use crate::backend::util::message_severity;
+use crate::backend::{
+    extension_registry::{ExtensionLoaders, INSTANCE_EXTENSION_DEPENDENCIES},
+    physical_device_selection,
+    util::message_severity,
+    AdapterInfo, ExtensionRegistry, InstanceExtensionLoader, PhysicalDeviceCandidate, PhysicalDeviceSelectionDesc
+};
+
+const EXT_VALIDATION_FEATURES: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"VK_EXT_validation_features\0") };
 
 #[inline]
 fn application_info_from_cargo_toml(api_version: u32) -> vk::ApplicationInfo<'static> {
@@ -93,58 +101,25 @@ unsafe impl Send for InstanceLayers {}
 unsafe impl Sync for InstanceLayers {}
 
 pub struct InstanceExtensions {
-    supported: Vec<vk::ExtensionProperties>,
-    supported_khronos_validation: Vec<vk::ExtensionProperties>,
-    enabled: Vec<*const c_char>,
-
-    ext_debug_utils: bool,
-    ext_validation_features: bool,
-    khr_get_surface_capabilities2: bool,
-    khr_surface: bool
+    registry: ExtensionRegistry
 }
 
 impl InstanceExtensions {
     pub fn new(entry_loader: &Entry, layers: &InstanceLayers) -> VkResult<Self> {
-        let supported = entry_loader.enumerate_instance_extension_properties(None)?;
+        let mut supported = entry_loader.enumerate_instance_extension_properties(None)?;
 
-        let supported_khronos_validation = if layers.khronos_validation() {
-            entry_loader.enumerate_instance_extension_properties(Some(unsafe { CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_KHRONOS_validation\0") }))?
-        } else {
-            Vec::new()
-        };
+        if layers.khronos_validation() {
+            supported.extend(entry_loader.enumerate_instance_extension_properties(Some(unsafe { CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_KHRONOS_validation\0") }))?);
+        }
 
         Ok(Self {
-            supported,
-            supported_khronos_validation,
-            enabled: Vec::new(),
-
-            ext_debug_utils: false,
-            ext_validation_features: false,
-            khr_get_surface_capabilities2: false,
-            khr_surface: false
+            registry: ExtensionRegistry::new(&supported, INSTANCE_EXTENSION_DEPENDENCIES, Vec::new())
         })
     }
 
-    #[inline]
-    unsafe fn try_push(&mut self, name: *const c_char) -> bool {
-        if self.supported.iter().any(|e| libc::strcmp(e.extension_name.as_ptr(), name) == 0)
-            || self.supported_khronos_validation.iter().any(|e| libc::strcmp(e.extension_name.as_ptr(), name) == 0)
-        {
-            self.enabled.push(name);
-            true
-        } else {
-            false
-        }
-    }
-
     #[inline]
     pub fn try_push_ext_debug_utils(&mut self) -> bool {
-        if unsafe { self.try_push(DebugUtils::name().as_ptr()) } {
-            self.ext_debug_utils = true;
-            true
-        } else {
-            false
-        }
+        self.enable(DebugUtils::name())
     }
 
     #[inline]
@@ -154,17 +129,12 @@ impl InstanceExtensions {
 
     #[inline]
     pub fn ext_debug_utils(&self) -> bool {
-        self.ext_debug_utils
+        self.is_enabled(DebugUtils::name())
     }
 
     #[inline]
     pub fn try_push_ext_validation_features(&mut self) -> bool {
-        if unsafe { self.try_push(b"VK_EXT_validation_features\0".as_ptr().cast()) } {
-            self.ext_validation_features = true;
-            true
-        } else {
-            false
-        }
+        self.enable(EXT_VALIDATION_FEATURES)
     }
 
     #[inline]
@@ -174,17 +144,12 @@ impl InstanceExtensions {
 
     #[inline]
     pub fn ext_validation_features(&self) -> bool {
-        self.ext_validation_features
+        self.is_enabled(EXT_VALIDATION_FEATURES)
     }
 
     #[inline]
     pub fn try_push_get_surface_capabilities2(&mut self) -> bool {
-        if unsafe { self.try_push(GetSurfaceCapabilities2::name().as_ptr()) } {
-            self.khr_get_surface_capabilities2 = true;
-            true
-        } else {
-            false
-        }
+        self.enable(GetSurfaceCapabilities2::name())
     }
 
     #[inline]
@@ -194,17 +159,12 @@ impl InstanceExtensions {
 
     #[inline]
     pub fn khr_get_surface_capabilities2(&self) -> bool {
-        self.khr_get_surface_capabilities2
+        self.is_enabled(GetSurfaceCapabilities2::name())
     }
 
     #[inline]
     pub fn try_push_khr_surface(&mut self) -> bool {
-        if unsafe { self.try_push(Surface::name().as_ptr()) } {
-            self.khr_surface = true;
-            true
-        } else {
-            false
-        }
+        self.enable(Surface::name())
     }
 
     #[inline]
@@ -214,34 +174,53 @@ impl InstanceExtensions {
 
     #[inline]
     pub fn khr_surface(&self) -> bool {
-        self.khr_surface
+        self.is_enabled(Surface::name())
     }
 }
 
-unsafe impl Send for InstanceExtensions {}
-unsafe impl Sync for InstanceExtensions {}
+impl Deref for InstanceExtensions {
+    type Target = ExtensionRegistry;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.registry
+    }
+}
+
+impl DerefMut for InstanceExtensions {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.registry
+    }
+}
 
 struct Inner {
     entry_loader: Entry,
 
     loader: ash::Instance,
-    debug_utils_loader: DebugUtils,
-    get_surface_capabilities2_loader: GetSurfaceCapabilities2,
-    surface_loader: Surface,
+    //Only attached if the extension is enabled
+    debug_utils_loader: Option<DebugUtils>,
+    get_surface_capabilities2_loader: Option<GetSurfaceCapabilities2>,
+    surface_loader: Option<Surface>,
+    extension_loaders: ExtensionLoaders,
 
     layers: InstanceLayers,
     extensions: InstanceExtensions,
 
     debug_utils_messenger: vk::DebugUtilsMessengerEXT,
 
-    physical_devices: Vec<vk::PhysicalDevice>
+    physical_devices: Vec<vk::PhysicalDevice>,
+
+    headless: bool
 }
 
 impl Drop for Inner {
     fn drop(&mut self) {
         unsafe {
-            if self.debug_utils_messenger != vk::DebugUtilsMessengerEXT::null() {
-                self.debug_utils_loader.destroy_debug_utils_messenger(self.debug_utils_messenger, None);
+            if let Some(debug_utils_loader) = &self.debug_utils_loader {
+                if self.debug_utils_messenger != vk::DebugUtilsMessengerEXT::null() {
+                    debug_utils_loader.destroy_debug_utils_messenger(self.debug_utils_messenger, None);
+                }
             }
 
             self.loader.destroy_instance(None);
@@ -253,10 +232,25 @@ impl Drop for Inner {
 pub struct Instance(Arc<Inner>);
 
 impl Instance {
+    #[inline]
     pub fn new(
         window: &impl HasRawWindowHandle,
         layer_callback: impl FnOnce(&mut InstanceLayers),
         callback: impl FnOnce(&Entry, &InstanceLayers, &mut InstanceExtensions) -> Result<u32>
+    ) -> Result<Self> {
+        Self::new_internal(Some(window), layer_callback, callback)
+    }
+
+    //Creates an instance without any presentation support, the window system extensions are never queried
+    #[inline]
+    pub fn new_headless(layer_callback: impl FnOnce(&mut InstanceLayers), callback: impl FnOnce(&Entry, &InstanceLayers, &mut InstanceExtensions) -> Result<u32>) -> Result<Self> {
+        Self::new_internal(None, layer_callback, callback)
+    }
+
+    fn new_internal(
+        window: Option<&dyn HasRawWindowHandle>,
+        layer_callback: impl FnOnce(&mut InstanceLayers),
+        callback: impl FnOnce(&Entry, &InstanceLayers, &mut InstanceExtensions) -> Result<u32>
     ) -> Result<Self> {
         unsafe {
             let entry_loader = Entry::load()?;
@@ -266,8 +260,12 @@ impl Instance {
             layer_callback(&mut layers);
 
             let mut extensions = InstanceExtensions::new(&entry_loader, &layers)?;
-            ash_window::enumerate_required_extensions(&window)?.iter().for_each(|name| assert!(extensions.try_push(*name)));
-            extensions.khr_surface = true;
+
+            if let Some(window) = window {
+                for name in ash_window::enumerate_required_extensions(window)? {
+                    extensions.require(CStr::from_ptr(*name))?;
+                }
+            }
 
             let application_info = application_info_from_cargo_toml(callback(&entry_loader, &layers, &mut extensions)?);
 
@@ -281,9 +279,10 @@ impl Instance {
                 .enabled_validation_features(&enabled_validation_features)
                 .disabled_validation_features(&disabled_validation_features);
 
+            let enabled_extension_names = extensions.enabled_names();
             let mut instance_create_info = vk::InstanceCreateInfo::default()
                 .application_info(&application_info)
-                .enabled_extension_names(&extensions.enabled)
+                .enabled_extension_names(&enabled_extension_names)
                 .enabled_layer_names(&layers.enabled);
 
             if extensions.ext_validation_features() {
@@ -291,11 +290,11 @@ impl Instance {
             }
 
             let loader = entry_loader.create_instance(&instance_create_info, None)?;
-            let debug_utils_loader = DebugUtils::new(&entry_loader, &loader);
-            let get_surface_capabilities2_loader = GetSurfaceCapabilities2::new(&entry_loader, &loader);
-            let surface_loader = Surface::new(&entry_loader, &loader);
+            let debug_utils_loader = extensions.ext_debug_utils().then(|| DebugUtils::new(&entry_loader, &loader));
+            let get_surface_capabilities2_loader = extensions.khr_get_surface_capabilities2().then(|| GetSurfaceCapabilities2::new(&entry_loader, &loader));
+            let surface_loader = extensions.khr_surface().then(|| Surface::new(&entry_loader, &loader));
 
-            let debug_utils_messenger = if extensions.ext_debug_utils() {
+            let debug_utils_messenger = if let Some(debug_utils_loader) = &debug_utils_loader {
                 let debug_utils_messenger_create_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
                     .message_severity(
                         vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
@@ -320,50 +319,43 @@ impl Instance {
                 debug_utils_loader,
                 get_surface_capabilities2_loader,
                 surface_loader,
+                extension_loaders: ExtensionLoaders::default(),
 
                 layers,
                 extensions,
 
                 debug_utils_messenger,
 
-                physical_devices
+                physical_devices,
+
+                headless: window.is_none()
             })))
         }
     }
 
-    pub fn find_optimal_physical_device(&self) -> vk::PhysicalDevice {
-        let mut heap_size: u64 = 0;
-        let mut physical_device = vk::PhysicalDevice::null();
-
-        for current_physical_device in self.0.physical_devices.iter() {
-            let properties = unsafe { self.0.loader.get_physical_device_properties(*current_physical_device) };
-
-            if properties.device_type != vk::PhysicalDeviceType::DISCRETE_GPU {
-                continue
-            }
-
-            let memory_properties = unsafe { self.0.loader.get_physical_device_memory_properties(*current_physical_device) };
-            let mut current_heap_size: u64 = 0;
-
-            for i in 0..memory_properties.memory_heap_count as usize {
-                let current_heap = &memory_properties.memory_heaps[i];
-
-                if (current_heap.flags & vk::MemoryHeapFlags::DEVICE_LOCAL) == vk::MemoryHeapFlags::DEVICE_LOCAL {
-                    current_heap_size += current_heap.size;
-                }
-            }
+    //Candidates of all physical devices in enumeration order
+    pub fn physical_device_candidates(&self) -> Result<Vec<PhysicalDeviceCandidate>> {
+        self.0
+            .physical_devices
+            .iter()
+            .enumerate()
+            .map(|(index, physical_device)| unsafe { PhysicalDeviceCandidate::new(self, *physical_device, index) })
+            .collect()
+    }
 
-            if current_heap_size > heap_size {
-                heap_size = current_heap_size;
-                physical_device = *current_physical_device;
-            }
-        }
+    //Adapter info of all physical devices in enumeration order, the index of an adapter can be used as the value of KAMEL_GPU
+    pub fn adapters(&self) -> Result<Vec<AdapterInfo>> {
+        Ok(self.physical_device_candidates()?.iter().map(AdapterInfo::from).collect())
+    }
 
-        if physical_device == vk::PhysicalDevice::null() {
-            physical_device = self.0.physical_devices[0];
-        }
+    //Picks the most preferred physical device that meets every requirement of the desc
+    pub fn select_physical_device(&self, desc: &PhysicalDeviceSelectionDesc) -> Result<PhysicalDeviceCandidate> {
+        physical_device_selection::select_physical_device(self, self.physical_device_candidates()?, desc)
+    }
 
-        physical_device
+    #[inline]
+    pub fn find_optimal_physical_device(&self) -> Result<vk::PhysicalDevice> {
+        Ok(self.select_physical_device(&PhysicalDeviceSelectionDesc::default())?.physical_device)
     }
 
     #[inline]
@@ -377,18 +369,32 @@ impl Instance {
     }
 
     #[inline]
-    pub fn debug_utils_loader(&self) -> &DebugUtils {
-        &self.0.debug_utils_loader
+    pub fn debug_utils_loader(&self) -> Option<&DebugUtils> {
+        self.0.debug_utils_loader.as_ref()
+    }
+
+    #[inline]
+    pub fn get_surface_capabilities2_loader(&self) -> Option<&GetSurfaceCapabilities2> {
+        self.0.get_surface_capabilities2_loader.as_ref()
+    }
+
+    #[inline]
+    pub fn surface_loader(&self) -> Option<&Surface> {
+        self.0.surface_loader.as_ref()
     }
 
+    //Created on first use, None if the extension of the loader is not enabled
     #[inline]
-    pub fn get_surface_capabilities2_loader(&self) -> &GetSurfaceCapabilities2 {
-        &self.0.get_surface_capabilities2_loader
+    pub fn extension_loader<T: InstanceExtensionLoader>(&self) -> Option<Arc<T>> {
+        self.0
+            .extensions
+            .is_enabled(T::extension_name())
+            .then(|| self.0.extension_loaders.get_or_insert_with(|| unsafe { T::new(&self.0.entry_loader, &self.0.loader) }))
     }
 
     #[inline]
-    pub fn surface_loader(&self) -> &Surface {
-        &self.0.surface_loader
+    pub fn physical_devices(&self) -> &[vk::PhysicalDevice] {
+        &self.0.physical_devices
     }
 
     #[inline]
@@ -400,6 +406,11 @@ impl Instance {
     pub fn extensions(&self) -> &InstanceExtensions {
         &self.0.extensions
     }
+
+    #[inline]
+    pub fn headless(&self) -> bool {
+        self.0.headless
+    }
 }
 
 unsafe extern "system" fn debug_callback(
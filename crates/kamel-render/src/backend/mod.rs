@@ -1,12 +1,41 @@
 pub mod sync;
 pub mod util;
 
+mod access;
+mod adapter_info;
 mod command;
+mod deletion_queue;
+mod descriptor;
 mod device;
+mod device_features;
+mod extension_registry;
+mod frame_context;
 mod instance;
+mod physical_device_selection;
+mod pipeline;
+mod queue;
+mod queue_ownership;
+mod resource;
+mod shader;
 mod surface;
+mod swapchain;
+mod upload_service;
 
+pub use access::*;
+pub use adapter_info::*;
 pub use command::*;
+pub use descriptor::*;
 pub use device::*;
+pub use device_features::*;
+pub use extension_registry::{DeviceExtensionLoader, ExtensionRegistry, InstanceExtensionLoader};
+pub use frame_context::*;
 pub use instance::*;
+pub use physical_device_selection::*;
+pub use pipeline::*;
+pub use queue::*;
+pub use queue_ownership::*;
+pub use resource::*;
+pub use shader::*;
 pub use surface::*;
+pub use swapchain::*;
+pub use upload_service::*;
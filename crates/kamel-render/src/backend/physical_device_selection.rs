@@ -0,0 +1,316 @@
+use std::{cmp::Reverse, env, ffi::CStr};
+
+use anyhow::Result;
+use ash::vk;
+
+use crate::backend::{
+    device::{find_queue_family_indices, DEVICE_REQUIRED_FEATURES},
+    DeviceExtensions, DeviceFeature, DeviceFeatures, DeviceMemoryProperties, DeviceProperties, DeviceQueueFamilyProperties, Instance, Surface
+};
+
+//Overrides the selection with a physical device index or a case insensitive substring of the device name
+pub const PHYSICAL_DEVICE_OVERRIDE_VARIABLE: &str = "KAMEL_GPU";
+
+pub type PhysicalDeviceScoreFn<'a> = &'a dyn Fn(&PhysicalDeviceCandidate) -> i64;
+
+#[derive(Copy, Clone)]
+pub struct PhysicalDeviceSelectionDesc<'a> {
+    pub required_extensions: &'a [&'a CStr],
+    //The features Device::new always enables are required implicitly
+    pub required_features: &'a [DeviceFeature],
+    //Every entry has to be supported by at least one queue family
+    pub required_queue_flags: &'a [vk::QueueFlags],
+    //Requires a direct queue family that can present to the surface
+    pub surface: Option<&'a Surface>,
+    //Ordered by preference, devices of other types are only selected if no preferred device qualifies
+    pub preferred_device_types: &'a [vk::PhysicalDeviceType],
+    //Summed up, breaks ties between devices of the same preferred type before the device local memory size does
+    pub score_callbacks: &'a [PhysicalDeviceScoreFn<'a>],
+    //Honors PHYSICAL_DEVICE_OVERRIDE_VARIABLE
+    pub allow_override: bool
+}
+
+impl Default for PhysicalDeviceSelectionDesc<'_> {
+    fn default() -> Self {
+        Self {
+            required_extensions: &[],
+            required_features: &[],
+            required_queue_flags: &[],
+            surface: None,
+            preferred_device_types: &[vk::PhysicalDeviceType::DISCRETE_GPU, vk::PhysicalDeviceType::INTEGRATED_GPU],
+            score_callbacks: &[],
+            allow_override: true
+        }
+    }
+}
+
+//Everything known about a physical device before a logical device is created for it
+pub struct PhysicalDeviceCandidate {
+    pub physical_device: vk::PhysicalDevice,
+    //Position in the enumeration order of the instance
+    pub index: usize,
+
+    pub properties: DeviceProperties,
+    pub memory_properties: DeviceMemoryProperties,
+    pub queue_family_properties: DeviceQueueFamilyProperties,
+    pub features: DeviceFeatures,
+    pub extensions: DeviceExtensions
+}
+
+impl PhysicalDeviceCandidate {
+    pub(crate) unsafe fn new(instance: &Instance, physical_device: vk::PhysicalDevice, index: usize) -> Result<Self> {
+        Ok(Self {
+            physical_device,
+            index,
+
+            properties: DeviceProperties::new(instance, physical_device),
+            memory_properties: DeviceMemoryProperties::new(instance, physical_device),
+            queue_family_properties: DeviceQueueFamilyProperties::new(instance, physical_device),
+            features: DeviceFeatures::new(instance, physical_device),
+            extensions: DeviceExtensions::new(instance, physical_device)?
+        })
+    }
+
+    #[inline]
+    pub fn name(&self) -> String {
+        unsafe { CStr::from_ptr(self.properties.properties.device_name.as_ptr()) }.to_string_lossy().into_owned()
+    }
+
+    #[inline]
+    pub fn device_type(&self) -> vk::PhysicalDeviceType {
+        self.properties.properties.device_type
+    }
+
+    //Sum of the sizes of all device local heaps
+    pub fn device_local_memory_size(&self) -> u64 {
+        let memory_properties = &self.memory_properties.memory_properties;
+
+        memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum()
+    }
+
+    //Reasons why the device cannot be used with the desc, empty if it qualifies
+    pub fn unmet_requirements(&self, instance: &Instance, desc: &PhysicalDeviceSelectionDesc) -> Vec<String> {
+        let mut unmet = Vec::new();
+
+        if self.properties.properties.api_version < vk::API_VERSION_1_3 {
+            unmet.push("Vulkan 1.3".to_owned());
+        }
+
+        for extension in desc.required_extensions {
+            if !self.extensions.is_supported(extension) {
+                unmet.push(extension.to_string_lossy().into_owned());
+            }
+        }
+
+        for feature in DEVICE_REQUIRED_FEATURES.iter().chain(desc.required_features) {
+            if !self.features.get(*feature) {
+                unmet.push(feature.name().to_owned());
+            }
+        }
+
+        let queue_family_properties = &self.queue_family_properties.queue_family_properties;
+
+        for queue_flags in desc.required_queue_flags {
+            if !queue_family_properties.iter().any(|properties| properties.queue_flags.contains(*queue_flags)) {
+                unmet.push(format!("queue family with {:?}", queue_flags));
+            }
+        }
+
+        if unsafe { find_queue_family_indices(instance, desc.surface, self.physical_device, queue_family_properties) }.is_none() {
+            unmet.push(if desc.surface.is_some() {
+                "direct queue family that can present to the surface".to_owned()
+            } else {
+                "direct queue family".to_owned()
+            });
+        }
+
+        unmet
+    }
+
+    #[inline]
+    fn matches_override_index(&self, value: &str) -> bool {
+        value.parse::<usize>().map_or(false, |index| index == self.index)
+    }
+
+    //Numeric values can also be part of the name, like 4090
+    #[inline]
+    fn matches_override(&self, value: &str) -> bool {
+        self.matches_override_index(value) || self.name().to_lowercase().contains(&value.to_lowercase())
+    }
+}
+
+//An index match wins over a name match
+fn find_override(mut candidates: Vec<PhysicalDeviceCandidate>, value: &str) -> Option<PhysicalDeviceCandidate> {
+    let position = candidates
+        .iter()
+        .position(|candidate| candidate.matches_override_index(value))
+        .or_else(|| candidates.iter().position(|candidate| candidate.matches_override(value)))?;
+
+    Some(candidates.swap_remove(position))
+}
+
+fn best_candidate(candidates: Vec<PhysicalDeviceCandidate>, desc: &PhysicalDeviceSelectionDesc) -> Option<PhysicalDeviceCandidate> {
+    //Lower rank is preferred, devices of types that are not listed come last
+    let rank = |candidate: &PhysicalDeviceCandidate| {
+        desc.preferred_device_types
+            .iter()
+            .position(|device_type| *device_type == candidate.device_type())
+            .unwrap_or(desc.preferred_device_types.len())
+    };
+    let score = |candidate: &PhysicalDeviceCandidate| desc.score_callbacks.iter().fold(0i64, |score, callback| score.saturating_add(callback(candidate)));
+
+    candidates
+        .into_iter()
+        .map(|candidate| ((rank(&candidate), Reverse(score(&candidate)), Reverse(candidate.device_local_memory_size())), candidate))
+        .min_by_key(|(key, _)| *key)
+        .map(|(_, candidate)| candidate)
+}
+
+pub(crate) fn select_physical_device(instance: &Instance, candidates: Vec<PhysicalDeviceCandidate>, desc: &PhysicalDeviceSelectionDesc) -> Result<PhysicalDeviceCandidate> {
+    if candidates.is_empty() {
+        anyhow::bail!("No physical device with Vulkan support found");
+    }
+
+    let mut rejections = Vec::new();
+    let mut qualified = Vec::new();
+
+    for candidate in candidates {
+        let unmet = candidate.unmet_requirements(instance, desc);
+
+        if unmet.is_empty() {
+            qualified.push(candidate);
+        } else {
+            rejections.push(format!("{} is missing {}", candidate.name(), unmet.join(", ")));
+        }
+    }
+
+    if desc.allow_override {
+        if let Some(value) = env::var(PHYSICAL_DEVICE_OVERRIDE_VARIABLE).ok().filter(|value| !value.is_empty()) {
+            return match find_override(qualified, &value) {
+                Some(candidate) => Ok(candidate),
+                None => {
+                    anyhow::bail!(
+                        "{}={} does not match any suitable physical device{}",
+                        PHYSICAL_DEVICE_OVERRIDE_VARIABLE,
+                        value,
+                        rejections.iter().map(|rejection| format!("\n{}", rejection)).collect::<String>()
+                    )
+                }
+            }
+        }
+    }
+
+    best_candidate(qualified, desc).ok_or_else(|| {
+        anyhow::anyhow!(
+            "No suitable physical device found{}",
+            rejections.iter().map(|rejection| format!("\n{}", rejection)).collect::<String>()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::raw::c_char;
+
+    use super::*;
+    use crate::backend::{extension_registry::DEVICE_EXTENSION_DEPENDENCIES, ExtensionRegistry};
+
+    fn candidate(index: usize, name: &str, device_type: vk::PhysicalDeviceType, device_local_memory_size: u64) -> PhysicalDeviceCandidate {
+        let mut properties = vk::PhysicalDeviceProperties::default();
+        properties.device_type = device_type;
+        for (dst, src) in properties.device_name.iter_mut().zip(name.bytes()) {
+            *dst = src as c_char;
+        }
+
+        let mut memory_properties = vk::PhysicalDeviceMemoryProperties::default();
+        memory_properties.memory_heap_count = 1;
+        memory_properties.memory_heaps[0] = vk::MemoryHeap {
+            size: device_local_memory_size,
+            flags: vk::MemoryHeapFlags::DEVICE_LOCAL
+        };
+
+        PhysicalDeviceCandidate {
+            physical_device: vk::PhysicalDevice::null(),
+            index,
+
+            properties: DeviceProperties {
+                properties,
+                properties_11: Default::default(),
+                properties_12: Default::default(),
+                properties_13: Default::default()
+            },
+            memory_properties: DeviceMemoryProperties { memory_properties },
+            queue_family_properties: DeviceQueueFamilyProperties {
+                queue_family_properties: Vec::new()
+            },
+            features: DeviceFeatures::default(),
+            extensions: DeviceExtensions {
+                registry: ExtensionRegistry::new(&[], DEVICE_EXTENSION_DEPENDENCIES, Vec::new())
+            }
+        }
+    }
+
+    fn candidates() -> Vec<PhysicalDeviceCandidate> {
+        vec![
+            candidate(0, "Integrated GPU", vk::PhysicalDeviceType::INTEGRATED_GPU, 8 << 30),
+            candidate(1, "Software Rasterizer", vk::PhysicalDeviceType::CPU, 16 << 30),
+            candidate(2, "Discrete GPU 1080", vk::PhysicalDeviceType::DISCRETE_GPU, 4 << 30),
+            candidate(3, "Discrete GPU 4090", vk::PhysicalDeviceType::DISCRETE_GPU, 24 << 30),
+        ]
+    }
+
+    #[test]
+    fn prefers_device_types_then_memory() {
+        assert_eq!(best_candidate(candidates(), &PhysicalDeviceSelectionDesc::default()).unwrap().index, 3);
+    }
+
+    #[test]
+    fn ranks_unlisted_device_types_last() {
+        let desc = PhysicalDeviceSelectionDesc {
+            preferred_device_types: &[vk::PhysicalDeviceType::INTEGRATED_GPU],
+            ..Default::default()
+        };
+        assert_eq!(best_candidate(candidates(), &desc).unwrap().index, 0);
+
+        let desc = PhysicalDeviceSelectionDesc {
+            preferred_device_types: &[],
+            ..Default::default()
+        };
+        assert_eq!(best_candidate(candidates(), &desc).unwrap().index, 3);
+    }
+
+    #[test]
+    fn scores_break_ties_before_memory() {
+        let prefer_1080: PhysicalDeviceScoreFn = &|candidate| if candidate.name().contains("1080") { 10 } else { 0 };
+        let desc = PhysicalDeviceSelectionDesc {
+            score_callbacks: &[prefer_1080],
+            ..Default::default()
+        };
+
+        assert_eq!(best_candidate(candidates(), &desc).unwrap().index, 2);
+    }
+
+    #[test]
+    fn saturates_scores() {
+        let max: PhysicalDeviceScoreFn = &|candidate| if candidate.index == 2 { i64::MAX } else { 0 };
+        let desc = PhysicalDeviceSelectionDesc {
+            score_callbacks: &[max, max],
+            ..Default::default()
+        };
+
+        assert_eq!(best_candidate(candidates(), &desc).unwrap().index, 2);
+    }
+
+    #[test]
+    fn overrides_by_index_or_name() {
+        assert_eq!(find_override(candidates(), "1").unwrap().index, 1);
+        assert_eq!(find_override(candidates(), "software").unwrap().index, 1);
+        assert_eq!(find_override(candidates(), "4090").unwrap().index, 3);
+        assert!(find_override(candidates(), "7").is_none());
+        assert!(find_override(candidates(), "radeon").is_none());
+    }
+}
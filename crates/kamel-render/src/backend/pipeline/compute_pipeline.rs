@@ -0,0 +1,142 @@
+use std::{ffi::CString, ops::Deref, slice};
+
+use anyhow::Result;
+use ash::vk;
+
+use crate::backend::{
+    deletion_queue::DeferredDestruction, reflect_pipeline_layout, util::debug_utils, Device, EntryPoint, PipelineLayout, ShaderModule, SpecializationConstant, SpecializationData,
+    SpecializationValue
+};
+
+#[derive(Copy, Clone)]
+pub struct ComputePipelineDesc<'a> {
+    pub shader_module: &'a ShaderModule,
+    pub entry_point: &'a str,
+    pub specialization_constants: &'a [SpecializationConstant],
+    //Overrides the push constant ranges reflected from the shader module
+    pub push_constant_ranges: Option<&'a [vk::PushConstantRange]>,
+    //Layouts of sets that are not described by the reflected bindings, e.g. the one of a bindless heap
+    pub descriptor_set_layouts: &'a [(u32, vk::DescriptorSetLayout)],
+    pub label: Option<&'a str>
+}
+
+//Applies the specialization constants that override the reflected workgroup size
+fn specialized_workgroup_size(entry_point: &EntryPoint, specialization_constants: &[SpecializationConstant]) -> Result<[u32; 3]> {
+    let mut workgroup_size = entry_point.workgroup_size.unwrap_or([1, 1, 1]);
+
+    for (size, spec_id) in workgroup_size.iter_mut().zip(entry_point.workgroup_size_spec_ids) {
+        let value = match spec_id.and_then(|spec_id| specialization_constants.iter().find(|constant| constant.id == spec_id)) {
+            Some(constant) => constant.value,
+            None => continue
+        };
+
+        *size = match value {
+            SpecializationValue::U32(value) => value,
+            SpecializationValue::I32(value) if value > 0 => value as u32,
+            _ => anyhow::bail!("Specialization constant {} is not a valid workgroup size: {:?}", spec_id.unwrap(), value)
+        };
+    }
+
+    if workgroup_size.contains(&0) {
+        anyhow::bail!("Workgroup size {:?} of entry point {} has an empty dimension", workgroup_size, entry_point.name);
+    }
+
+    Ok(workgroup_size)
+}
+
+pub struct ComputePipeline {
+    pipeline: vk::Pipeline,
+    layout: PipelineLayout,
+    workgroup_size: [u32; 3],
+    device: Device
+}
+
+impl ComputePipeline {
+    pub fn new(device: Device, desc: &ComputePipelineDesc) -> Result<Self> {
+        let entry_point = desc
+            .shader_module
+            .reflection()
+            .entry_point(desc.entry_point)
+            .ok_or_else(|| anyhow::anyhow!("Shader module has no entry point named {}", desc.entry_point))?;
+
+        if entry_point.stage != vk::ShaderStageFlags::COMPUTE {
+            anyhow::bail!("Entry point {} is a {:?} shader and not a compute shader", desc.entry_point, entry_point.stage);
+        }
+
+        let mut layout_reflection = reflect_pipeline_layout(&[entry_point])?;
+        if let Some(push_constant_ranges) = desc.push_constant_ranges {
+            layout_reflection.push_constant_ranges = push_constant_ranges.to_vec();
+        }
+
+        let layout = PipelineLayout::new(&device, &layout_reflection, desc.descriptor_set_layouts)?;
+        let workgroup_size = specialized_workgroup_size(entry_point, desc.specialization_constants)?;
+
+        let entry_point_name = CString::new(desc.entry_point)?;
+        let specialization_data = SpecializationData::new(desc.specialization_constants);
+        let specialization_info = specialization_data.specialization_info();
+
+        let compute_pipeline_create_info = vk::ComputePipelineCreateInfo::default()
+            .stage(
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::COMPUTE)
+                    .module(**desc.shader_module)
+                    .name(&entry_point_name)
+                    .specialization_info(&specialization_info)
+            )
+            .layout(*layout);
+
+        let pipeline = unsafe {
+            device
+                .loader()
+                .create_compute_pipelines(*device.pipeline_cache().handle(), slice::from_ref(&compute_pipeline_create_info), None)
+                .map_err(|(_, result)| result)
+        }?[0];
+
+        if let Some(label) = desc.label {
+            unsafe { debug_utils::set_object_name(&device, pipeline, label) }?;
+        }
+
+        Ok(Self {
+            pipeline,
+            layout,
+            workgroup_size,
+            device
+        })
+    }
+
+    #[inline]
+    pub fn layout(&self) -> &PipelineLayout {
+        &self.layout
+    }
+
+    #[inline]
+    pub fn workgroup_size(&self) -> [u32; 3] {
+        self.workgroup_size
+    }
+
+    //Number of workgroups needed to cover the given number of invocations in each dimension
+    #[inline]
+    pub fn group_count(&self, invocations: [u32; 3]) -> [u32; 3] {
+        [
+            invocations[0].div_ceil(self.workgroup_size[0]),
+            invocations[1].div_ceil(self.workgroup_size[1]),
+            invocations[2].div_ceil(self.workgroup_size[2])
+        ]
+    }
+}
+
+impl Deref for ComputePipeline {
+    type Target = vk::Pipeline;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.pipeline
+    }
+}
+
+impl Drop for ComputePipeline {
+    #[inline]
+    fn drop(&mut self) {
+        self.device.defer_destruction(DeferredDestruction::Pipeline(self.pipeline));
+    }
+}
@@ -0,0 +1,383 @@
+use std::{ffi::CString, ops::Deref, slice};
+
+use anyhow::Result;
+use ash::vk;
+
+use crate::backend::{deletion_queue::DeferredDestruction, reflect_pipeline_layout, util::debug_utils, Device, PipelineLayout, ShaderModule, SpecializationConstant, SpecializationData};
+
+#[derive(Copy, Clone)]
+struct ShaderStage<'a> {
+    shader_module: &'a ShaderModule,
+    entry_point: &'a str,
+    specialization_constants: &'a [SpecializationConstant]
+}
+
+pub struct GraphicsPipelineBuilder<'a> {
+    stages: Vec<ShaderStage<'a>>,
+
+    vertex_bindings: Vec<vk::VertexInputBindingDescription>,
+    vertex_attributes: Vec<vk::VertexInputAttributeDescription>,
+    topology: vk::PrimitiveTopology,
+    primitive_restart: bool,
+
+    polygon_mode: vk::PolygonMode,
+    cull_mode: vk::CullModeFlags,
+    front_face: vk::FrontFace,
+    depth_clamp: bool,
+    depth_bias: Option<(f32, f32, f32)>,
+    line_width: f32,
+
+    depth_compare_op: Option<vk::CompareOp>,
+    depth_write: bool,
+    stencil: Option<(vk::StencilOpState, vk::StencilOpState)>,
+
+    samples: vk::SampleCountFlags,
+    min_sample_shading: Option<f32>,
+    alpha_to_coverage: bool,
+
+    color_formats: Vec<vk::Format>,
+    color_blend_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    depth_format: vk::Format,
+    stencil_format: vk::Format,
+
+    dynamic_states: Vec<vk::DynamicState>,
+    push_constant_ranges: Option<&'a [vk::PushConstantRange]>,
+    descriptor_set_layouts: Vec<(u32, vk::DescriptorSetLayout)>,
+    label: Option<&'a str>
+}
+
+impl Default for GraphicsPipelineBuilder<'_> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> GraphicsPipelineBuilder<'a> {
+    pub fn new() -> Self {
+        Self {
+            stages: Vec::new(),
+
+            vertex_bindings: Vec::new(),
+            vertex_attributes: Vec::new(),
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            primitive_restart: false,
+
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            depth_clamp: false,
+            depth_bias: None,
+            line_width: 1.0,
+
+            depth_compare_op: None,
+            depth_write: false,
+            stencil: None,
+
+            samples: vk::SampleCountFlags::TYPE_1,
+            min_sample_shading: None,
+            alpha_to_coverage: false,
+
+            color_formats: Vec::new(),
+            color_blend_attachments: Vec::new(),
+            depth_format: vk::Format::UNDEFINED,
+            stencil_format: vk::Format::UNDEFINED,
+
+            dynamic_states: vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR],
+            push_constant_ranges: None,
+            descriptor_set_layouts: Vec::new(),
+            label: None
+        }
+    }
+
+    #[inline]
+    pub fn shader_stage(self, shader_module: &'a ShaderModule, entry_point: &'a str) -> Self {
+        self.specialized_shader_stage(shader_module, entry_point, &[])
+    }
+
+    #[inline]
+    pub fn specialized_shader_stage(mut self, shader_module: &'a ShaderModule, entry_point: &'a str, specialization_constants: &'a [SpecializationConstant]) -> Self {
+        self.stages.push(ShaderStage {
+            shader_module,
+            entry_point,
+            specialization_constants
+        });
+        self
+    }
+
+    #[inline]
+    pub fn vertex_binding(mut self, binding: u32, stride: u32, input_rate: vk::VertexInputRate) -> Self {
+        self.vertex_bindings
+            .push(vk::VertexInputBindingDescription::default().binding(binding).stride(stride).input_rate(input_rate));
+        self
+    }
+
+    #[inline]
+    pub fn vertex_attribute(mut self, location: u32, binding: u32, format: vk::Format, offset: u32) -> Self {
+        self.vertex_attributes
+            .push(vk::VertexInputAttributeDescription::default().location(location).binding(binding).format(format).offset(offset));
+        self
+    }
+
+    #[inline]
+    pub fn topology(mut self, topology: vk::PrimitiveTopology, primitive_restart: bool) -> Self {
+        self.topology = topology;
+        self.primitive_restart = primitive_restart;
+        self
+    }
+
+    #[inline]
+    pub fn rasterization(mut self, polygon_mode: vk::PolygonMode, cull_mode: vk::CullModeFlags, front_face: vk::FrontFace) -> Self {
+        self.polygon_mode = polygon_mode;
+        self.cull_mode = cull_mode;
+        self.front_face = front_face;
+        self
+    }
+
+    #[inline]
+    pub fn depth_clamp(mut self, depth_clamp: bool) -> Self {
+        self.depth_clamp = depth_clamp;
+        self
+    }
+
+    #[inline]
+    pub fn depth_bias(mut self, constant_factor: f32, clamp: f32, slope_factor: f32) -> Self {
+        self.depth_bias = Some((constant_factor, clamp, slope_factor));
+        self
+    }
+
+    #[inline]
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    #[inline]
+    pub fn depth_test(mut self, compare_op: vk::CompareOp, write: bool) -> Self {
+        self.depth_compare_op = Some(compare_op);
+        self.depth_write = write;
+        self
+    }
+
+    #[inline]
+    pub fn stencil_test(mut self, front: vk::StencilOpState, back: vk::StencilOpState) -> Self {
+        self.stencil = Some((front, back));
+        self
+    }
+
+    #[inline]
+    pub fn multisample(mut self, samples: vk::SampleCountFlags, min_sample_shading: Option<f32>, alpha_to_coverage: bool) -> Self {
+        self.samples = samples;
+        self.min_sample_shading = min_sample_shading;
+        self.alpha_to_coverage = alpha_to_coverage;
+        self
+    }
+
+    //Blending is disabled if no blend state is given
+    #[inline]
+    pub fn color_attachment(mut self, format: vk::Format, blend: Option<vk::PipelineColorBlendAttachmentState>) -> Self {
+        self.color_formats.push(format);
+        self.color_blend_attachments
+            .push(blend.unwrap_or_else(|| vk::PipelineColorBlendAttachmentState::default().color_write_mask(vk::ColorComponentFlags::RGBA)));
+        self
+    }
+
+    #[inline]
+    pub fn depth_attachment(mut self, format: vk::Format) -> Self {
+        self.depth_format = format;
+        self
+    }
+
+    #[inline]
+    pub fn stencil_attachment(mut self, format: vk::Format) -> Self {
+        self.stencil_format = format;
+        self
+    }
+
+    #[inline]
+    pub fn dynamic_state(mut self, dynamic_state: vk::DynamicState) -> Self {
+        if !self.dynamic_states.contains(&dynamic_state) {
+            self.dynamic_states.push(dynamic_state);
+        }
+        self
+    }
+
+    //Overrides the push constant ranges reflected from the shader modules
+    #[inline]
+    pub fn push_constant_ranges(mut self, push_constant_ranges: &'a [vk::PushConstantRange]) -> Self {
+        self.push_constant_ranges = Some(push_constant_ranges);
+        self
+    }
+
+    //Uses the given layout for the set instead of the reflected bindings, e.g. the layout of a bindless heap
+    #[inline]
+    pub fn descriptor_set_layout(mut self, set: u32, descriptor_set_layout: vk::DescriptorSetLayout) -> Self {
+        self.descriptor_set_layouts.retain(|(explicit_set, _)| *explicit_set != set);
+        self.descriptor_set_layouts.push((set, descriptor_set_layout));
+        self
+    }
+
+    #[inline]
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub fn build(self, device: Device) -> Result<GraphicsPipeline> {
+        if device.enabled_features().features_13.dynamic_rendering == vk::FALSE {
+            anyhow::bail!("Graphics pipelines are created for dynamic rendering but the dynamicRendering feature of Vulkan 1.3 is not enabled");
+        }
+
+        if self.stages.is_empty() {
+            anyhow::bail!("Graphics pipeline has no shader stages");
+        }
+
+        let mut entry_points = Vec::with_capacity(self.stages.len());
+        for stage in self.stages.iter() {
+            let entry_point = stage
+                .shader_module
+                .reflection()
+                .entry_point(stage.entry_point)
+                .ok_or_else(|| anyhow::anyhow!("Shader module has no entry point named {}", stage.entry_point))?;
+
+            if entry_point.stage == vk::ShaderStageFlags::COMPUTE {
+                anyhow::bail!("Entry point {} is a compute shader and cannot be used in a graphics pipeline", stage.entry_point);
+            }
+
+            entry_points.push(entry_point);
+        }
+
+        let mut layout_reflection = reflect_pipeline_layout(&entry_points)?;
+        if let Some(push_constant_ranges) = self.push_constant_ranges {
+            layout_reflection.push_constant_ranges = push_constant_ranges.to_vec();
+        }
+
+        let layout = PipelineLayout::new(&device, &layout_reflection, &self.descriptor_set_layouts)?;
+
+        let entry_point_names = self.stages.iter().map(|stage| CString::new(stage.entry_point)).collect::<Result<Vec<_>, _>>()?;
+        let specialization_data: Vec<_> = self.stages.iter().map(|stage| SpecializationData::new(stage.specialization_constants)).collect();
+        let specialization_infos: Vec<_> = specialization_data.iter().map(SpecializationData::specialization_info).collect();
+
+        let shader_stage_create_infos: Vec<_> = self
+            .stages
+            .iter()
+            .zip(entry_points.iter())
+            .enumerate()
+            .map(|(i, (stage, entry_point))| {
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(entry_point.stage)
+                    .module(**stage.shader_module)
+                    .name(&entry_point_names[i])
+                    .specialization_info(&specialization_infos[i])
+            })
+            .collect();
+
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(&self.vertex_bindings)
+            .vertex_attribute_descriptions(&self.vertex_attributes);
+
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::default()
+            .topology(self.topology)
+            .primitive_restart_enable(self.primitive_restart);
+
+        //Viewport and scissor are dynamic, only their count is baked into the pipeline
+        let viewport_state = vk::PipelineViewportStateCreateInfo::default().viewport_count(1).scissor_count(1);
+
+        let (depth_bias_constant_factor, depth_bias_clamp, depth_bias_slope_factor) = self.depth_bias.unwrap_or_default();
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::default()
+            .depth_clamp_enable(self.depth_clamp)
+            .polygon_mode(self.polygon_mode)
+            .cull_mode(self.cull_mode)
+            .front_face(self.front_face)
+            .depth_bias_enable(self.depth_bias.is_some())
+            .depth_bias_constant_factor(depth_bias_constant_factor)
+            .depth_bias_clamp(depth_bias_clamp)
+            .depth_bias_slope_factor(depth_bias_slope_factor)
+            .line_width(self.line_width);
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::default()
+            .rasterization_samples(self.samples)
+            .sample_shading_enable(self.min_sample_shading.is_some())
+            .min_sample_shading(self.min_sample_shading.unwrap_or(0.0))
+            .alpha_to_coverage_enable(self.alpha_to_coverage);
+
+        let (stencil_front, stencil_back) = self.stencil.unwrap_or_default();
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::default()
+            .depth_test_enable(self.depth_compare_op.is_some())
+            .depth_write_enable(self.depth_write)
+            .depth_compare_op(self.depth_compare_op.unwrap_or(vk::CompareOp::ALWAYS))
+            .stencil_test_enable(self.stencil.is_some())
+            .front(stencil_front)
+            .back(stencil_back)
+            .max_depth_bounds(1.0);
+
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::default().attachments(&self.color_blend_attachments);
+
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&self.dynamic_states);
+
+        let mut rendering_create_info = vk::PipelineRenderingCreateInfo::default()
+            .color_attachment_formats(&self.color_formats)
+            .depth_attachment_format(self.depth_format)
+            .stencil_attachment_format(self.stencil_format);
+
+        let graphics_pipeline_create_info = vk::GraphicsPipelineCreateInfo::default()
+            .stages(&shader_stage_create_infos)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state)
+            .layout(*layout)
+            .push_next(&mut rendering_create_info);
+
+        let pipeline = unsafe {
+            device
+                .loader()
+                .create_graphics_pipelines(*device.pipeline_cache().handle(), slice::from_ref(&graphics_pipeline_create_info), None)
+                .map_err(|(_, result)| result)
+        }?[0];
+
+        if let Some(label) = self.label {
+            unsafe { debug_utils::set_object_name(&device, pipeline, label) }?;
+        }
+
+        Ok(GraphicsPipeline { pipeline, layout, device })
+    }
+}
+
+pub struct GraphicsPipeline {
+    pipeline: vk::Pipeline,
+    layout: PipelineLayout,
+    device: Device
+}
+
+impl GraphicsPipeline {
+    #[inline]
+    pub fn builder<'a>() -> GraphicsPipelineBuilder<'a> {
+        GraphicsPipelineBuilder::new()
+    }
+
+    #[inline]
+    pub fn layout(&self) -> &PipelineLayout {
+        &self.layout
+    }
+}
+
+impl Deref for GraphicsPipeline {
+    type Target = vk::Pipeline;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.pipeline
+    }
+}
+
+impl Drop for GraphicsPipeline {
+    #[inline]
+    fn drop(&mut self) {
+        self.device.defer_destruction(DeferredDestruction::Pipeline(self.pipeline));
+    }
+}
@@ -0,0 +1,11 @@
+mod compute_pipeline;
+mod graphics_pipeline;
+mod pipeline_cache;
+mod pipeline_layout;
+mod specialization;
+
+pub use compute_pipeline::*;
+pub use graphics_pipeline::*;
+pub use pipeline_cache::*;
+pub use pipeline_layout::*;
+pub use specialization::*;
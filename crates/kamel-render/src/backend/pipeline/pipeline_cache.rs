@@ -0,0 +1,139 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    slice,
+    sync::{Mutex, RwLock, RwLockReadGuard}
+};
+
+use anyhow::Result;
+use ash::{prelude::VkResult, vk};
+
+use crate::backend::DeviceProperties;
+
+const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+pub struct PipelineCache {
+    //Merging requires external synchronization of the cache, creating pipelines with it does not
+    pipeline_cache: RwLock<vk::PipelineCache>,
+    directory: Mutex<Option<PathBuf>>,
+
+    vendor_id: u32,
+    device_id: u32,
+    pipeline_cache_uuid: [u8; vk::UUID_SIZE]
+}
+
+impl PipelineCache {
+    pub(crate) unsafe fn new(loader: &ash::Device, properties: &DeviceProperties) -> VkResult<Self> {
+        let pipeline_cache = loader.create_pipeline_cache(&vk::PipelineCacheCreateInfo::default(), None)?;
+
+        Ok(Self {
+            pipeline_cache: RwLock::new(pipeline_cache),
+            directory: Mutex::new(None),
+
+            vendor_id: properties.properties.vendor_id,
+            device_id: properties.properties.device_id,
+            pipeline_cache_uuid: properties.properties.pipeline_cache_uuid
+        })
+    }
+
+    pub(crate) unsafe fn destroy(&self, loader: &ash::Device) {
+        loader.destroy_pipeline_cache(*self.handle(), None);
+    }
+
+    #[inline]
+    fn file_path(&self, directory: &Path) -> PathBuf {
+        directory.join(format!("pipeline_cache_{:04x}_{:04x}.bin", self.vendor_id, self.device_id))
+    }
+
+    fn validate(&self, data: &[u8]) -> Result<()> {
+        if data.len() < HEADER_SIZE {
+            anyhow::bail!("Pipeline cache of {} bytes is too small to contain a header", data.len());
+        }
+
+        let read_u32 = |offset: usize| u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+
+        let header_size = read_u32(0);
+        let header_version = read_u32(4);
+        let vendor_id = read_u32(8);
+        let device_id = read_u32(12);
+        let pipeline_cache_uuid = &data[16..HEADER_SIZE];
+
+        if (header_size as usize) < HEADER_SIZE || header_version != vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32 {
+            anyhow::bail!("Unsupported pipeline cache header (size {}, version {})", header_size, header_version);
+        }
+
+        if vendor_id != self.vendor_id || device_id != self.device_id {
+            anyhow::bail!(
+                "Pipeline cache was created for device {:04x}:{:04x} instead of {:04x}:{:04x}",
+                vendor_id,
+                device_id,
+                self.vendor_id,
+                self.device_id
+            );
+        }
+
+        if pipeline_cache_uuid != self.pipeline_cache_uuid {
+            anyhow::bail!("Pipeline cache UUID does not match, the driver has probably been updated");
+        }
+
+        Ok(())
+    }
+
+    //Returns false if there was no valid cache in the directory, invalid caches are discarded and overwritten on the next save
+    pub(crate) unsafe fn load(&self, loader: &ash::Device, directory: &Path) -> Result<bool> {
+        let mut current_directory = self.directory.lock().unwrap();
+        *current_directory = Some(directory.to_owned());
+
+        let path = self.file_path(directory);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(error) => {
+                log::warn!("Failed to read pipeline cache {}: {}", path.display(), error);
+                return Ok(false)
+            }
+        };
+
+        if let Err(error) = self.validate(&data) {
+            log::warn!("Discarding pipeline cache: {}", error);
+            return Ok(false)
+        }
+
+        let loaded_pipeline_cache = loader.create_pipeline_cache(&vk::PipelineCacheCreateInfo::default().initial_data(&data), None)?;
+        let result = loader.merge_pipeline_caches(*self.pipeline_cache.write().unwrap(), slice::from_ref(&loaded_pipeline_cache));
+        loader.destroy_pipeline_cache(loaded_pipeline_cache, None);
+        result?;
+
+        Ok(true)
+    }
+
+    pub(crate) unsafe fn save(&self, loader: &ash::Device) -> Result<()> {
+        let directory = match self.directory.lock().unwrap().clone() {
+            Some(directory) => directory,
+            None => anyhow::bail!("Pipeline cache has no directory to save to")
+        };
+
+        let data = loader.get_pipeline_cache_data(*self.handle())?;
+
+        fs::create_dir_all(&directory)?;
+
+        //Write to a temporary file first so an interrupted save never leaves a truncated cache behind
+        let path = self.file_path(&directory);
+        let temporary_path = path.with_extension("tmp");
+        fs::write(&temporary_path, &data)?;
+        fs::rename(&temporary_path, &path)?;
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn directory(&self) -> Option<PathBuf> {
+        self.directory.lock().unwrap().clone()
+    }
+
+    //Pipelines can be created with the cache while the guard is alive, loading a cache waits until it is dropped
+    #[inline]
+    pub fn handle(&self) -> RwLockReadGuard<'_, vk::PipelineCache> {
+        self.pipeline_cache.read().unwrap()
+    }
+}
@@ -0,0 +1,176 @@
+use std::ops::Deref;
+
+use anyhow::Result;
+use ash::vk;
+
+use crate::backend::{DescriptorSetLayoutBindingDesc, Device, PipelineLayoutReflection};
+
+//The layouts are owned by the layout caches of the device and shared between all pipelines with the same interface
+#[derive(Clone, Debug)]
+pub struct PipelineLayout {
+    pipeline_layout: vk::PipelineLayout,
+    descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
+    push_constant_ranges: Vec<vk::PushConstantRange>
+}
+
+//Sets with an explicit layout, e.g. the one of a bindless heap, ignore the reflected bindings of that set
+fn resolve_descriptor_set_layouts(
+    reflection: &PipelineLayoutReflection,
+    explicit_set_layouts: &[(u32, vk::DescriptorSetLayout)],
+    mut get_or_create: impl FnMut(&[DescriptorSetLayoutBindingDesc]) -> Result<vk::DescriptorSetLayout>
+) -> Result<Vec<vk::DescriptorSetLayout>> {
+    let set_count = explicit_set_layouts.iter().map(|(set, _)| *set as usize + 1).fold(reflection.sets.len(), usize::max);
+    let mut descriptor_set_layouts = Vec::with_capacity(set_count);
+
+    for set in 0..set_count {
+        if let Some((_, descriptor_set_layout)) = explicit_set_layouts.iter().find(|(explicit_set, _)| *explicit_set as usize == set) {
+            descriptor_set_layouts.push(*descriptor_set_layout);
+            continue
+        }
+
+        let descriptor_bindings = reflection.sets.get(set).map_or(&[][..], Vec::as_slice);
+        let mut bindings = Vec::with_capacity(descriptor_bindings.len());
+
+        for descriptor_binding in descriptor_bindings {
+            if descriptor_binding.count == 0 {
+                anyhow::bail!(
+                    "Descriptor binding (set {}, binding {}) is a runtime sized array and needs an explicit layout",
+                    set,
+                    descriptor_binding.binding
+                );
+            }
+
+            bindings.push(DescriptorSetLayoutBindingDesc {
+                binding: descriptor_binding.binding,
+                descriptor_type: descriptor_binding.descriptor_type,
+                count: descriptor_binding.count,
+                stages: descriptor_binding.stages,
+                flags: vk::DescriptorBindingFlags::empty()
+            });
+        }
+
+        descriptor_set_layouts.push(get_or_create(&bindings)?);
+    }
+
+    Ok(descriptor_set_layouts)
+}
+
+impl PipelineLayout {
+    #[inline]
+    pub fn from_reflection(device: &Device, reflection: &PipelineLayoutReflection) -> Result<Self> {
+        Self::new(device, reflection, &[])
+    }
+
+    pub fn new(device: &Device, reflection: &PipelineLayoutReflection, explicit_set_layouts: &[(u32, vk::DescriptorSetLayout)]) -> Result<Self> {
+        let descriptor_set_layouts = resolve_descriptor_set_layouts(reflection, explicit_set_layouts, |bindings| device.get_or_create_descriptor_set_layout(bindings))?;
+        let pipeline_layout = device.get_or_create_pipeline_layout(&descriptor_set_layouts, &reflection.push_constant_ranges)?;
+
+        Ok(Self {
+            pipeline_layout,
+            descriptor_set_layouts,
+            push_constant_ranges: reflection.push_constant_ranges.clone()
+        })
+    }
+
+    #[inline]
+    pub fn uses_set_layout(&self, set: u32, descriptor_set_layout: vk::DescriptorSetLayout) -> bool {
+        self.descriptor_set_layouts.get(set as usize) == Some(&descriptor_set_layout)
+    }
+
+    #[inline]
+    pub fn descriptor_set_layouts(&self) -> &[vk::DescriptorSetLayout] {
+        &self.descriptor_set_layouts
+    }
+
+    #[inline]
+    pub fn push_constant_ranges(&self) -> &[vk::PushConstantRange] {
+        &self.push_constant_ranges
+    }
+}
+
+impl Deref for PipelineLayout {
+    type Target = vk::PipelineLayout;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.pipeline_layout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ash::vk::Handle;
+
+    use super::*;
+    use crate::backend::DescriptorBinding;
+
+    fn descriptor_binding(set: u32, descriptor_type: vk::DescriptorType, count: u32) -> DescriptorBinding {
+        DescriptorBinding {
+            set,
+            binding: 0,
+            descriptor_type,
+            count,
+            stages: vk::ShaderStageFlags::COMPUTE,
+            name: None
+        }
+    }
+
+    fn reflection(sets: Vec<Vec<DescriptorBinding>>) -> PipelineLayoutReflection {
+        PipelineLayoutReflection {
+            sets,
+            push_constant_ranges: Vec::new()
+        }
+    }
+
+    #[test]
+    fn binds_explicit_bindless_set() {
+        let bindless_layout = vk::DescriptorSetLayout::from_raw(1);
+        let reflected_layout = vk::DescriptorSetLayout::from_raw(2);
+        let reflection = reflection(vec![
+            vec![descriptor_binding(0, vk::DescriptorType::UNIFORM_BUFFER, 1)],
+            vec![descriptor_binding(1, vk::DescriptorType::SAMPLED_IMAGE, 0)],
+        ]);
+
+        let mut created = Vec::new();
+        let descriptor_set_layouts = resolve_descriptor_set_layouts(&reflection, &[(1, bindless_layout)], |bindings| {
+            created.push(bindings.to_vec());
+            Ok(reflected_layout)
+        })
+        .unwrap();
+
+        assert_eq!(descriptor_set_layouts, [reflected_layout, bindless_layout]);
+        assert_eq!(created.len(), 1);
+
+        let layout = PipelineLayout {
+            pipeline_layout: vk::PipelineLayout::null(),
+            descriptor_set_layouts,
+            push_constant_ranges: Vec::new()
+        };
+
+        assert!(layout.uses_set_layout(1, bindless_layout));
+        assert!(!layout.uses_set_layout(0, bindless_layout));
+        assert!(!layout.uses_set_layout(2, bindless_layout));
+    }
+
+    #[test]
+    fn explicit_set_after_reflected_sets() {
+        let bindless_layout = vk::DescriptorSetLayout::from_raw(1);
+        let empty_layout = vk::DescriptorSetLayout::from_raw(2);
+        let reflection = reflection(vec![vec![]]);
+
+        let descriptor_set_layouts = resolve_descriptor_set_layouts(&reflection, &[(2, bindless_layout)], |bindings| {
+            assert!(bindings.is_empty());
+            Ok(empty_layout)
+        })
+        .unwrap();
+
+        assert_eq!(descriptor_set_layouts, [empty_layout, empty_layout, bindless_layout]);
+    }
+
+    #[test]
+    fn runtime_array_without_explicit_layout() {
+        let reflection = reflection(vec![vec![descriptor_binding(0, vk::DescriptorType::SAMPLED_IMAGE, 0)]]);
+
+        assert!(resolve_descriptor_set_layouts(&reflection, &[], |_| Ok(vk::DescriptorSetLayout::null())).is_err());
+    }
+}
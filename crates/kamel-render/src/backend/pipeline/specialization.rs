@@ -0,0 +1,46 @@
+use ash::vk;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SpecializationValue {
+    Bool(bool),
+    U32(u32),
+    I32(i32),
+    F32(f32)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpecializationConstant {
+    pub id: u32,
+    pub value: SpecializationValue
+}
+
+pub(crate) struct SpecializationData {
+    map_entries: Vec<vk::SpecializationMapEntry>,
+    data: Vec<u8>
+}
+
+impl SpecializationData {
+    pub(crate) fn new(constants: &[SpecializationConstant]) -> Self {
+        let mut map_entries = Vec::with_capacity(constants.len());
+        let mut data = Vec::with_capacity(constants.len() * 4);
+
+        for constant in constants {
+            map_entries.push(vk::SpecializationMapEntry::default().constant_id(constant.id).offset(data.len() as u32).size(4));
+
+            let bytes = match constant.value {
+                SpecializationValue::Bool(value) => (value as vk::Bool32).to_ne_bytes(),
+                SpecializationValue::U32(value) => value.to_ne_bytes(),
+                SpecializationValue::I32(value) => value.to_ne_bytes(),
+                SpecializationValue::F32(value) => value.to_ne_bytes()
+            };
+            data.extend_from_slice(&bytes);
+        }
+
+        Self { map_entries, data }
+    }
+
+    #[inline]
+    pub(crate) fn specialization_info(&self) -> vk::SpecializationInfo {
+        vk::SpecializationInfo::default().map_entries(&self.map_entries).data(&self.data)
+    }
+}
@@ -0,0 +1,182 @@
+use std::{slice, sync::Mutex};
+
+use ash::{extensions::khr::Swapchain, prelude::VkResult, vk};
+
+use crate::backend::{
+    sync::{BinarySemaphore, Fence, TimelineSemaphore},
+    CommandBuffer, Executable, PendingCommandBuffers
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum QueueType {
+    Direct,
+    Compute,
+    Transfer
+}
+
+//Per queue timeline values, queues that share a family report the same value
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubmissionValues {
+    pub direct: u64,
+    pub compute: u64,
+    pub transfer: u64
+}
+
+impl SubmissionValues {
+    #[inline]
+    pub fn is_completed(&self, completed: &SubmissionValues) -> bool {
+        self.direct <= completed.direct && self.compute <= completed.compute && self.transfer <= completed.transfer
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum SemaphoreSubmit<'a> {
+    Binary {
+        semaphore: &'a BinarySemaphore,
+        stage_mask: vk::PipelineStageFlags2
+    },
+    Timeline {
+        semaphore: &'a TimelineSemaphore,
+        value: u64,
+        stage_mask: vk::PipelineStageFlags2
+    }
+}
+
+impl SemaphoreSubmit<'_> {
+    #[inline]
+    fn semaphore_submit_info(&self) -> vk::SemaphoreSubmitInfo<'static> {
+        match *self {
+            Self::Binary { semaphore, stage_mask } => vk::SemaphoreSubmitInfo::default().semaphore(**semaphore).stage_mask(stage_mask),
+            Self::Timeline { semaphore, value, stage_mask } => vk::SemaphoreSubmitInfo::default().semaphore(**semaphore).value(value).stage_mask(stage_mask)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct SubmitDesc<'a> {
+    pub wait_semaphores: &'a [SemaphoreSubmit<'a>],
+    pub signal_semaphores: &'a [SemaphoreSubmit<'a>],
+    pub fence: Option<&'a Fence>
+}
+
+struct State {
+    queue: vk::Queue,
+    submission_value: u64
+}
+
+pub struct Queue {
+    state: Mutex<State>,
+    family_index: u32,
+
+    //Signaled by every submission with a monotonically increasing value
+    timeline_semaphore: vk::Semaphore,
+
+    loader: ash::Device,
+    swapchain_loader: Option<Swapchain>
+}
+
+impl Queue {
+    pub(crate) unsafe fn new(loader: &ash::Device, swapchain_loader: Option<&Swapchain>, family_index: u32) -> VkResult<Self> {
+        let queue = loader.get_device_queue(family_index, 0);
+
+        let mut semaphore_type_create_info = vk::SemaphoreTypeCreateInfo::default().semaphore_type(vk::SemaphoreType::TIMELINE).initial_value(0);
+        let timeline_semaphore = loader.create_semaphore(&vk::SemaphoreCreateInfo::default().push_next(&mut semaphore_type_create_info), None)?;
+
+        Ok(Self {
+            state: Mutex::new(State { queue, submission_value: 0 }),
+            family_index,
+
+            timeline_semaphore,
+
+            loader: loader.clone(),
+            swapchain_loader: swapchain_loader.cloned()
+        })
+    }
+
+    pub(crate) unsafe fn destroy(&self) {
+        self.loader.destroy_semaphore(self.timeline_semaphore, None);
+    }
+
+    //The command buffers are handed back once the submission completed
+    pub unsafe fn submit(&self, command_buffers: Vec<CommandBuffer<Executable>>, desc: &SubmitDesc) -> VkResult<PendingCommandBuffers> {
+        let command_buffer_infos: Vec<_> = command_buffers
+            .iter()
+            .map(|command_buffer| vk::CommandBufferSubmitInfo::default().command_buffer(**command_buffer))
+            .collect();
+        let wait_semaphore_infos: Vec<_> = desc.wait_semaphores.iter().map(SemaphoreSubmit::semaphore_submit_info).collect();
+        let mut signal_semaphore_infos: Vec<_> = desc.signal_semaphores.iter().map(SemaphoreSubmit::semaphore_submit_info).collect();
+
+        let mut state = self.state.lock().unwrap();
+        let submission_value = state.submission_value + 1;
+
+        signal_semaphore_infos.push(
+            vk::SemaphoreSubmitInfo::default()
+                .semaphore(self.timeline_semaphore)
+                .value(submission_value)
+                .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+        );
+
+        let submit_info = vk::SubmitInfo2::default()
+            .wait_semaphore_infos(&wait_semaphore_infos)
+            .command_buffer_infos(&command_buffer_infos)
+            .signal_semaphore_infos(&signal_semaphore_infos);
+
+        self.loader
+            .queue_submit2(state.queue, slice::from_ref(&submit_info), desc.fence.map_or(vk::Fence::null(), |fence| **fence))?;
+
+        state.submission_value = submission_value;
+
+        Ok(PendingCommandBuffers::new(command_buffers, self.timeline_semaphore, submission_value))
+    }
+
+    //Returns true if the swapchain is suboptimal
+    pub unsafe fn present(&self, swapchain: vk::SwapchainKHR, image_index: u32, wait_semaphores: &[&BinarySemaphore]) -> VkResult<bool> {
+        let wait_semaphores: Vec<_> = wait_semaphores.iter().map(|semaphore| ***semaphore).collect();
+
+        let present_info = vk::PresentInfoKHR::default()
+            .wait_semaphores(&wait_semaphores)
+            .swapchains(slice::from_ref(&swapchain))
+            .image_indices(slice::from_ref(&image_index));
+
+        let swapchain_loader = self.swapchain_loader.as_ref().ok_or(vk::Result::ERROR_EXTENSION_NOT_PRESENT)?;
+
+        let state = self.state.lock().unwrap();
+        swapchain_loader.queue_present(state.queue, &present_info)
+    }
+
+    #[inline]
+    pub unsafe fn wait_idle(&self) -> VkResult<()> {
+        let state = self.state.lock().unwrap();
+        self.loader.queue_wait_idle(state.queue)
+    }
+
+    #[inline]
+    pub unsafe fn completed_value(&self) -> VkResult<u64> {
+        self.loader.get_semaphore_counter_value(self.timeline_semaphore)
+    }
+
+    #[inline]
+    pub unsafe fn wait_for_value(&self, value: u64, timeout: u64) -> VkResult<()> {
+        self.loader.wait_semaphores(
+            &vk::SemaphoreWaitInfo::default()
+                .semaphores(slice::from_ref(&self.timeline_semaphore))
+                .values(slice::from_ref(&value)),
+            timeout
+        )
+    }
+
+    #[inline]
+    pub fn last_submitted_value(&self) -> u64 {
+        self.state.lock().unwrap().submission_value
+    }
+
+    #[inline]
+    pub fn family_index(&self) -> u32 {
+        self.family_index
+    }
+
+    #[inline]
+    pub fn timeline_semaphore(&self) -> &vk::Semaphore {
+        &self.timeline_semaphore
+    }
+}
@@ -0,0 +1,211 @@
+use std::{any::Any, sync::Arc};
+
+use anyhow::Result;
+use ash::vk;
+
+use crate::backend::{Access, AccessInfo, AccessState, Buffer, CommandBuffer, Device, Image, ImageRange, QueueType, Recording};
+
+//Command buffers that record a barrier of the transfer keep the resource alive until their submission completed
+#[derive(Clone)]
+enum OwnershipResource {
+    Buffer(Arc<Buffer>),
+    Image(Arc<Image>, ImageRange)
+}
+
+impl OwnershipResource {
+    #[inline]
+    fn retained(&self) -> Arc<dyn Any + Send + Sync> {
+        match self {
+            Self::Buffer(buffer) => buffer.clone(),
+            Self::Image(image, _) => image.clone()
+        }
+    }
+}
+
+enum OwnershipBarrier {
+    Buffer(vk::BufferMemoryBarrier2<'static>),
+    Image(vk::ImageMemoryBarrier2<'static>)
+}
+
+//Release and acquire barrier pair that moves an exclusive resource from one queue family to another.
+//The submission of the acquire has to wait on a semaphore signaled by the submission of the release.
+#[derive(Clone)]
+pub struct QueueOwnershipTransfer {
+    resource: OwnershipResource,
+    src_info: AccessInfo,
+    dst_access: Access,
+    dst_info: AccessInfo,
+    //None if both queues belong to the same family
+    family_indices: Option<(u32, u32)>
+}
+
+impl QueueOwnershipTransfer {
+    #[inline]
+    fn family_indices(device: &Device, src_queue: QueueType, dst_queue: QueueType) -> Option<(u32, u32)> {
+        let src_family_index = device.queue(src_queue).family_index();
+        let dst_family_index = device.queue(dst_queue).family_index();
+
+        if src_family_index != dst_family_index {
+            Some((src_family_index, dst_family_index))
+        } else {
+            None
+        }
+    }
+
+    pub fn buffer(device: &Device, buffer: &Arc<Buffer>, src_queue: QueueType, src_access: Access, dst_queue: QueueType, dst_access: Access) -> Self {
+        Self {
+            resource: OwnershipResource::Buffer(buffer.clone()),
+            src_info: src_access.info(),
+            dst_access,
+            dst_info: dst_access.info(),
+            family_indices: Self::family_indices(device, src_queue, dst_queue)
+        }
+    }
+
+    //The layout transition from the layout of src_access to the one of dst_access is part of the transfer,
+    //fails if the range is not part of the image
+    pub fn image(
+        device: &Device,
+        image: &Arc<Image>,
+        subresource_range: vk::ImageSubresourceRange,
+        src_queue: QueueType,
+        src_access: Access,
+        dst_queue: QueueType,
+        dst_access: Access
+    ) -> Result<Self> {
+        Ok(Self {
+            resource: OwnershipResource::Image(image.clone(), image.resolve_subresource_range(&subresource_range)?),
+            src_info: src_access.info(),
+            dst_access,
+            dst_info: dst_access.info(),
+            family_indices: Self::family_indices(device, src_queue, dst_queue)
+        })
+    }
+
+    //True if both queues share a family, only a layout transition is recorded by the acquire then
+    #[inline]
+    pub fn is_noop(&self) -> bool {
+        self.family_indices.is_none()
+    }
+
+    fn barrier(
+        &self,
+        src_stage_mask: vk::PipelineStageFlags2,
+        src_access_mask: vk::AccessFlags2,
+        dst_stage_mask: vk::PipelineStageFlags2,
+        dst_access_mask: vk::AccessFlags2
+    ) -> OwnershipBarrier {
+        let (src_family_index, dst_family_index) = self.family_indices.unwrap_or((vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED));
+
+        match &self.resource {
+            OwnershipResource::Buffer(buffer) => {
+                OwnershipBarrier::Buffer(
+                    vk::BufferMemoryBarrier2::default()
+                        .src_stage_mask(src_stage_mask)
+                        .src_access_mask(src_access_mask)
+                        .dst_stage_mask(dst_stage_mask)
+                        .dst_access_mask(dst_access_mask)
+                        .src_queue_family_index(src_family_index)
+                        .dst_queue_family_index(dst_family_index)
+                        .buffer(***buffer)
+                        .offset(0)
+                        .size(vk::WHOLE_SIZE)
+                )
+            }
+            OwnershipResource::Image(image, range) => {
+                OwnershipBarrier::Image(
+                    vk::ImageMemoryBarrier2::default()
+                        .src_stage_mask(src_stage_mask)
+                        .src_access_mask(src_access_mask)
+                        .dst_stage_mask(dst_stage_mask)
+                        .dst_access_mask(dst_access_mask)
+                        .old_layout(self.src_info.image_layout)
+                        .new_layout(self.dst_info.image_layout)
+                        .src_queue_family_index(src_family_index)
+                        .dst_queue_family_index(dst_family_index)
+                        .image(***image)
+                        .subresource_range(range.subresource_range())
+                )
+            }
+        }
+    }
+
+    #[inline]
+    fn release_barrier(&self) -> Option<OwnershipBarrier> {
+        self.family_indices
+            .map(|_| self.barrier(self.src_info.stage_mask, self.src_info.access_mask, vk::PipelineStageFlags2::NONE, vk::AccessFlags2::NONE))
+    }
+
+    fn acquire_barrier(&self) -> Option<OwnershipBarrier> {
+        if self.family_indices.is_some() {
+            Some(self.barrier(vk::PipelineStageFlags2::NONE, vk::AccessFlags2::NONE, self.dst_info.stage_mask, self.dst_info.access_mask))
+        } else if self.src_info.image_layout != self.dst_info.image_layout && matches!(self.resource, OwnershipResource::Image(..)) {
+            //The semaphore wait already made the writes of the source queue visible
+            Some(self.barrier(vk::PipelineStageFlags2::ALL_COMMANDS, vk::AccessFlags2::NONE, self.dst_info.stage_mask, self.dst_info.access_mask))
+        } else {
+            None
+        }
+    }
+
+    //Records all barriers with a single pipeline barrier, nothing is recorded if there are none
+    fn record_barriers(command_buffer: &mut CommandBuffer<Recording>, barriers: impl IntoIterator<Item = OwnershipBarrier>) {
+        let mut buffer_barriers = Vec::new();
+        let mut image_barriers = Vec::new();
+
+        for barrier in barriers {
+            match barrier {
+                OwnershipBarrier::Buffer(barrier) => buffer_barriers.push(barrier),
+                OwnershipBarrier::Image(barrier) => image_barriers.push(barrier)
+            }
+        }
+
+        if !buffer_barriers.is_empty() || !image_barriers.is_empty() {
+            unsafe { command_buffer.pipeline_barrier2(&vk::DependencyInfo::default().buffer_memory_barriers(&buffer_barriers).image_memory_barriers(&image_barriers)) };
+        }
+    }
+
+    #[inline]
+    fn retain(&self, command_buffer: &mut CommandBuffer<Recording>, barrier: Option<OwnershipBarrier>) -> Option<OwnershipBarrier> {
+        if barrier.is_some() {
+            command_buffer.retain(self.resource.retained());
+        }
+
+        barrier
+    }
+
+    //Has to be recorded on a command buffer of the source queue
+    #[inline]
+    pub fn record_release(&self, command_buffer: &mut CommandBuffer<Recording>) {
+        let barrier = self.retain(command_buffer, self.release_barrier());
+        Self::record_barriers(command_buffer, barrier);
+    }
+
+    //The tracked state of the resource is in dst_access afterwards, for CommandBuffer::transition on the destination queue
+    fn acquire(&self, command_buffer: &mut CommandBuffer<Recording>) -> Option<OwnershipBarrier> {
+        match &self.resource {
+            OwnershipResource::Buffer(buffer) => buffer.assume_access(self.dst_access),
+            OwnershipResource::Image(image, range) => image.assume_range_state(*range, AccessState::image(self.dst_access))
+        }
+
+        self.retain(command_buffer, self.acquire_barrier())
+    }
+
+    //Has to be recorded on a command buffer of the destination queue
+    #[inline]
+    pub fn record_acquire(&self, command_buffer: &mut CommandBuffer<Recording>) {
+        let barrier = self.acquire(command_buffer);
+        Self::record_barriers(command_buffer, barrier);
+    }
+
+    //Records the acquires of several transfers to the same queue with one pipeline barrier
+    pub fn record_acquires<'a>(command_buffer: &mut CommandBuffer<Recording>, transfers: impl IntoIterator<Item = &'a Self>) {
+        let barriers: Vec<_> = transfers.into_iter().filter_map(|transfer| transfer.acquire(command_buffer)).collect();
+        Self::record_barriers(command_buffer, barriers);
+    }
+
+    #[inline]
+    pub fn record(&self, release_command_buffer: &mut CommandBuffer<Recording>, acquire_command_buffer: &mut CommandBuffer<Recording>) {
+        self.record_release(release_command_buffer);
+        self.record_acquire(acquire_command_buffer);
+    }
+}
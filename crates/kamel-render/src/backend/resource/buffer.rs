@@ -0,0 +1,254 @@
+use std::{
+    mem,
+    ops::Deref,
+    ptr,
+    sync::{Arc, Mutex}
+};
+
+use anyhow::Result;
+use ash::vk;
+use vk_mem_alloc::Allocation;
+
+use crate::backend::{deletion_queue::DeferredDestruction, util::debug_utils, Access, AccessState, Device, MemoryBlock, MemoryLocation};
+
+#[derive(Copy, Clone, Debug)]
+pub struct BufferDesc<'a> {
+    pub size: u64,
+    pub usage: vk::BufferUsageFlags,
+    pub memory_location: MemoryLocation,
+    pub label: Option<&'a str>
+}
+
+enum BufferMemory {
+    Allocation(Allocation),
+    //Placed into a block that is shared with other resources, the block is kept alive by the buffer
+    Aliased(Arc<MemoryBlock>)
+}
+
+pub struct Buffer {
+    buffer: vk::Buffer,
+    memory: BufferMemory,
+    mapped_ptr: *mut u8,
+    device_address: Option<vk::DeviceAddress>,
+
+    size: u64,
+    usage: vk::BufferUsageFlags,
+    memory_location: MemoryLocation,
+
+    state: Mutex<AccessState>,
+
+    device: Device
+}
+
+impl Buffer {
+    #[inline]
+    fn buffer_create_info(desc: &BufferDesc) -> vk::BufferCreateInfo<'static> {
+        vk::BufferCreateInfo::default().size(desc.size).usage(desc.usage).sharing_mode(vk::SharingMode::EXCLUSIVE)
+    }
+
+    #[inline]
+    fn check_desc(device: &Device, desc: &BufferDesc) -> Result<()> {
+        if desc.usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) && device.enabled_features().features_12.buffer_device_address == vk::FALSE {
+            anyhow::bail!("Buffers with SHADER_DEVICE_ADDRESS usage need the buffer_device_address feature");
+        }
+
+        Ok(())
+    }
+
+    fn from_raw(device: Device, buffer: vk::Buffer, memory: BufferMemory, mapped_ptr: *mut u8, desc: &BufferDesc) -> Result<Self> {
+        let device_address = if desc.usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+            Some(unsafe { device.loader().get_buffer_device_address(&vk::BufferDeviceAddressInfo::default().buffer(buffer)) })
+        } else {
+            None
+        };
+
+        let buffer = Self {
+            buffer,
+            memory,
+            mapped_ptr,
+            device_address,
+
+            size: desc.size,
+            usage: desc.usage,
+            memory_location: desc.memory_location,
+
+            state: Mutex::new(AccessState::buffer(Access::Nothing)),
+
+            device
+        };
+
+        if let Some(label) = desc.label {
+            unsafe { debug_utils::set_object_name(&buffer.device, buffer.buffer, label) }?;
+        }
+
+        Ok(buffer)
+    }
+
+    pub fn new(device: Device, desc: &BufferDesc) -> Result<Self> {
+        Self::check_desc(&device, desc)?;
+
+        let buffer_create_info = Self::buffer_create_info(desc);
+
+        let (buffer, allocation, allocation_info) = unsafe { vk_mem_alloc::create_buffer(*device.allocator(), &buffer_create_info, &desc.memory_location.allocation_create_info()) }?;
+
+        Self::from_raw(device, buffer, BufferMemory::Allocation(allocation), allocation_info.mapped_data.cast(), desc)
+    }
+
+    //The buffer is never mapped, the memory location of the desc has to be GpuOnly
+    pub fn new_aliased(device: Device, desc: &BufferDesc, memory_block: Arc<MemoryBlock>, offset: u64) -> Result<Self> {
+        if desc.memory_location != MemoryLocation::GpuOnly {
+            anyhow::bail!("Aliased buffers have to be GpuOnly instead of {:?}", desc.memory_location);
+        }
+        Self::check_desc(&device, desc)?;
+
+        let buffer = unsafe { device.loader().create_buffer(&Self::buffer_create_info(desc), None) }?;
+        let memory_requirements = unsafe { device.loader().get_buffer_memory_requirements(buffer) };
+
+        if let Err(error) = memory_block
+            .check_range(&memory_requirements, offset)
+            .and_then(|_| unsafe { memory_block.bind_buffer(buffer, offset) })
+        {
+            unsafe { device.loader().destroy_buffer(buffer, None) };
+            return Err(error);
+        }
+
+        Self::from_raw(device, buffer, BufferMemory::Aliased(memory_block), ptr::null_mut(), desc)
+    }
+
+    //Memory requirements of a buffer created with the desc, without creating it
+    pub fn memory_requirements(device: &Device, desc: &BufferDesc) -> Result<vk::MemoryRequirements> {
+        if device.enabled_features().features_13.maintenance4 == vk::FALSE {
+            anyhow::bail!("Querying memory requirements without a buffer requires maintenance4");
+        }
+
+        let buffer_create_info = Self::buffer_create_info(desc);
+
+        let mut memory_requirements = vk::MemoryRequirements2::default();
+        unsafe {
+            device
+                .loader()
+                .get_device_buffer_memory_requirements(&vk::DeviceBufferMemoryRequirements::default().create_info(&buffer_create_info), &mut memory_requirements)
+        };
+
+        Ok(memory_requirements.memory_requirements)
+    }
+
+    #[inline]
+    fn check_range<T>(&self, offset: u64, len: usize) -> Result<()> {
+        if self.mapped_ptr.is_null() {
+            anyhow::bail!("Buffer with memory location {:?} is not mapped", self.memory_location);
+        }
+
+        let size = len.checked_mul(mem::size_of::<T>()).map(|size| size as u64);
+        if size.and_then(|size| offset.checked_add(size)).map_or(true, |end| end > self.size) {
+            anyhow::bail!("{} elements at offset {} are out of bounds for a buffer of size {}", len, offset, self.size);
+        }
+
+        Ok(())
+    }
+
+    //The caller has to make sure that the GPU does not access the range concurrently
+    pub unsafe fn write<T: Copy>(&self, offset: u64, data: &[T]) -> Result<()> {
+        self.check_range::<T>(offset, data.len())?;
+
+        let size = mem::size_of_val(data);
+        ptr::copy_nonoverlapping(data.as_ptr().cast::<u8>(), self.mapped_ptr.add(offset as usize), size);
+        if let BufferMemory::Allocation(allocation) = &self.memory {
+            vk_mem_alloc::flush_allocation(*self.device.allocator(), *allocation, offset, size as u64)?;
+        }
+
+        Ok(())
+    }
+
+    //The caller has to make sure that the GPU does not access the range concurrently
+    pub unsafe fn read<T: Copy>(&self, offset: u64, data: &mut [T]) -> Result<()> {
+        self.check_range::<T>(offset, data.len())?;
+
+        let size = mem::size_of_val(data);
+        if let BufferMemory::Allocation(allocation) = &self.memory {
+            vk_mem_alloc::invalidate_allocation(*self.device.allocator(), *allocation, offset, size as u64)?;
+        }
+        ptr::copy_nonoverlapping(self.mapped_ptr.add(offset as usize), data.as_mut_ptr().cast::<u8>(), size);
+
+        Ok(())
+    }
+
+    //Updates the tracked state and returns the barrier needed before the access
+    #[inline]
+    pub(crate) fn transition_barrier(&self, access: Access) -> Option<vk::BufferMemoryBarrier2<'static>> {
+        self.state
+            .lock()
+            .unwrap()
+            .transition(access)
+            .map(|barrier| barrier.buffer_memory_barrier(self.buffer, 0, vk::WHOLE_SIZE))
+    }
+
+    //Overrides the tracked state after barriers were recorded without CommandBuffer::transition_buffer
+    #[inline]
+    pub fn assume_access(&self, access: Access) {
+        self.assume_state(AccessState::buffer(access));
+    }
+
+    #[inline]
+    pub(crate) fn assume_state(&self, state: AccessState) {
+        *self.state.lock().unwrap() = state;
+    }
+
+    #[inline]
+    pub(crate) fn state(&self) -> AccessState {
+        *self.state.lock().unwrap()
+    }
+
+    #[inline]
+    pub fn mapped_ptr(&self) -> Option<*mut u8> {
+        if self.mapped_ptr.is_null() {
+            None
+        } else {
+            Some(self.mapped_ptr)
+        }
+    }
+
+    #[inline]
+    pub fn device_address(&self) -> Option<vk::DeviceAddress> {
+        self.device_address
+    }
+
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    #[inline]
+    pub fn usage(&self) -> vk::BufferUsageFlags {
+        self.usage
+    }
+
+    #[inline]
+    pub fn memory_location(&self) -> MemoryLocation {
+        self.memory_location
+    }
+}
+
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
+
+impl Deref for Buffer {
+    type Target = vk::Buffer;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl Drop for Buffer {
+    #[inline]
+    fn drop(&mut self) {
+        let allocation = match &self.memory {
+            BufferMemory::Allocation(allocation) => Some(*allocation),
+            BufferMemory::Aliased(_) => None
+        };
+
+        self.device.defer_destruction(DeferredDestruction::Buffer(self.buffer, allocation));
+    }
+}
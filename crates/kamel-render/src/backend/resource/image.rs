@@ -0,0 +1,518 @@
+use std::{
+    ops::Deref,
+    sync::{Arc, Mutex}
+};
+
+use anyhow::Result;
+use ash::vk;
+use vk_mem_alloc::Allocation;
+
+use crate::backend::{deletion_queue::DeferredDestruction, util::debug_utils, Access, AccessBarrier, AccessState, Device, MemoryBlock, MemoryLocation};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ImageType {
+    Tex2d,
+    Tex3d,
+    Cube
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ImageDesc<'a> {
+    pub image_type: ImageType,
+    pub format: vk::Format,
+    pub extent: vk::Extent3D,
+    pub mip_levels: u32,
+    //Multiple of 6 for cube images
+    pub array_layers: u32,
+    pub samples: vk::SampleCountFlags,
+    pub usage: vk::ImageUsageFlags,
+    pub memory_location: MemoryLocation,
+    pub label: Option<&'a str>
+}
+
+enum ImageMemory {
+    Allocation(Allocation),
+    //Placed into a block that is shared with other resources, the block is kept alive by the image
+    Aliased(Arc<MemoryBlock>)
+}
+
+//Subresource range that was checked against an image, with the remaining mip levels and array layers resolved
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ImageRange {
+    aspect_mask: vk::ImageAspectFlags,
+    base_mip_level: u32,
+    level_count: u32,
+    base_array_layer: u32,
+    layer_count: u32
+}
+
+impl ImageRange {
+    #[inline]
+    pub(crate) fn subresource_range(&self) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange::default()
+            .aspect_mask(self.aspect_mask)
+            .base_mip_level(self.base_mip_level)
+            .level_count(self.level_count)
+            .base_array_layer(self.base_array_layer)
+            .layer_count(self.layer_count)
+    }
+}
+
+//None if the range is empty or exceeds the total
+#[inline]
+fn resolve_count(base: u32, count: u32, remaining: u32, total: u32) -> Option<u32> {
+    let count = if count == remaining { total.checked_sub(base)? } else { count };
+
+    (count != 0 && base.checked_add(count)? <= total).then_some(count)
+}
+
+pub struct Image {
+    image: vk::Image,
+    memory: ImageMemory,
+
+    image_type: ImageType,
+    format: vk::Format,
+    extent: vk::Extent3D,
+    mip_levels: u32,
+    array_layers: u32,
+    samples: vk::SampleCountFlags,
+    usage: vk::ImageUsageFlags,
+
+    //One state per tracked aspect, mip level and array layer, aspect major then mip level major
+    states: Mutex<Vec<AccessState>>,
+
+    device: Device
+}
+
+impl Image {
+    fn image_create_info(desc: &ImageDesc) -> Result<vk::ImageCreateInfo<'static>> {
+        let (vk_image_type, flags) = match desc.image_type {
+            ImageType::Tex2d => (vk::ImageType::TYPE_2D, vk::ImageCreateFlags::empty()),
+            ImageType::Tex3d => {
+                if desc.array_layers != 1 {
+                    anyhow::bail!("3D images cannot have array layers");
+                }
+
+                (vk::ImageType::TYPE_3D, vk::ImageCreateFlags::empty())
+            }
+            ImageType::Cube => {
+                if desc.array_layers == 0 || desc.array_layers % 6 != 0 || desc.extent.width != desc.extent.height {
+                    anyhow::bail!("Cube images need square faces and a multiple of 6 array layers");
+                }
+
+                (vk::ImageType::TYPE_2D, vk::ImageCreateFlags::CUBE_COMPATIBLE)
+            }
+        };
+
+        Ok(vk::ImageCreateInfo::default()
+            .flags(flags)
+            .image_type(vk_image_type)
+            .format(desc.format)
+            .extent(desc.extent)
+            .mip_levels(desc.mip_levels)
+            .array_layers(desc.array_layers)
+            .samples(desc.samples)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(desc.usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED))
+    }
+
+    fn from_raw(device: Device, image: vk::Image, memory: ImageMemory, desc: &ImageDesc) -> Result<Self> {
+        let image = Self {
+            image,
+            memory,
+
+            image_type: desc.image_type,
+            format: desc.format,
+            extent: desc.extent,
+            mip_levels: desc.mip_levels,
+            array_layers: desc.array_layers,
+            samples: desc.samples,
+            usage: desc.usage,
+
+            states: Mutex::new(vec![
+                AccessState::image(Access::Nothing);
+                tracked_aspects(desc.format).len() * desc.mip_levels as usize * desc.array_layers as usize
+            ]),
+
+            device
+        };
+
+        if let Some(label) = desc.label {
+            unsafe { debug_utils::set_object_name(&image.device, image.image, label) }?;
+        }
+
+        Ok(image)
+    }
+
+    pub fn new(device: Device, desc: &ImageDesc) -> Result<Self> {
+        let image_create_info = Self::image_create_info(desc)?;
+
+        let (image, allocation, _) = unsafe { vk_mem_alloc::create_image(*device.allocator(), &image_create_info, &desc.memory_location.allocation_create_info()) }?;
+
+        Self::from_raw(device, image, ImageMemory::Allocation(allocation), desc)
+    }
+
+    //The memory location of the desc is ignored, the image lives in the memory block
+    pub fn new_aliased(device: Device, desc: &ImageDesc, memory_block: Arc<MemoryBlock>, offset: u64) -> Result<Self> {
+        if desc.memory_location != MemoryLocation::GpuOnly {
+            anyhow::bail!("Aliased images have to be GpuOnly instead of {:?}", desc.memory_location);
+        }
+        let image_create_info = Self::image_create_info(desc)?;
+
+        let image = unsafe { device.loader().create_image(&image_create_info, None) }?;
+        let memory_requirements = unsafe { device.loader().get_image_memory_requirements(image) };
+
+        if let Err(error) = memory_block
+            .check_range(&memory_requirements, offset)
+            .and_then(|_| unsafe { memory_block.bind_image(image, offset) })
+        {
+            unsafe { device.loader().destroy_image(image, None) };
+            return Err(error);
+        }
+
+        Self::from_raw(device, image, ImageMemory::Aliased(memory_block), desc)
+    }
+
+    //Memory requirements of an image created with the desc, without creating it
+    pub fn memory_requirements(device: &Device, desc: &ImageDesc) -> Result<vk::MemoryRequirements> {
+        if device.enabled_features().features_13.maintenance4 == vk::FALSE {
+            anyhow::bail!("Querying memory requirements without an image requires maintenance4");
+        }
+
+        let image_create_info = Self::image_create_info(desc)?;
+
+        let mut memory_requirements = vk::MemoryRequirements2::default();
+        unsafe {
+            device
+                .loader()
+                .get_device_image_memory_requirements(&vk::DeviceImageMemoryRequirements::default().create_info(&image_create_info), &mut memory_requirements)
+        };
+
+        Ok(memory_requirements.memory_requirements)
+    }
+
+    #[inline]
+    pub fn image_type(&self) -> ImageType {
+        self.image_type
+    }
+
+    #[inline]
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    #[inline]
+    pub fn extent(&self) -> vk::Extent3D {
+        self.extent
+    }
+
+    #[inline]
+    pub fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    #[inline]
+    pub fn array_layers(&self) -> u32 {
+        self.array_layers
+    }
+
+    #[inline]
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.samples
+    }
+
+    #[inline]
+    pub fn usage(&self) -> vk::ImageUsageFlags {
+        self.usage
+    }
+
+    #[inline]
+    pub fn aspect_mask(&self) -> vk::ImageAspectFlags {
+        format_aspect_mask(self.format)
+    }
+
+    //Depth and stencil are tracked separately since they can be in different layouts
+    #[inline]
+    fn tracked_aspects(&self) -> &'static [vk::ImageAspectFlags] {
+        tracked_aspects(self.format)
+    }
+
+    #[inline]
+    fn state_index(&self, plane: usize, mip_level: u32, array_layer: u32) -> usize {
+        (plane * self.mip_levels as usize + mip_level as usize) * self.array_layers as usize + array_layer as usize
+    }
+
+    //Checks the range against the image and resolves the remaining mip levels and array layers
+    pub(crate) fn resolve_subresource_range(&self, subresource_range: &vk::ImageSubresourceRange) -> Result<ImageRange> {
+        if subresource_range.aspect_mask.is_empty() || !self.aspect_mask().contains(subresource_range.aspect_mask) {
+            anyhow::bail!("Aspects {:?} are not part of the image aspects {:?}", subresource_range.aspect_mask, self.aspect_mask());
+        }
+
+        let level_count = match resolve_count(subresource_range.base_mip_level, subresource_range.level_count, vk::REMAINING_MIP_LEVELS, self.mip_levels) {
+            Some(level_count) => level_count,
+            None => {
+                anyhow::bail!(
+                    "Mip levels {} with count {} are out of range, the image has {} mip levels",
+                    subresource_range.base_mip_level,
+                    subresource_range.level_count,
+                    self.mip_levels
+                )
+            }
+        };
+
+        let layer_count = match resolve_count(subresource_range.base_array_layer, subresource_range.layer_count, vk::REMAINING_ARRAY_LAYERS, self.array_layers) {
+            Some(layer_count) => layer_count,
+            None => {
+                anyhow::bail!(
+                    "Array layers {} with count {} are out of range, the image has {} array layers",
+                    subresource_range.base_array_layer,
+                    subresource_range.layer_count,
+                    self.array_layers
+                )
+            }
+        };
+
+        Ok(ImageRange {
+            aspect_mask: subresource_range.aspect_mask,
+            base_mip_level: subresource_range.base_mip_level,
+            level_count,
+            base_array_layer: subresource_range.base_array_layer,
+            layer_count
+        })
+    }
+
+    #[inline]
+    pub(crate) fn full_range(&self) -> ImageRange {
+        ImageRange {
+            aspect_mask: self.aspect_mask(),
+            base_mip_level: 0,
+            level_count: self.mip_levels,
+            base_array_layer: 0,
+            layer_count: self.array_layers
+        }
+    }
+
+    //Updates the tracked state of the subresources and returns the barriers needed before the access,
+    //subresources that need the same barrier are merged into one
+    pub(crate) fn transition_range(&self, range: ImageRange, access: Access) -> Vec<vk::ImageMemoryBarrier2<'static>> {
+        //Barrier, aspect mask, base mip level, level count, base array layer, layer count
+        let mut ranges: Vec<(AccessBarrier, vk::ImageAspectFlags, u32, u32, u32, u32)> = Vec::new();
+        let mut states = self.states.lock().unwrap();
+
+        for (plane, aspect) in self.tracked_aspects().iter().enumerate().filter(|(_, aspect)| range.aspect_mask.contains(**aspect)) {
+            let first_range = ranges.len();
+            let mut push_range = |barrier: AccessBarrier, mip_level: u32, base_array_layer: u32, layer_count: u32| {
+                match ranges[first_range..].last_mut() {
+                    Some(last) if last.0 == barrier && last.4 == base_array_layer && last.5 == layer_count && last.2 + last.3 == mip_level => last.3 += 1,
+                    _ => ranges.push((barrier, *aspect, mip_level, 1, base_array_layer, layer_count))
+                }
+            };
+
+            for mip_level in range.base_mip_level..range.base_mip_level + range.level_count {
+                let mut current: Option<(AccessBarrier, u32, u32)> = None;
+
+                for array_layer in range.base_array_layer..range.base_array_layer + range.layer_count {
+                    let barrier = states[self.state_index(plane, mip_level, array_layer)].transition(access);
+
+                    if let (Some(current), Some(barrier)) = (current.as_mut(), barrier) {
+                        if current.0 == barrier {
+                            current.2 += 1;
+                            continue;
+                        }
+                    }
+
+                    if let Some((current_barrier, base_array_layer, count)) = current {
+                        push_range(current_barrier, mip_level, base_array_layer, count);
+                    }
+
+                    current = barrier.map(|barrier| (barrier, array_layer, 1));
+                }
+
+                if let Some((current_barrier, base_array_layer, count)) = current {
+                    push_range(current_barrier, mip_level, base_array_layer, count);
+                }
+            }
+        }
+
+        //Depth and stencil barriers of the same subresources are combined
+        let mut merged: Vec<(AccessBarrier, vk::ImageAspectFlags, u32, u32, u32, u32)> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged
+                .iter_mut()
+                .find(|merged| merged.0 == range.0 && (merged.2, merged.3, merged.4, merged.5) == (range.2, range.3, range.4, range.5))
+            {
+                Some(merged) => merged.1 |= range.1,
+                None => merged.push(range)
+            }
+        }
+
+        merged
+            .into_iter()
+            .map(|(barrier, aspect_mask, base_mip_level, level_count, base_array_layer, layer_count)| {
+                barrier.image_memory_barrier(
+                    self.image,
+                    vk::ImageSubresourceRange::default()
+                        .aspect_mask(aspect_mask)
+                        .base_mip_level(base_mip_level)
+                        .level_count(level_count)
+                        .base_array_layer(base_array_layer)
+                        .layer_count(layer_count)
+                )
+            })
+            .collect()
+    }
+
+    #[inline]
+    pub(crate) fn transition_barriers(&self, subresource_range: vk::ImageSubresourceRange, access: Access) -> Result<Vec<vk::ImageMemoryBarrier2<'static>>> {
+        Ok(self.transition_range(self.resolve_subresource_range(&subresource_range)?, access))
+    }
+
+    pub(crate) fn assume_range_state(&self, range: ImageRange, state: AccessState) {
+        let mut states = self.states.lock().unwrap();
+
+        for (plane, aspect) in self.tracked_aspects().iter().enumerate() {
+            if !range.aspect_mask.contains(*aspect) {
+                continue;
+            }
+
+            for mip_level in range.base_mip_level..range.base_mip_level + range.level_count {
+                for array_layer in range.base_array_layer..range.base_array_layer + range.layer_count {
+                    states[self.state_index(plane, mip_level, array_layer)] = state;
+                }
+            }
+        }
+    }
+
+    //Overrides the tracked state after barriers were recorded without CommandBuffer::transition
+    #[inline]
+    pub fn assume_access(&self, subresource_range: vk::ImageSubresourceRange, access: Access) -> Result<()> {
+        self.assume_range_state(self.resolve_subresource_range(&subresource_range)?, AccessState::image(access));
+        Ok(())
+    }
+
+    //State shared by every subresource, None if they differ
+    pub(crate) fn uniform_state(&self) -> Option<AccessState> {
+        let states = self.states.lock().unwrap();
+        let first = states.first().copied()?;
+
+        states.iter().all(|state| *state == first).then_some(first)
+    }
+
+    #[inline]
+    pub fn full_subresource_range(&self) -> vk::ImageSubresourceRange {
+        self.full_range().subresource_range()
+    }
+}
+
+unsafe impl Send for Image {}
+unsafe impl Sync for Image {}
+
+impl Deref for Image {
+    type Target = vk::Image;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.image
+    }
+}
+
+impl Drop for Image {
+    #[inline]
+    fn drop(&mut self) {
+        let allocation = match &self.memory {
+            ImageMemory::Allocation(allocation) => Some(*allocation),
+            ImageMemory::Aliased(_) => None
+        };
+
+        self.device.defer_destruction(DeferredDestruction::Image(self.image, allocation));
+    }
+}
+
+fn tracked_aspects(format: vk::Format) -> &'static [vk::ImageAspectFlags] {
+    let aspect_mask = format_aspect_mask(format);
+
+    if aspect_mask == vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL {
+        &[vk::ImageAspectFlags::DEPTH, vk::ImageAspectFlags::STENCIL]
+    } else if aspect_mask == vk::ImageAspectFlags::DEPTH {
+        &[vk::ImageAspectFlags::DEPTH]
+    } else if aspect_mask == vk::ImageAspectFlags::STENCIL {
+        &[vk::ImageAspectFlags::STENCIL]
+    } else {
+        &[vk::ImageAspectFlags::COLOR]
+    }
+}
+
+pub fn format_aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => vk::ImageAspectFlags::DEPTH,
+        vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+        vk::Format::D16_UNORM_S8_UINT | vk::Format::D24_UNORM_S8_UINT | vk::Format::D32_SFLOAT_S8_UINT => vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL,
+        _ => vk::ImageAspectFlags::COLOR
+    }
+}
+
+//Size in bytes and extent of a texel block of the aspect as it is laid out in buffers by copy commands, None for unknown formats
+pub fn format_texel_block(format: vk::Format, aspect_mask: vk::ImageAspectFlags) -> Option<(u32, u32, u32)> {
+    if aspect_mask == vk::ImageAspectFlags::STENCIL && format_aspect_mask(format).contains(vk::ImageAspectFlags::STENCIL) {
+        return Some((1, 1, 1));
+    }
+
+    let block = match format {
+        vk::Format::R8_UNORM | vk::Format::R8_SNORM | vk::Format::R8_UINT | vk::Format::R8_SINT | vk::Format::R8_SRGB => (1, 1, 1),
+        vk::Format::R8G8_UNORM | vk::Format::R8G8_SNORM | vk::Format::R8G8_UINT | vk::Format::R8G8_SINT | vk::Format::R8G8_SRGB => (2, 1, 1),
+        vk::Format::R16_UNORM | vk::Format::R16_SNORM | vk::Format::R16_UINT | vk::Format::R16_SINT | vk::Format::R16_SFLOAT => (2, 1, 1),
+        vk::Format::R5G6B5_UNORM_PACK16 | vk::Format::B5G6R5_UNORM_PACK16 | vk::Format::R4G4B4A4_UNORM_PACK16 | vk::Format::B4G4R4A4_UNORM_PACK16 => (2, 1, 1),
+        vk::Format::R8G8B8_UNORM | vk::Format::R8G8B8_SNORM | vk::Format::R8G8B8_UINT | vk::Format::R8G8B8_SINT | vk::Format::R8G8B8_SRGB => (3, 1, 1),
+        vk::Format::B8G8R8_UNORM | vk::Format::B8G8R8_SNORM | vk::Format::B8G8R8_UINT | vk::Format::B8G8R8_SINT | vk::Format::B8G8R8_SRGB => (3, 1, 1),
+        vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SNORM | vk::Format::R8G8B8A8_UINT | vk::Format::R8G8B8A8_SINT | vk::Format::R8G8B8A8_SRGB => (4, 1, 1),
+        vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SNORM | vk::Format::B8G8R8A8_UINT | vk::Format::B8G8R8A8_SINT | vk::Format::B8G8R8A8_SRGB => (4, 1, 1),
+        vk::Format::A2R10G10B10_UNORM_PACK32 | vk::Format::A2B10G10R10_UNORM_PACK32 | vk::Format::B10G11R11_UFLOAT_PACK32 | vk::Format::E5B9G9R9_UFLOAT_PACK32 => (4, 1, 1),
+        vk::Format::R16G16_UNORM | vk::Format::R16G16_SNORM | vk::Format::R16G16_UINT | vk::Format::R16G16_SINT | vk::Format::R16G16_SFLOAT => (4, 1, 1),
+        vk::Format::R32_UINT | vk::Format::R32_SINT | vk::Format::R32_SFLOAT => (4, 1, 1),
+        vk::Format::R16G16B16_UNORM | vk::Format::R16G16B16_SNORM | vk::Format::R16G16B16_UINT | vk::Format::R16G16B16_SINT | vk::Format::R16G16B16_SFLOAT => (6, 1, 1),
+        vk::Format::R16G16B16A16_UNORM | vk::Format::R16G16B16A16_SNORM | vk::Format::R16G16B16A16_UINT | vk::Format::R16G16B16A16_SINT | vk::Format::R16G16B16A16_SFLOAT => (8, 1, 1),
+        vk::Format::R32G32_UINT | vk::Format::R32G32_SINT | vk::Format::R32G32_SFLOAT => (8, 1, 1),
+        vk::Format::R32G32B32_UINT | vk::Format::R32G32B32_SINT | vk::Format::R32G32B32_SFLOAT => (12, 1, 1),
+        vk::Format::R32G32B32A32_UINT | vk::Format::R32G32B32A32_SINT | vk::Format::R32G32B32A32_SFLOAT => (16, 1, 1),
+        //Depth aspect of the depth formats, X8_D24 and D24_S8 store the depth in 4 bytes in buffers
+        vk::Format::D16_UNORM | vk::Format::D16_UNORM_S8_UINT => (2, 1, 1),
+        vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D24_UNORM_S8_UINT | vk::Format::D32_SFLOAT | vk::Format::D32_SFLOAT_S8_UINT => (4, 1, 1),
+        vk::Format::BC1_RGB_UNORM_BLOCK | vk::Format::BC1_RGB_SRGB_BLOCK | vk::Format::BC1_RGBA_UNORM_BLOCK | vk::Format::BC1_RGBA_SRGB_BLOCK => (8, 4, 4),
+        vk::Format::BC4_UNORM_BLOCK | vk::Format::BC4_SNORM_BLOCK => (8, 4, 4),
+        vk::Format::BC2_UNORM_BLOCK | vk::Format::BC2_SRGB_BLOCK | vk::Format::BC3_UNORM_BLOCK | vk::Format::BC3_SRGB_BLOCK => (16, 4, 4),
+        vk::Format::BC5_UNORM_BLOCK | vk::Format::BC5_SNORM_BLOCK | vk::Format::BC6H_UFLOAT_BLOCK | vk::Format::BC6H_SFLOAT_BLOCK => (16, 4, 4),
+        vk::Format::BC7_UNORM_BLOCK | vk::Format::BC7_SRGB_BLOCK => (16, 4, 4),
+        _ => return None
+    };
+
+    Some(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_counts() {
+        assert_eq!(resolve_count(2, vk::REMAINING_MIP_LEVELS, vk::REMAINING_MIP_LEVELS, 5), Some(3));
+        assert_eq!(resolve_count(1, 4, vk::REMAINING_MIP_LEVELS, 5), Some(4));
+    }
+
+    #[test]
+    fn rejects_out_of_range_counts() {
+        assert_eq!(resolve_count(6, vk::REMAINING_ARRAY_LAYERS, vk::REMAINING_ARRAY_LAYERS, 5), None);
+        assert_eq!(resolve_count(5, vk::REMAINING_ARRAY_LAYERS, vk::REMAINING_ARRAY_LAYERS, 5), None);
+        assert_eq!(resolve_count(2, 4, vk::REMAINING_ARRAY_LAYERS, 5), None);
+        assert_eq!(resolve_count(0, 0, vk::REMAINING_ARRAY_LAYERS, 5), None);
+        assert_eq!(resolve_count(u32::MAX - 1, 2, vk::REMAINING_ARRAY_LAYERS, 5), None);
+    }
+
+    #[test]
+    fn tracks_depth_and_stencil_separately() {
+        assert_eq!(tracked_aspects(vk::Format::D24_UNORM_S8_UINT), &[vk::ImageAspectFlags::DEPTH, vk::ImageAspectFlags::STENCIL]);
+        assert_eq!(tracked_aspects(vk::Format::D32_SFLOAT), &[vk::ImageAspectFlags::DEPTH]);
+        assert_eq!(tracked_aspects(vk::Format::R8G8B8A8_UNORM), &[vk::ImageAspectFlags::COLOR]);
+    }
+}
@@ -0,0 +1,103 @@
+use std::{ops::Deref, sync::Arc};
+
+use anyhow::Result;
+use ash::vk;
+
+use crate::backend::{deletion_queue::DeferredDestruction, util::debug_utils, Device, Image};
+
+#[derive(Copy, Clone, Debug)]
+pub struct ImageViewDesc<'a> {
+    pub view_type: vk::ImageViewType,
+    //Defaults to the format of the image
+    pub format: Option<vk::Format>,
+    pub components: vk::ComponentMapping,
+    pub subresource_range: vk::ImageSubresourceRange,
+    pub label: Option<&'a str>
+}
+
+pub struct ImageView {
+    image_view: vk::ImageView,
+    subresource_range: vk::ImageSubresourceRange,
+    image: Arc<Image>,
+    device: Device
+}
+
+impl ImageView {
+    pub fn new(device: Device, image: Arc<Image>, desc: &ImageViewDesc) -> Result<Self> {
+        let range = &desc.subresource_range;
+
+        let level_count = if range.level_count == vk::REMAINING_MIP_LEVELS {
+            image.mip_levels().saturating_sub(range.base_mip_level)
+        } else {
+            range.level_count
+        };
+        let layer_count = if range.layer_count == vk::REMAINING_ARRAY_LAYERS {
+            image.array_layers().saturating_sub(range.base_array_layer)
+        } else {
+            range.layer_count
+        };
+
+        let mip_end = range.base_mip_level.checked_add(level_count);
+        let layer_end = range.base_array_layer.checked_add(layer_count);
+
+        if level_count == 0 || mip_end.map_or(true, |end| end > image.mip_levels()) || layer_count == 0 || layer_end.map_or(true, |end| end > image.array_layers()) {
+            anyhow::bail!(
+                "Subresource range (mips {}+{}, layers {}+{}) is out of bounds for an image with {} mips and {} layers",
+                range.base_mip_level,
+                level_count,
+                range.base_array_layer,
+                layer_count,
+                image.mip_levels(),
+                image.array_layers()
+            );
+        }
+
+        let subresource_range = range.level_count(level_count).layer_count(layer_count);
+
+        let image_view_create_info = vk::ImageViewCreateInfo::default()
+            .image(**image)
+            .view_type(desc.view_type)
+            .format(desc.format.unwrap_or_else(|| image.format()))
+            .components(desc.components)
+            .subresource_range(subresource_range);
+
+        let image_view = unsafe { device.loader().create_image_view(&image_view_create_info, None) }?;
+
+        if let Some(label) = desc.label {
+            unsafe { debug_utils::set_object_name(&device, image_view, label) }?;
+        }
+
+        Ok(Self {
+            image_view,
+            subresource_range,
+            image,
+            device
+        })
+    }
+
+    #[inline]
+    pub fn subresource_range(&self) -> &vk::ImageSubresourceRange {
+        &self.subresource_range
+    }
+
+    #[inline]
+    pub fn image(&self) -> &Arc<Image> {
+        &self.image
+    }
+}
+
+impl Deref for ImageView {
+    type Target = vk::ImageView;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.image_view
+    }
+}
+
+impl Drop for ImageView {
+    #[inline]
+    fn drop(&mut self) {
+        self.device.defer_destruction(DeferredDestruction::ImageView(self.image_view));
+    }
+}
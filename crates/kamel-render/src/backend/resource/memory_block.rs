@@ -0,0 +1,97 @@
+use anyhow::Result;
+use ash::vk;
+use vk_mem_alloc::{Allocation, AllocationCreateInfo};
+
+use crate::backend::{deletion_queue::DeferredDestruction, Device};
+
+//Device local memory that images and buffers can be placed into at arbitrary offsets, used for aliasing
+pub struct MemoryBlock {
+    allocation: Allocation,
+    memory: vk::DeviceMemory,
+    memory_type_index: u32,
+    offset: u64,
+    size: u64,
+    alignment: u64,
+    device: Device
+}
+
+impl MemoryBlock {
+    pub fn new(device: Device, memory_requirements: &vk::MemoryRequirements) -> Result<Self> {
+        //Automatic memory usages are not allowed for allocations without a resource
+        let allocation_create_info = AllocationCreateInfo {
+            preferred_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            ..Default::default()
+        };
+
+        let (allocation, allocation_info) = unsafe { vk_mem_alloc::allocate_memory(*device.allocator(), memory_requirements, &allocation_create_info) }?;
+
+        Ok(Self {
+            allocation,
+            memory: allocation_info.device_memory,
+            memory_type_index: allocation_info.memory_type,
+            offset: allocation_info.offset,
+            size: memory_requirements.size,
+            alignment: memory_requirements.alignment,
+            device
+        })
+    }
+
+    #[inline]
+    pub(crate) fn check_range(&self, memory_requirements: &vk::MemoryRequirements, offset: u64) -> Result<()> {
+        if memory_requirements.memory_type_bits & (1 << self.memory_type_index) == 0 {
+            anyhow::bail!(
+                "Memory type {} of the memory block is not one of the supported memory types {:#b}",
+                self.memory_type_index,
+                memory_requirements.memory_type_bits
+            );
+        }
+
+        //The block itself starts at an offset into the device memory it was suballocated from
+        let memory_offset = self.offset.checked_add(offset).ok_or_else(|| anyhow::anyhow!("Offset {} overflows the memory block", offset))?;
+        if memory_offset % memory_requirements.alignment != 0 {
+            anyhow::bail!("Offset {} does not satisfy the alignment of {}", offset, memory_requirements.alignment);
+        }
+
+        if offset.checked_add(memory_requirements.size).map_or(true, |end| end > self.size) {
+            anyhow::bail!(
+                "Range {}..{} is out of bounds for a memory block of size {}",
+                offset,
+                offset.saturating_add(memory_requirements.size),
+                self.size
+            );
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) unsafe fn bind_image(&self, image: vk::Image, offset: u64) -> Result<()> {
+        Ok(self.device.loader().bind_image_memory(image, self.memory, self.offset + offset)?)
+    }
+
+    #[inline]
+    pub(crate) unsafe fn bind_buffer(&self, buffer: vk::Buffer, offset: u64) -> Result<()> {
+        Ok(self.device.loader().bind_buffer_memory(buffer, self.memory, self.offset + offset)?)
+    }
+
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    //Whether a resource with the requirements can be placed at the start of the block
+    #[inline]
+    pub fn is_compatible(&self, memory_requirements: &vk::MemoryRequirements) -> bool {
+        memory_requirements.size <= self.size && self.alignment % memory_requirements.alignment == 0 && memory_requirements.memory_type_bits & (1 << self.memory_type_index) != 0
+    }
+}
+
+unsafe impl Send for MemoryBlock {}
+unsafe impl Sync for MemoryBlock {}
+
+impl Drop for MemoryBlock {
+    #[inline]
+    fn drop(&mut self) {
+        self.device.defer_destruction(DeferredDestruction::Allocation(self.allocation));
+    }
+}
@@ -0,0 +1,41 @@
+use vk_mem_alloc::{AllocationCreateFlags, AllocationCreateInfo, MemoryUsage};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryLocation {
+    GpuOnly,
+    CpuToGpu,
+    GpuToCpu
+}
+
+impl MemoryLocation {
+    #[inline]
+    pub(crate) fn allocation_create_info(self) -> AllocationCreateInfo {
+        match self {
+            Self::GpuOnly => {
+                AllocationCreateInfo {
+                    usage: MemoryUsage::AUTO_PREFER_DEVICE,
+                    ..Default::default()
+                }
+            }
+            Self::CpuToGpu => {
+                AllocationCreateInfo {
+                    flags: AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE | AllocationCreateFlags::MAPPED,
+                    usage: MemoryUsage::AUTO,
+                    ..Default::default()
+                }
+            }
+            Self::GpuToCpu => {
+                AllocationCreateInfo {
+                    flags: AllocationCreateFlags::HOST_ACCESS_RANDOM | AllocationCreateFlags::MAPPED,
+                    usage: MemoryUsage::AUTO,
+                    ..Default::default()
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub fn host_visible(self) -> bool {
+        self != Self::GpuOnly
+    }
+}
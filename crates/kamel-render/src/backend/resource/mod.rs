@@ -0,0 +1,13 @@
+mod buffer;
+mod image;
+mod image_view;
+mod memory_block;
+mod memory_location;
+mod sampler;
+
+pub use buffer::*;
+pub use image::*;
+pub use image_view::*;
+pub use memory_block::*;
+pub use memory_location::*;
+pub use sampler::*;
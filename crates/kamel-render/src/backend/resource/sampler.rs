@@ -0,0 +1,83 @@
+use std::ops::Deref;
+
+use anyhow::Result;
+use ash::vk;
+
+use crate::backend::{deletion_queue::DeferredDestruction, util::debug_utils, Device};
+
+#[derive(Copy, Clone, Debug)]
+pub struct SamplerDesc<'a> {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode_u: vk::SamplerAddressMode,
+    pub address_mode_v: vk::SamplerAddressMode,
+    pub address_mode_w: vk::SamplerAddressMode,
+    pub mip_lod_bias: f32,
+    pub max_anisotropy: Option<f32>,
+    pub compare_op: Option<vk::CompareOp>,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub border_color: vk::BorderColor,
+    pub label: Option<&'a str>
+}
+
+pub struct Sampler {
+    sampler: vk::Sampler,
+    device: Device
+}
+
+impl Sampler {
+    pub fn new(device: Device, desc: &SamplerDesc) -> Result<Self> {
+        if let Some(max_anisotropy) = desc.max_anisotropy {
+            if device.enabled_features().features.sampler_anisotropy == vk::FALSE {
+                anyhow::bail!("Sampler anisotropy was requested but the samplerAnisotropy feature is not enabled");
+            }
+
+            let limit = device.properties().properties.limits.max_sampler_anisotropy;
+            if max_anisotropy > limit {
+                anyhow::bail!("Sampler anisotropy of {} exceeds the device limit of {}", max_anisotropy, limit);
+            }
+        }
+
+        let sampler_create_info = vk::SamplerCreateInfo::default()
+            .mag_filter(desc.mag_filter)
+            .min_filter(desc.min_filter)
+            .mipmap_mode(desc.mipmap_mode)
+            .address_mode_u(desc.address_mode_u)
+            .address_mode_v(desc.address_mode_v)
+            .address_mode_w(desc.address_mode_w)
+            .mip_lod_bias(desc.mip_lod_bias)
+            .anisotropy_enable(desc.max_anisotropy.is_some())
+            .max_anisotropy(desc.max_anisotropy.unwrap_or(1.0))
+            .compare_enable(desc.compare_op.is_some())
+            .compare_op(desc.compare_op.unwrap_or(vk::CompareOp::NEVER))
+            .min_lod(desc.min_lod)
+            .max_lod(desc.max_lod)
+            .border_color(desc.border_color);
+
+        let sampler = unsafe { device.loader().create_sampler(&sampler_create_info, None) }?;
+
+        if let Some(label) = desc.label {
+            unsafe { debug_utils::set_object_name(&device, sampler, label) }?;
+        }
+
+        Ok(Self { sampler, device })
+    }
+}
+
+impl Deref for Sampler {
+    type Target = vk::Sampler;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.sampler
+    }
+}
+
+impl Drop for Sampler {
+    #[inline]
+    fn drop(&mut self) {
+        self.device.defer_destruction(DeferredDestruction::Sampler(self.sampler));
+    }
+}
@@ -0,0 +1,5 @@
+mod reflection;
+mod shader_module;
+
+pub use reflection::*;
+pub use shader_module::*;
@@ -0,0 +1,793 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use ash::vk;
+
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_EXECUTION_MODE: u32 = 16;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_CONSTANT_COMPOSITE: u32 = 44;
+const OP_SPEC_CONSTANT: u32 = 50;
+const OP_SPEC_CONSTANT_COMPOSITE: u32 = 51;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+const OP_EXECUTION_MODE_ID: u32 = 331;
+const OP_TYPE_ACCELERATION_STRUCTURE: u32 = 5341;
+
+const DECORATION_SPEC_ID: u32 = 1;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const BUILT_IN_WORKGROUP_SIZE: u32 = 25;
+
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_INPUT: u32 = 1;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+const STORAGE_CLASS_PHYSICAL_STORAGE_BUFFER: u32 = 5349;
+
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+const EXECUTION_MODE_LOCAL_SIZE_ID: u32 = 38;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+//Types nested deeper than this are treated as cyclic
+const MAX_TYPE_DEPTH: u32 = 64;
+
+#[derive(Clone, Debug)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    //0 for runtime sized arrays
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+    pub name: Option<String>
+}
+
+#[derive(Clone, Debug)]
+pub struct VertexInput {
+    pub location: u32,
+    pub format: vk::Format,
+    pub name: Option<String>
+}
+
+#[derive(Clone, Debug)]
+pub struct EntryPoint {
+    pub name: String,
+    pub stage: vk::ShaderStageFlags,
+    pub descriptor_bindings: Vec<DescriptorBinding>,
+    pub push_constant_range: Option<vk::PushConstantRange>,
+    pub vertex_inputs: Vec<VertexInput>,
+    //Default values if the workgroup size is given by specialization constants
+    pub workgroup_size: Option<[u32; 3]>,
+    //Specialization constant ids that override the workgroup size per dimension
+    pub workgroup_size_spec_ids: [Option<u32>; 3]
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ShaderReflection {
+    pub entry_points: Vec<EntryPoint>
+}
+
+impl ShaderReflection {
+    #[inline]
+    pub fn entry_point(&self, name: &str) -> Option<&EntryPoint> {
+        self.entry_points.iter().find(|entry_point| entry_point.name == name)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PipelineLayoutReflection {
+    //Indexed by set, sets without bindings are empty
+    pub sets: Vec<Vec<DescriptorBinding>>,
+    pub push_constant_ranges: Vec<vk::PushConstantRange>
+}
+
+//Merges the interfaces of all stages of a pipeline, bindings used by multiple stages have to agree on their type and count
+pub fn reflect_pipeline_layout(entry_points: &[&EntryPoint]) -> Result<PipelineLayoutReflection> {
+    let mut sets: BTreeMap<u32, BTreeMap<u32, DescriptorBinding>> = BTreeMap::new();
+    let mut push_constant_ranges: Vec<vk::PushConstantRange> = Vec::new();
+
+    for entry_point in entry_points {
+        for descriptor_binding in entry_point.descriptor_bindings.iter() {
+            let bindings = sets.entry(descriptor_binding.set).or_default();
+
+            match bindings.get_mut(&descriptor_binding.binding) {
+                Some(existing) => {
+                    if existing.descriptor_type != descriptor_binding.descriptor_type || existing.count != descriptor_binding.count {
+                        anyhow::bail!(
+                            "Descriptor binding (set {}, binding {}) is declared as {:?}[{}] and {:?}[{}]",
+                            descriptor_binding.set,
+                            descriptor_binding.binding,
+                            existing.descriptor_type,
+                            existing.count,
+                            descriptor_binding.descriptor_type,
+                            descriptor_binding.count
+                        );
+                    }
+
+                    existing.stages |= descriptor_binding.stages;
+                }
+                None => {
+                    bindings.insert(descriptor_binding.binding, descriptor_binding.clone());
+                }
+            }
+        }
+
+        if let Some(push_constant_range) = entry_point.push_constant_range {
+            match push_constant_ranges
+                .iter_mut()
+                .find(|range| range.offset == push_constant_range.offset && range.size == push_constant_range.size)
+            {
+                Some(range) => range.stage_flags |= push_constant_range.stage_flags,
+                None => push_constant_ranges.push(push_constant_range)
+            }
+        }
+    }
+
+    let set_count = sets.keys().next_back().map_or(0, |set| set + 1);
+    let mut result = PipelineLayoutReflection {
+        sets: vec![Vec::new(); set_count as usize],
+        push_constant_ranges
+    };
+
+    for (set, bindings) in sets {
+        result.sets[set as usize] = bindings.into_values().collect();
+    }
+
+    Ok(result)
+}
+
+#[derive(Clone, Debug)]
+enum Type {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component_type: u32, count: u32 },
+    Matrix { column_type: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element_type: u32, length: u32 },
+    RuntimeArray { element_type: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { storage_class: u32, pointee: u32 },
+    AccelerationStructure
+}
+
+#[derive(Default)]
+struct Decorations {
+    set: Option<u32>,
+    binding: Option<u32>,
+    location: Option<u32>,
+    array_stride: Option<u32>,
+    spec_id: Option<u32>,
+    built_in: Option<u32>,
+    buffer_block: bool
+}
+
+#[derive(Default)]
+struct MemberDecorations {
+    offset: Option<u32>,
+    matrix_stride: Option<u32>
+}
+
+struct RawEntryPoint {
+    execution_model: u32,
+    function: u32,
+    name: String,
+    interface: Vec<u32>
+}
+
+struct Variable {
+    pointer_type: u32,
+    storage_class: u32
+}
+
+#[derive(Default)]
+struct Module {
+    version: u32,
+    names: HashMap<u32, String>,
+    entry_points: Vec<RawEntryPoint>,
+    local_sizes: HashMap<u32, [u32; 3]>,
+    local_size_ids: HashMap<u32, [u32; 3]>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    composites: HashMap<u32, Vec<u32>>,
+    variables: Vec<(u32, Variable)>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), MemberDecorations>
+}
+
+fn parse_string(operands: &[u32]) -> Result<(String, usize)> {
+    let bytes: Vec<u8> = operands.iter().flat_map(|word| word.to_le_bytes()).collect();
+    let length = bytes.iter().position(|byte| *byte == 0).ok_or_else(|| anyhow::anyhow!("Unterminated string literal in SPIR-V"))?;
+
+    Ok((String::from_utf8(bytes[..length].to_vec())?, length / 4 + 1))
+}
+
+fn stage_from_execution_model(execution_model: u32) -> Result<vk::ShaderStageFlags> {
+    Ok(match execution_model {
+        0 => vk::ShaderStageFlags::VERTEX,
+        1 => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        2 => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        3 => vk::ShaderStageFlags::GEOMETRY,
+        4 => vk::ShaderStageFlags::FRAGMENT,
+        5 => vk::ShaderStageFlags::COMPUTE,
+        5313 => vk::ShaderStageFlags::RAYGEN_KHR,
+        5314 => vk::ShaderStageFlags::INTERSECTION_KHR,
+        5315 => vk::ShaderStageFlags::ANY_HIT_KHR,
+        5316 => vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+        5317 => vk::ShaderStageFlags::MISS_KHR,
+        5318 => vk::ShaderStageFlags::CALLABLE_KHR,
+        5364 => vk::ShaderStageFlags::TASK_EXT,
+        5365 => vk::ShaderStageFlags::MESH_EXT,
+        _ => anyhow::bail!("Unsupported SPIR-V execution model {}", execution_model)
+    })
+}
+
+impl Module {
+    fn parse(code: &[u32]) -> Result<Self> {
+        if code.len() < 5 {
+            anyhow::bail!("SPIR-V module is too short for its header");
+        }
+
+        let mut module = Self {
+            version: code[1],
+            ..Default::default()
+        };
+        let mut offset = 5;
+
+        while offset < code.len() {
+            let word_count = (code[offset] >> 16) as usize;
+            let opcode = code[offset] & 0xffff;
+
+            if word_count == 0 || offset + word_count > code.len() {
+                anyhow::bail!("Malformed SPIR-V instruction at word {}", offset);
+            }
+
+            let operands = &code[offset + 1..offset + word_count];
+            module.parse_instruction(opcode, operands)?;
+
+            offset += word_count;
+        }
+
+        Ok(module)
+    }
+
+    fn parse_instruction(&mut self, opcode: u32, operands: &[u32]) -> Result<()> {
+        let missing_operand = || anyhow::anyhow!("Missing operand for SPIR-V opcode {}", opcode);
+        let operand = |index: usize| operands.get(index).copied().ok_or_else(missing_operand);
+        let operands_from = |index: usize| operands.get(index..).ok_or_else(missing_operand);
+
+        match opcode {
+            OP_NAME => {
+                let (name, _) = parse_string(operands_from(1)?)?;
+                self.names.insert(operand(0)?, name);
+            }
+            OP_ENTRY_POINT => {
+                let (name, name_words) = parse_string(operands_from(2)?)?;
+
+                self.entry_points.push(RawEntryPoint {
+                    execution_model: operand(0)?,
+                    function: operand(1)?,
+                    name,
+                    interface: operands_from(2 + name_words)?.to_vec()
+                });
+            }
+            OP_EXECUTION_MODE | OP_EXECUTION_MODE_ID => {
+                match operand(1)? {
+                    EXECUTION_MODE_LOCAL_SIZE => {
+                        self.local_sizes.insert(operand(0)?, [operand(2)?, operand(3)?, operand(4)?]);
+                    }
+                    EXECUTION_MODE_LOCAL_SIZE_ID => {
+                        self.local_size_ids.insert(operand(0)?, [operand(2)?, operand(3)?, operand(4)?]);
+                    }
+                    _ => {}
+                }
+            }
+            OP_TYPE_BOOL => {
+                self.types.insert(operand(0)?, Type::Bool);
+            }
+            OP_TYPE_INT => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Int {
+                        width: operand(1)?,
+                        signed: operand(2)? != 0
+                    }
+                );
+            }
+            OP_TYPE_FLOAT => {
+                self.types.insert(operand(0)?, Type::Float { width: operand(1)? });
+            }
+            OP_TYPE_VECTOR => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Vector {
+                        component_type: operand(1)?,
+                        count: operand(2)?
+                    }
+                );
+            }
+            OP_TYPE_MATRIX => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Matrix {
+                        column_type: operand(1)?,
+                        count: operand(2)?
+                    }
+                );
+            }
+            OP_TYPE_IMAGE => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Image {
+                        dim: operand(2)?,
+                        sampled: operand(6)?
+                    }
+                );
+            }
+            OP_TYPE_SAMPLER => {
+                self.types.insert(operand(0)?, Type::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operand(0)?, Type::SampledImage);
+            }
+            OP_TYPE_ARRAY => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Array {
+                        element_type: operand(1)?,
+                        length: operand(2)?
+                    }
+                );
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                self.types.insert(operand(0)?, Type::RuntimeArray { element_type: operand(1)? });
+            }
+            OP_TYPE_STRUCT => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Struct {
+                        members: operands_from(1)?.to_vec()
+                    }
+                );
+            }
+            OP_TYPE_POINTER => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Pointer {
+                        storage_class: operand(1)?,
+                        pointee: operand(2)?
+                    }
+                );
+            }
+            OP_TYPE_ACCELERATION_STRUCTURE => {
+                self.types.insert(operand(0)?, Type::AccelerationStructure);
+            }
+            OP_CONSTANT | OP_SPEC_CONSTANT => {
+                //Only the low word is of interest, constants are used for array lengths and workgroup sizes
+                if let Some(value) = operands.get(2) {
+                    self.constants.insert(operand(1)?, *value);
+                }
+            }
+            OP_CONSTANT_COMPOSITE | OP_SPEC_CONSTANT_COMPOSITE => {
+                self.composites.insert(operand(1)?, operands_from(2)?.to_vec());
+            }
+            OP_VARIABLE => {
+                self.variables.push((
+                    operand(1)?,
+                    Variable {
+                        pointer_type: operand(0)?,
+                        storage_class: operand(2)?
+                    }
+                ));
+            }
+            OP_DECORATE => {
+                let decorations = self.decorations.entry(operand(0)?).or_default();
+
+                match operand(1)? {
+                    DECORATION_BUFFER_BLOCK => decorations.buffer_block = true,
+                    DECORATION_ARRAY_STRIDE => decorations.array_stride = Some(operand(2)?),
+                    DECORATION_SPEC_ID => decorations.spec_id = Some(operand(2)?),
+                    DECORATION_BUILT_IN => decorations.built_in = Some(operand(2)?),
+                    DECORATION_LOCATION => decorations.location = Some(operand(2)?),
+                    DECORATION_BINDING => decorations.binding = Some(operand(2)?),
+                    DECORATION_DESCRIPTOR_SET => decorations.set = Some(operand(2)?),
+                    _ => {}
+                }
+            }
+            OP_MEMBER_DECORATE => {
+                let member_decorations = self.member_decorations.entry((operand(0)?, operand(1)?)).or_default();
+
+                match operand(2)? {
+                    DECORATION_OFFSET => member_decorations.offset = Some(operand(3)?),
+                    DECORATION_MATRIX_STRIDE => member_decorations.matrix_stride = Some(operand(3)?),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn get_type(&self, id: u32) -> Result<&Type> {
+        self.types.get(&id).ok_or_else(|| anyhow::anyhow!("Unknown SPIR-V type %{}", id))
+    }
+
+    #[inline]
+    fn constant(&self, id: u32) -> Result<u32> {
+        self.constants.get(&id).copied().ok_or_else(|| anyhow::anyhow!("Unknown SPIR-V constant %{}", id))
+    }
+
+    fn type_size(&self, id: u32, matrix_stride: Option<u32>, depth: u32) -> Result<u32> {
+        if depth > MAX_TYPE_DEPTH {
+            anyhow::bail!("SPIR-V type %{} is nested deeper than {} levels", id, MAX_TYPE_DEPTH);
+        }
+
+        let overflow = || anyhow::anyhow!("Size of SPIR-V type %{} overflows", id);
+
+        Ok(match self.get_type(id)? {
+            Type::Bool => 4,
+            Type::Int { width, .. } | Type::Float { width } => width / 8,
+            Type::Vector { component_type, count } => self.type_size(*component_type, None, depth + 1)?.checked_mul(*count).ok_or_else(overflow)?,
+            Type::Matrix { column_type, count } => {
+                let column_size = match matrix_stride {
+                    Some(matrix_stride) => matrix_stride,
+                    None => self.type_size(*column_type, None, depth + 1)?
+                };
+
+                column_size.checked_mul(*count).ok_or_else(overflow)?
+            }
+            Type::Array { element_type, length } => {
+                let stride = match self.decorations.get(&id).and_then(|decorations| decorations.array_stride) {
+                    Some(stride) => stride,
+                    None => self.type_size(*element_type, None, depth + 1)?
+                };
+
+                stride.checked_mul(self.constant(*length)?).ok_or_else(overflow)?
+            }
+            Type::Struct { members } => {
+                let mut size = 0;
+
+                for (i, member) in members.iter().enumerate() {
+                    let member_decorations = self.member_decorations.get(&(id, i as u32));
+                    let offset = member_decorations.and_then(|decorations| decorations.offset).unwrap_or(size);
+                    let member_size = self.type_size(*member, member_decorations.and_then(|decorations| decorations.matrix_stride), depth + 1)?;
+
+                    size = size.max(offset.checked_add(member_size).ok_or_else(overflow)?);
+                }
+
+                size
+            }
+            Type::RuntimeArray { .. } => 0,
+            //Buffer references are 64 bit device addresses
+            Type::Pointer {
+                storage_class: STORAGE_CLASS_PHYSICAL_STORAGE_BUFFER,
+                ..
+            } => 8,
+            _ => anyhow::bail!("SPIR-V type %{} has no size", id)
+        })
+    }
+
+    fn descriptor_type(&self, type_id: u32, storage_class: u32) -> Result<(vk::DescriptorType, u32)> {
+        let (type_id, count) = match self.get_type(type_id)? {
+            Type::Array { element_type, length } => (*element_type, self.constant(*length)?),
+            Type::RuntimeArray { element_type } => (*element_type, 0),
+            _ => (type_id, 1)
+        };
+
+        let descriptor_type = match (storage_class, self.get_type(type_id)?) {
+            (STORAGE_CLASS_UNIFORM_CONSTANT, Type::Sampler) => vk::DescriptorType::SAMPLER,
+            (STORAGE_CLASS_UNIFORM_CONSTANT, Type::SampledImage) => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            (STORAGE_CLASS_UNIFORM_CONSTANT, Type::Image { dim, sampled }) => {
+                match (*dim, *sampled) {
+                    (DIM_BUFFER, 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+                    (DIM_BUFFER, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+                    (DIM_SUBPASS_DATA, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+                    (_, 2) => vk::DescriptorType::STORAGE_IMAGE,
+                    _ => vk::DescriptorType::SAMPLED_IMAGE
+                }
+            }
+            (STORAGE_CLASS_UNIFORM_CONSTANT, Type::AccelerationStructure) => vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+            (STORAGE_CLASS_UNIFORM, _) => {
+                if self.decorations.get(&type_id).map_or(false, |decorations| decorations.buffer_block) {
+                    vk::DescriptorType::STORAGE_BUFFER
+                } else {
+                    vk::DescriptorType::UNIFORM_BUFFER
+                }
+            }
+            (STORAGE_CLASS_STORAGE_BUFFER, _) => vk::DescriptorType::STORAGE_BUFFER,
+            (_, ty) => anyhow::bail!("Unsupported descriptor type {:?} in storage class {}", ty, storage_class)
+        };
+
+        Ok((descriptor_type, count))
+    }
+
+    fn vertex_format(&self, type_id: u32) -> Result<vk::Format> {
+        let (component_type, count) = match self.get_type(type_id)? {
+            Type::Vector { component_type, count } => (*component_type, *count),
+            _ => (type_id, 1)
+        };
+
+        Ok(match (self.get_type(component_type)?, count) {
+            (Type::Float { width: 32 }, 1) => vk::Format::R32_SFLOAT,
+            (Type::Float { width: 32 }, 2) => vk::Format::R32G32_SFLOAT,
+            (Type::Float { width: 32 }, 3) => vk::Format::R32G32B32_SFLOAT,
+            (Type::Float { width: 32 }, 4) => vk::Format::R32G32B32A32_SFLOAT,
+            (Type::Int { width: 32, signed: true }, 1) => vk::Format::R32_SINT,
+            (Type::Int { width: 32, signed: true }, 2) => vk::Format::R32G32_SINT,
+            (Type::Int { width: 32, signed: true }, 3) => vk::Format::R32G32B32_SINT,
+            (Type::Int { width: 32, signed: true }, 4) => vk::Format::R32G32B32A32_SINT,
+            (Type::Int { width: 32, signed: false }, 1) => vk::Format::R32_UINT,
+            (Type::Int { width: 32, signed: false }, 2) => vk::Format::R32G32_UINT,
+            (Type::Int { width: 32, signed: false }, 3) => vk::Format::R32G32B32_UINT,
+            (Type::Int { width: 32, signed: false }, 4) => vk::Format::R32G32B32A32_UINT,
+            (ty, count) => anyhow::bail!("Unsupported vertex input type {:?} with {} components", ty, count)
+        })
+    }
+
+    fn reflect_entry_point(&self, raw: &RawEntryPoint) -> Result<EntryPoint> {
+        let stage = stage_from_execution_model(raw.execution_model)?;
+
+        let mut descriptor_bindings = Vec::new();
+        let mut push_constant_range = None;
+        let mut vertex_inputs = Vec::new();
+
+        for (id, variable) in self.variables.iter() {
+            //Starting with SPIR-V 1.4 the interface of an entry point lists every global variable it statically uses
+            if self.version >= 0x0001_0400 && !raw.interface.contains(id) {
+                continue
+            }
+
+            let pointee = match self.get_type(variable.pointer_type)? {
+                Type::Pointer { pointee, .. } => *pointee,
+                _ => anyhow::bail!("SPIR-V variable %{} is not a pointer", id)
+            };
+            let decorations = self.decorations.get(id);
+
+            match variable.storage_class {
+                STORAGE_CLASS_UNIFORM_CONSTANT | STORAGE_CLASS_UNIFORM | STORAGE_CLASS_STORAGE_BUFFER => {
+                    let (set, binding) = match decorations.and_then(|decorations| decorations.set.zip(decorations.binding)) {
+                        Some(set_binding) => set_binding,
+                        None => continue
+                    };
+                    let (descriptor_type, count) = self.descriptor_type(pointee, variable.storage_class)?;
+
+                    descriptor_bindings.push(DescriptorBinding {
+                        set,
+                        binding,
+                        descriptor_type,
+                        count,
+                        stages: stage,
+                        name: self.names.get(id).cloned()
+                    });
+                }
+                STORAGE_CLASS_PUSH_CONSTANT => {
+                    let size = self.type_size(pointee, None, 0)?;
+
+                    push_constant_range = Some(vk::PushConstantRange::default().stage_flags(stage).offset(0).size(size));
+                }
+                STORAGE_CLASS_INPUT if stage == vk::ShaderStageFlags::VERTEX && raw.interface.contains(id) => {
+                    if decorations.map_or(false, |decorations| decorations.built_in.is_some()) {
+                        continue
+                    }
+
+                    let location = decorations
+                        .and_then(|decorations| decorations.location)
+                        .ok_or_else(|| anyhow::anyhow!("Vertex input %{} has no location", id))?;
+
+                    vertex_inputs.push(VertexInput {
+                        location,
+                        format: self.vertex_format(pointee)?,
+                        name: self.names.get(id).cloned()
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        descriptor_bindings.sort_by_key(|descriptor_binding| (descriptor_binding.set, descriptor_binding.binding));
+        vertex_inputs.sort_by_key(|vertex_input| vertex_input.location);
+
+        //A constant decorated as the WorkgroupSize built-in takes precedence over the execution modes
+        let workgroup_size_built_in = self
+            .decorations
+            .iter()
+            .find(|(_, decorations)| decorations.built_in == Some(BUILT_IN_WORKGROUP_SIZE))
+            .and_then(|(id, _)| self.composites.get(id));
+        let workgroup_size_ids = match (workgroup_size_built_in, self.local_size_ids.get(&raw.function)) {
+            (Some(constituents), _) => {
+                match constituents[..] {
+                    [x, y, z] => Some([x, y, z]),
+                    _ => anyhow::bail!("WorkgroupSize built-in has {} components instead of 3", constituents.len())
+                }
+            }
+            (None, Some(ids)) => Some(*ids),
+            (None, None) => None
+        };
+
+        let (workgroup_size, workgroup_size_spec_ids) = match workgroup_size_ids {
+            Some([x, y, z]) => {
+                let spec_id = |id: u32| self.decorations.get(&id).and_then(|decorations| decorations.spec_id);
+
+                (Some([self.constant(x)?, self.constant(y)?, self.constant(z)?]), [spec_id(x), spec_id(y), spec_id(z)])
+            }
+            None => (self.local_sizes.get(&raw.function).copied(), [None; 3])
+        };
+
+        Ok(EntryPoint {
+            name: raw.name.clone(),
+            stage,
+            descriptor_bindings,
+            push_constant_range,
+            vertex_inputs,
+            workgroup_size,
+            workgroup_size_spec_ids
+        })
+    }
+}
+
+pub(crate) fn reflect(code: &[u32]) -> Result<ShaderReflection> {
+    let module = Module::parse(code)?;
+
+    Ok(ShaderReflection {
+        entry_points: module.entry_points.iter().map(|raw| module.reflect_entry_point(raw)).collect::<Result<_>>()?
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: [u32; 5] = [0x0723_0203, 0x0001_0000, 0, 16, 0];
+
+    fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    fn string(value: &str) -> Vec<u32> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize(value.len() / 4 * 4 + 4, 0);
+        bytes.chunks(4).map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap())).collect()
+    }
+
+    fn module(instructions: &[Vec<u32>]) -> Vec<u32> {
+        HEADER.iter().copied().chain(instructions.iter().flatten().copied()).collect()
+    }
+
+    #[test]
+    fn compute_entry_point() {
+        let entry_point = [&[5, 1][..], &string("main")[..]].concat();
+        let code = module(&[
+            instruction(OP_ENTRY_POINT, &entry_point),
+            instruction(OP_EXECUTION_MODE, &[1, EXECUTION_MODE_LOCAL_SIZE, 8, 4, 1])
+        ]);
+
+        let reflection = reflect(&code).unwrap();
+        let entry_point = reflection.entry_point("main").unwrap();
+
+        assert_eq!(entry_point.stage, vk::ShaderStageFlags::COMPUTE);
+        assert_eq!(entry_point.workgroup_size, Some([8, 4, 1]));
+    }
+
+    #[test]
+    fn specialized_workgroup_size() {
+        let entry_point = [&[5, 1][..], &string("main")[..]].concat();
+        let code = module(&[
+            instruction(OP_ENTRY_POINT, &entry_point),
+            instruction(OP_EXECUTION_MODE_ID, &[1, EXECUTION_MODE_LOCAL_SIZE_ID, 3, 4, 4]),
+            instruction(OP_DECORATE, &[3, DECORATION_SPEC_ID, 7]),
+            instruction(OP_TYPE_INT, &[2, 32, 0]),
+            instruction(OP_SPEC_CONSTANT, &[2, 3, 64]),
+            instruction(OP_CONSTANT, &[2, 4, 1])
+        ]);
+
+        let reflection = reflect(&code).unwrap();
+        let entry_point = reflection.entry_point("main").unwrap();
+
+        assert_eq!(entry_point.workgroup_size, Some([64, 1, 1]));
+        assert_eq!(entry_point.workgroup_size_spec_ids, [Some(7), None, None]);
+    }
+
+    #[test]
+    fn name_without_string() {
+        assert!(reflect(&module(&[instruction(OP_NAME, &[1])])).is_err());
+    }
+
+    #[test]
+    fn entry_point_without_name() {
+        assert!(reflect(&module(&[instruction(OP_ENTRY_POINT, &[5])])).is_err());
+        assert!(reflect(&module(&[instruction(OP_ENTRY_POINT, &[5, 1])])).is_err());
+    }
+
+    #[test]
+    fn unterminated_string() {
+        let code = module(&[instruction(OP_NAME, &[1, u32::from_le_bytes(*b"main")])]);
+
+        assert!(reflect(&code).is_err());
+    }
+
+    #[test]
+    fn truncated_instruction() {
+        let mut code = module(&[instruction(OP_TYPE_INT, &[1, 32, 0])]);
+        code.pop();
+
+        assert!(reflect(&code).is_err());
+    }
+
+    fn push_constant_module(types: &[Vec<u32>]) -> Vec<u32> {
+        let entry_point = [&[5, 1][..], &string("main")[..]].concat();
+        let mut instructions = vec![instruction(OP_ENTRY_POINT, &entry_point), instruction(OP_TYPE_INT, &[2, 32, 0])];
+        instructions.extend_from_slice(types);
+        instructions.push(instruction(OP_TYPE_POINTER, &[6, STORAGE_CLASS_PUSH_CONSTANT, 5]));
+        instructions.push(instruction(OP_VARIABLE, &[6, 7, STORAGE_CLASS_PUSH_CONSTANT]));
+
+        module(&instructions)
+    }
+
+    #[test]
+    fn push_constant_buffer_reference() {
+        let code = push_constant_module(&[
+            instruction(OP_TYPE_POINTER, &[4, STORAGE_CLASS_PHYSICAL_STORAGE_BUFFER, 2]),
+            instruction(OP_TYPE_STRUCT, &[5, 2, 4]),
+            instruction(OP_MEMBER_DECORATE, &[5, 1, DECORATION_OFFSET, 8])
+        ]);
+
+        let reflection = reflect(&code).unwrap();
+        let push_constant_range = reflection.entry_point("main").unwrap().push_constant_range.unwrap();
+
+        assert_eq!(push_constant_range.size, 16);
+    }
+
+    #[test]
+    fn overflowing_array_length() {
+        let code = push_constant_module(&[
+            instruction(OP_CONSTANT, &[2, 3, 0x8000_0000]),
+            instruction(OP_TYPE_ARRAY, &[4, 2, 3]),
+            instruction(OP_TYPE_STRUCT, &[5, 4])
+        ]);
+
+        assert!(reflect(&code).is_err());
+    }
+
+    #[test]
+    fn cyclic_type() {
+        let code = push_constant_module(&[instruction(OP_TYPE_STRUCT, &[5, 2, 5])]);
+
+        assert!(reflect(&code).is_err());
+    }
+
+    #[test]
+    fn truncated_header() {
+        assert!(reflect(&HEADER[..3]).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use std::{fs, ops::Deref, path::Path};
+
+use anyhow::Result;
+use ash::vk;
+
+use crate::backend::{
+    shader::{reflection, ShaderReflection},
+    util::debug_utils,
+    Device
+};
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+#[derive(Copy, Clone, Debug)]
+pub struct ShaderModuleDesc<'a> {
+    pub label: Option<&'a str>
+}
+
+pub struct ShaderModule {
+    shader_module: vk::ShaderModule,
+    reflection: ShaderReflection,
+    device: Device
+}
+
+impl ShaderModule {
+    pub fn from_bytes(device: Device, bytes: &[u8], desc: &ShaderModuleDesc) -> Result<Self> {
+        if bytes.len() % 4 != 0 {
+            anyhow::bail!("SPIR-V byte code size of {} is not a multiple of 4", bytes.len());
+        }
+
+        let code: Vec<u32> = bytes.chunks_exact(4).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]])).collect();
+
+        Self::from_words(device, &code, desc)
+    }
+
+    pub fn from_words(device: Device, code: &[u32], desc: &ShaderModuleDesc) -> Result<Self> {
+        if code.len() < 5 {
+            anyhow::bail!("SPIR-V byte code is too short to contain a header");
+        }
+
+        if code[0] != SPIRV_MAGIC {
+            if code[0].swap_bytes() == SPIRV_MAGIC {
+                anyhow::bail!("Big endian SPIR-V byte code is not supported");
+            }
+
+            anyhow::bail!("Invalid SPIR-V magic number {:#010x}", code[0]);
+        }
+
+        let reflection = reflection::reflect(code)?;
+
+        let shader_module = unsafe { device.loader().create_shader_module(&vk::ShaderModuleCreateInfo::default().code(code), None) }?;
+
+        let shader_module = Self { shader_module, reflection, device };
+
+        if let Some(label) = desc.label {
+            unsafe { debug_utils::set_object_name(&shader_module.device, shader_module.shader_module, label) }?;
+        }
+
+        Ok(shader_module)
+    }
+
+    #[inline]
+    pub fn from_file(device: Device, path: impl AsRef<Path>, desc: &ShaderModuleDesc) -> Result<Self> {
+        Self::from_bytes(device, &fs::read(path)?, desc)
+    }
+
+    #[inline]
+    pub fn reflection(&self) -> &ShaderReflection {
+        &self.reflection
+    }
+}
+
+impl Deref for ShaderModule {
+    type Target = vk::ShaderModule;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.shader_module
+    }
+}
+
+impl Drop for ShaderModule {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            self.device.loader().destroy_shader_module(self.shader_module, None);
+        }
+    }
+}
@@ -17,6 +17,10 @@ pub struct Surface(Arc<Inner>);
 
 impl Surface {
     pub fn new(instance: Instance, window: &impl HasRawWindowHandle) -> Result<Self> {
+        if instance.headless() {
+            anyhow::bail!("Cannot create a surface for a headless instance");
+        }
+
         unsafe {
             let surface = ash_window::create_surface(instance.entry_loader(), instance.loader(), window, None)?;
             Ok(Self(Arc::new(Inner { surface, instance })))
@@ -32,8 +36,8 @@ impl Surface {
 impl Drop for Inner {
     #[inline]
     fn drop(&mut self) {
-        unsafe {
-            self.instance.surface_loader().destroy_surface(self.surface, None);
+        if let Some(surface_loader) = self.instance.surface_loader() {
+            unsafe { surface_loader.destroy_surface(self.surface, None) };
         }
     }
 }
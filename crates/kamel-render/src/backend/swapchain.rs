@@ -0,0 +1,318 @@
+use anyhow::Result;
+use ash::{prelude::VkResult, vk};
+use kamel_bevy::ecs::{self as bevy_ecs, system::Resource};
+
+use crate::backend::{sync::BinarySemaphore, util::debug_utils, Device, Queue, Surface};
+
+pub struct SwapchainSupport {
+    pub capabilities: vk::SurfaceCapabilitiesKHR,
+    pub formats: Vec<vk::SurfaceFormatKHR>,
+    pub present_modes: Vec<vk::PresentModeKHR>
+}
+
+impl SwapchainSupport {
+    pub unsafe fn new(device: &Device, surface: &Surface) -> VkResult<Self> {
+        let instance = device.instance();
+        let physical_device = *device.physical_device();
+
+        let surface_loader = instance.surface_loader().ok_or(vk::Result::ERROR_EXTENSION_NOT_PRESENT)?;
+
+        let (capabilities, formats) = if let Some(get_surface_capabilities2_loader) = instance.get_surface_capabilities2_loader() {
+            let surface_info = vk::PhysicalDeviceSurfaceInfo2KHR::default().surface(*surface.surface());
+
+            let mut capabilities = vk::SurfaceCapabilities2KHR::default();
+            get_surface_capabilities2_loader.get_physical_device_surface_capabilities2(physical_device, &surface_info, &mut capabilities)?;
+
+            let mut formats: Vec<_> = (0..get_surface_capabilities2_loader.get_physical_device_surface_formats2_len(physical_device, &surface_info)?)
+                .into_iter()
+                .map(|_| vk::SurfaceFormat2KHR::default())
+                .collect();
+            get_surface_capabilities2_loader.get_physical_device_surface_formats2(physical_device, &surface_info, &mut formats)?;
+
+            (capabilities.surface_capabilities, formats.iter().map(|format| format.surface_format).collect())
+        } else {
+            (
+                surface_loader.get_physical_device_surface_capabilities(physical_device, *surface.surface())?,
+                surface_loader.get_physical_device_surface_formats(physical_device, *surface.surface())?
+            )
+        };
+
+        let present_modes = surface_loader.get_physical_device_surface_present_modes(physical_device, *surface.surface())?;
+
+        Ok(Self {
+            capabilities,
+            formats,
+            present_modes
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SwapchainDesc<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub image_count: u32,
+    pub usage: vk::ImageUsageFlags,
+    //Ordered by preference, the first supported format of the surface is used as a fallback
+    pub formats: &'a [vk::SurfaceFormatKHR],
+    //Ordered by preference, FIFO is used as a fallback
+    pub present_modes: &'a [vk::PresentModeKHR],
+    pub label: Option<&'a str>
+}
+
+struct SwapchainConfig {
+    width: u32,
+    height: u32,
+    image_count: u32,
+    usage: vk::ImageUsageFlags,
+    formats: Vec<vk::SurfaceFormatKHR>,
+    present_modes: Vec<vk::PresentModeKHR>,
+    label: Option<String>
+}
+
+#[derive(Resource)]
+pub struct Swapchain {
+    swapchain: vk::SwapchainKHR,
+    images: Vec<vk::Image>,
+    image_views: Vec<vk::ImageView>,
+
+    format: vk::SurfaceFormatKHR,
+    present_mode: vk::PresentModeKHR,
+    extent: vk::Extent2D,
+
+    config: SwapchainConfig,
+    needs_recreation: bool,
+
+    surface: Surface,
+    device: Device
+}
+
+impl Swapchain {
+    pub fn new(device: Device, desc: &SwapchainDesc) -> Result<Self> {
+        let surface = device
+            .surface()
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Cannot create a swapchain for a device without a surface"))?;
+
+        let mut swapchain = Self {
+            swapchain: vk::SwapchainKHR::null(),
+            images: Vec::new(),
+            image_views: Vec::new(),
+
+            format: vk::SurfaceFormatKHR::default(),
+            present_mode: vk::PresentModeKHR::FIFO,
+            extent: vk::Extent2D::default(),
+
+            config: SwapchainConfig {
+                width: desc.width,
+                height: desc.height,
+                image_count: desc.image_count,
+                usage: desc.usage,
+                formats: desc.formats.to_vec(),
+                present_modes: desc.present_modes.to_vec(),
+                label: desc.label.map(str::to_owned)
+            },
+            needs_recreation: false,
+
+            surface,
+            device
+        };
+
+        if !unsafe { swapchain.recreate() }? {
+            anyhow::bail!("Cannot create a swapchain for a surface with a zero sized extent");
+        }
+
+        Ok(swapchain)
+    }
+
+    //Returns false if the surface currently has a zero sized extent, e.g. because the window is minimized
+    unsafe fn recreate(&mut self) -> Result<bool> {
+        let support = SwapchainSupport::new(&self.device, &self.surface)?;
+        let capabilities = &support.capabilities;
+
+        let extent = if capabilities.current_extent.width != u32::MAX {
+            capabilities.current_extent
+        } else {
+            vk::Extent2D {
+                width: self.config.width.clamp(capabilities.min_image_extent.width, capabilities.max_image_extent.width),
+                height: self.config.height.clamp(capabilities.min_image_extent.height, capabilities.max_image_extent.height)
+            }
+        };
+
+        if extent.width == 0 || extent.height == 0 {
+            return Ok(false)
+        }
+
+        let format = self
+            .config
+            .formats
+            .iter()
+            .find(|format| support.formats.contains(format))
+            .or_else(|| support.formats.first())
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Surface does not support any formats"))?;
+
+        let present_mode = self
+            .config
+            .present_modes
+            .iter()
+            .find(|present_mode| support.present_modes.contains(present_mode))
+            .copied()
+            .unwrap_or(vk::PresentModeKHR::FIFO);
+
+        let mut image_count = self.config.image_count.max(capabilities.min_image_count);
+        if capabilities.max_image_count > 0 {
+            image_count = image_count.min(capabilities.max_image_count);
+        }
+
+        let composite_alpha = if capabilities.supported_composite_alpha.contains(vk::CompositeAlphaFlagsKHR::OPAQUE) {
+            vk::CompositeAlphaFlagsKHR::OPAQUE
+        } else {
+            vk::CompositeAlphaFlagsKHR::from_raw(1 << capabilities.supported_composite_alpha.as_raw().trailing_zeros())
+        };
+
+        let device_loader = self.device.loader();
+        let swapchain_loader = self.device.swapchain_loader().ok_or_else(|| anyhow::anyhow!("VK_KHR_swapchain is not enabled"))?;
+
+        //The old swapchain and its image views may still be in use by previous frames
+        device_loader.device_wait_idle()?;
+
+        let swapchain_create_info = vk::SwapchainCreateInfoKHR::default()
+            .surface(*self.surface.surface())
+            .min_image_count(image_count)
+            .image_format(format.format)
+            .image_color_space(format.color_space)
+            .image_extent(extent)
+            .image_array_layers(1)
+            .image_usage(self.config.usage)
+            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .pre_transform(capabilities.current_transform)
+            .composite_alpha(composite_alpha)
+            .present_mode(present_mode)
+            .clipped(true)
+            .old_swapchain(self.swapchain);
+
+        let swapchain = swapchain_loader.create_swapchain(&swapchain_create_info, None)?;
+        self.destroy();
+        self.swapchain = swapchain;
+
+        self.images = swapchain_loader.get_swapchain_images(swapchain)?;
+        for image in self.images.iter() {
+            let image_view_create_info = vk::ImageViewCreateInfo::default()
+                .image(*image)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(format.format)
+                .subresource_range(vk::ImageSubresourceRange::default().aspect_mask(vk::ImageAspectFlags::COLOR).level_count(1).layer_count(1));
+
+            self.image_views.push(device_loader.create_image_view(&image_view_create_info, None)?);
+        }
+
+        if let Some(label) = &self.config.label {
+            debug_utils::set_object_name(&self.device, swapchain, label)?;
+        }
+
+        self.format = format;
+        self.present_mode = present_mode;
+        self.extent = extent;
+        self.needs_recreation = false;
+
+        Ok(true)
+    }
+
+    unsafe fn destroy(&mut self) {
+        let device_loader = self.device.loader();
+
+        for image_view in self.image_views.drain(..) {
+            device_loader.destroy_image_view(image_view, None);
+        }
+        self.images.clear();
+
+        if let Some(swapchain_loader) = self.device.swapchain_loader() {
+            if self.swapchain != vk::SwapchainKHR::null() {
+                swapchain_loader.destroy_swapchain(self.swapchain, None);
+                self.swapchain = vk::SwapchainKHR::null();
+            }
+        }
+    }
+
+    #[inline]
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if self.config.width != width || self.config.height != height {
+            self.config.width = width;
+            self.config.height = height;
+            self.needs_recreation = true;
+        }
+    }
+
+    //Returns None if the surface currently has a zero sized extent and nothing can be rendered
+    pub unsafe fn acquire_next_image(&mut self, semaphore: &BinarySemaphore, timeout: u64) -> Result<Option<u32>> {
+        let device = self.device.clone();
+        let swapchain_loader = device.swapchain_loader().ok_or_else(|| anyhow::anyhow!("VK_KHR_swapchain is not enabled"))?;
+
+        loop {
+            if self.needs_recreation && !self.recreate()? {
+                return Ok(None)
+            }
+
+            match swapchain_loader.acquire_next_image(self.swapchain, timeout, **semaphore, vk::Fence::null()) {
+                Ok((image_index, suboptimal)) => {
+                    //The semaphore is signaled in this case, recreate after the image has been presented
+                    self.needs_recreation |= suboptimal;
+                    return Ok(Some(image_index))
+                }
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.needs_recreation = true,
+                Err(result) => return Err(result.into())
+            }
+        }
+    }
+
+    pub unsafe fn present(&mut self, queue: &Queue, image_index: u32, wait_semaphores: &[&BinarySemaphore]) -> Result<()> {
+        match queue.present(self.swapchain, image_index, wait_semaphores) {
+            Ok(suboptimal) => self.needs_recreation |= suboptimal,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.needs_recreation = true,
+            Err(result) => return Err(result.into())
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn images(&self) -> &[vk::Image] {
+        &self.images
+    }
+
+    #[inline]
+    pub fn image_views(&self) -> &[vk::ImageView] {
+        &self.image_views
+    }
+
+    #[inline]
+    pub fn format(&self) -> vk::SurfaceFormatKHR {
+        self.format
+    }
+
+    #[inline]
+    pub fn present_mode(&self) -> vk::PresentModeKHR {
+        self.present_mode
+    }
+
+    #[inline]
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    #[inline]
+    pub fn needs_recreation(&self) -> bool {
+        self.needs_recreation
+    }
+}
+
+impl Drop for Swapchain {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.device.loader().device_wait_idle();
+            self.destroy();
+        }
+    }
+}
@@ -3,7 +3,7 @@ use std::ops::Deref;
 use anyhow::Result;
 use ash::vk;
 
-use crate::backend::{util::debug_utils, Device};
+use crate::backend::{deletion_queue::DeferredDestruction, util::debug_utils, Device};
 
 #[derive(Copy, Clone, Debug)]
 pub struct BinarySemaphoreDesc<'a> {
@@ -39,8 +39,6 @@ impl Deref for BinarySemaphore {
 impl Drop for BinarySemaphore {
     #[inline]
     fn drop(&mut self) {
-        unsafe {
-            self.device.loader().destroy_semaphore(self.semaphore, None);
-        }
+        self.device.defer_destruction(DeferredDestruction::Semaphore(self.semaphore));
     }
 }
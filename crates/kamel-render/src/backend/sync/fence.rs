@@ -3,7 +3,7 @@ use std::{ops::Deref, slice};
 use anyhow::Result;
 use ash::{prelude::VkResult, vk};
 
-use crate::backend::{util::debug_utils, Device};
+use crate::backend::{deletion_queue::DeferredDestruction, util::debug_utils, Device};
 
 #[derive(Copy, Clone, Debug)]
 pub struct FenceDesc<'a> {
@@ -55,8 +55,6 @@ impl Deref for Fence {
 impl Drop for Fence {
     #[inline]
     fn drop(&mut self) {
-        unsafe {
-            self.device.loader().destroy_fence(self.fence, None);
-        }
+        self.device.defer_destruction(DeferredDestruction::Fence(self.fence));
     }
 }
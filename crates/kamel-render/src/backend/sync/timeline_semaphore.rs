@@ -3,7 +3,7 @@ use std::{ops::Deref, slice};
 use anyhow::Result;
 use ash::{prelude::VkResult, vk};
 
-use crate::backend::{util::debug_utils, Device};
+use crate::backend::{deletion_queue::DeferredDestruction, util::debug_utils, Device};
 
 #[derive(Copy, Clone, Debug)]
 pub struct TimelineSemaphoreDesc<'a> {
@@ -66,8 +66,6 @@ impl Deref for TimelineSemaphore {
 impl Drop for TimelineSemaphore {
     #[inline]
     fn drop(&mut self) {
-        unsafe {
-            self.device.loader().destroy_semaphore(self.semaphore, None);
-        }
+        self.device.defer_destruction(DeferredDestruction::Semaphore(self.semaphore));
     }
 }
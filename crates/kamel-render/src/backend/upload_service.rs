@@ -0,0 +1,497 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    slice,
+    sync::Arc
+};
+
+use anyhow::Result;
+use ash::vk;
+use kamel_bevy::ecs::{self as bevy_ecs, system::Resource};
+
+use crate::backend::{
+    format_texel_block,
+    sync::{TimelineSemaphore, TimelineSemaphoreDesc},
+    Access, Buffer, BufferDesc, CommandBuffer, CommandBufferDesc, CommandPool, CommandPoolDesc, Device, Image, Initial, MemoryLocation, PendingCommandBuffers, QueueOwnershipTransfer,
+    QueueType, Recording, SemaphoreSubmit, SubmitDesc
+};
+
+//Staging offsets are aligned to this at least, image uploads additionally align to a multiple of their texel block size
+const MIN_STAGING_ALIGNMENT: u64 = 16;
+
+#[derive(Copy, Clone, Debug)]
+pub struct UploadServiceDesc<'a> {
+    //Size of the staging ring buffer, a single upload cannot be larger than this
+    pub staging_size: u64,
+    pub label: Option<&'a str>
+}
+
+//Staging memory of the uploads recorded between two flushes
+struct Batch {
+    start: u64,
+    //None while the batch is still being recorded
+    timeline_value: Option<u64>,
+    pending_command_buffers: Option<PendingCommandBuffers>
+}
+
+//Ownership acquire the destination queue has to record before using an uploaded resource
+struct PendingAcquire {
+    queue_type: QueueType,
+    //None until the batch of the upload is flushed
+    timeline_value: Option<u64>,
+    transfer: QueueOwnershipTransfer
+}
+
+//Uploads data through a CPU visible staging ring buffer on the transfer queue and hands the ownership of the destinations
+//to the queue that uses them. Destinations are kept alive by the command buffers that use them and have to stay unused until
+//their acquire is recorded.
+#[derive(Resource)]
+pub struct UploadService {
+    staging_buffer: Buffer,
+    staging_alignment: u64,
+    head: u64,
+    batches: VecDeque<Batch>,
+
+    command_pool: Arc<CommandPool>,
+    command_buffers: Vec<CommandBuffer<Initial>>,
+    recording_command_buffer: Option<CommandBuffer<Recording>>,
+    //Destinations of the recording command buffer, later copies to them have to wait for the earlier ones
+    written_buffers: HashSet<vk::Buffer>,
+    written_images: HashSet<vk::Image>,
+    pending_acquires: Vec<PendingAcquire>,
+
+    timeline_semaphore: TimelineSemaphore,
+    timeline_value: u64,
+
+    device: Device
+}
+
+#[inline]
+fn align_up(value: u64, alignment: u64) -> u64 {
+    (value + alignment - 1) / alignment * alignment
+}
+
+#[inline]
+fn lcm(a: u64, b: u64) -> u64 {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+
+    a / x * b
+}
+
+impl UploadService {
+    pub fn new(device: Device, desc: &UploadServiceDesc) -> Result<Self> {
+        let staging_buffer = Buffer::new(
+            device.clone(),
+            &BufferDesc {
+                size: desc.staging_size,
+                usage: vk::BufferUsageFlags::TRANSFER_SRC,
+                memory_location: MemoryLocation::CpuToGpu,
+                label: desc.label.map(|label| format!("{} staging buffer", label)).as_deref()
+            }
+        )?;
+
+        let command_pool = CommandPool::new(
+            device.clone(),
+            &CommandPoolDesc {
+                flags: vk::CommandPoolCreateFlags::TRANSIENT | vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+                family_index: device.transfer_queue().family_index(),
+                label: desc.label.map(|label| format!("{} command pool", label)).as_deref()
+            }
+        )?;
+
+        let timeline_semaphore = TimelineSemaphore::new(
+            device.clone(),
+            &TimelineSemaphoreDesc {
+                initial_value: 0,
+                label: desc.label.map(|label| format!("{} timeline semaphore", label)).as_deref()
+            }
+        )?;
+
+        let staging_alignment = MIN_STAGING_ALIGNMENT.max(device.properties().properties.limits.optimal_buffer_copy_offset_alignment);
+
+        Ok(Self {
+            staging_buffer,
+            staging_alignment,
+            head: 0,
+            batches: VecDeque::new(),
+
+            command_pool: Arc::new(command_pool),
+            command_buffers: Vec::new(),
+            recording_command_buffer: None,
+            written_buffers: HashSet::new(),
+            written_images: HashSet::new(),
+            pending_acquires: Vec::new(),
+
+            timeline_semaphore,
+            timeline_value: 0,
+
+            device
+        })
+    }
+
+    //Frees the staging memory and command buffers of completed batches, waits for the oldest batch if requested
+    fn reclaim(&mut self, wait_for_oldest: bool) -> Result<()> {
+        if wait_for_oldest {
+            if let Some(timeline_value) = self.batches.front().and_then(|batch| batch.timeline_value) {
+                unsafe { self.timeline_semaphore.wait_for_value(timeline_value, u64::MAX) }?;
+            }
+        }
+
+        let completed_value = unsafe { self.timeline_semaphore.value() }?;
+
+        while let Some(batch) = self.batches.front() {
+            if !batch.timeline_value.map_or(false, |timeline_value| timeline_value <= completed_value) {
+                break;
+            }
+
+            let batch = self.batches.pop_front().unwrap();
+            if let Some(pending_command_buffers) = batch.pending_command_buffers {
+                match pending_command_buffers.try_complete() {
+                    Ok(completed_command_buffers) => {
+                        for completed_command_buffer in completed_command_buffers {
+                            self.command_buffers.push(completed_command_buffer.reset(vk::CommandBufferResetFlags::empty())?);
+                        }
+                    }
+                    Err((_, result)) => anyhow::bail!("Upload command buffers could not be recycled: {}", result)
+                }
+            }
+        }
+
+        if self.batches.is_empty() {
+            self.head = 0;
+        }
+
+        Ok(())
+    }
+
+    fn try_allocate(&self, size: u64, alignment: u64) -> Option<u64> {
+        let capacity = self.staging_buffer.size();
+
+        let tail = match self.batches.front() {
+            Some(batch) => batch.start,
+            None => return if size <= capacity { Some(0) } else { None }
+        };
+
+        let offset = align_up(self.head, alignment);
+
+        if self.head > tail {
+            if offset + size <= capacity {
+                Some(offset)
+            } else if size <= tail {
+                //Wrap around, the end of the buffer is wasted until the batch completes
+                Some(0)
+            } else {
+                None
+            }
+        } else if self.head < tail && offset + size <= tail {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+
+    //Flushes and waits for older uploads if the ring buffer is full
+    fn allocate(&mut self, size: u64, alignment: u64) -> Result<u64> {
+        if size > self.staging_buffer.size() {
+            anyhow::bail!("Upload of {} bytes does not fit into a staging buffer of {} bytes", size, self.staging_buffer.size());
+        }
+
+        self.reclaim(false)?;
+
+        let offset = loop {
+            if let Some(offset) = self.try_allocate(size, alignment) {
+                break offset;
+            }
+
+            if self.batches.front().map_or(false, |batch| batch.timeline_value.is_none()) {
+                self.flush()?;
+            }
+            self.reclaim(true)?;
+        };
+
+        if !self.batches.back().map_or(false, |batch| batch.timeline_value.is_none()) {
+            self.batches.push_back(Batch {
+                start: offset,
+                timeline_value: None,
+                pending_command_buffers: None
+            });
+        }
+        self.head = offset + size;
+
+        Ok(offset)
+    }
+
+    fn begin_recording(&mut self) -> Result<()> {
+        if self.recording_command_buffer.is_none() {
+            let command_buffer = match self.command_buffers.pop() {
+                Some(command_buffer) => command_buffer,
+                None => CommandBuffer::new(self.device.clone(), self.command_pool.clone(), &CommandBufferDesc { label: None })?
+            };
+
+            self.recording_command_buffer = Some(command_buffer.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?);
+        }
+
+        Ok(())
+    }
+
+    //Copies the data into the staging buffer and begins recording, the staging memory is given back if that fails
+    fn stage(&mut self, data: &[u8], alignment: u64) -> Result<u64> {
+        let offset = self.allocate(data.len() as u64, alignment)?;
+
+        if let Err(error) = unsafe { self.staging_buffer.write(offset, data) }.and_then(|_| self.begin_recording()) {
+            self.head = offset;
+
+            //Only the batch opened by this upload can be without commands
+            if self.recording_command_buffer.is_none() && self.batches.back().map_or(false, |batch| batch.timeline_value.is_none()) {
+                self.batches.pop_back();
+            }
+            if self.batches.is_empty() {
+                self.head = 0;
+            }
+
+            return Err(error);
+        }
+
+        Ok(offset)
+    }
+
+    //The whole buffer is handed over to the destination queue which uses it with dst_access
+    pub fn upload_buffer(&mut self, buffer: &Arc<Buffer>, offset: u64, data: &[u8], dst_queue: QueueType, dst_access: Access) -> Result<()> {
+        if data.is_empty() {
+            anyhow::bail!("Buffer upload without data");
+        }
+
+        if !offset.checked_add(data.len() as u64).map_or(false, |end| end <= buffer.size()) {
+            anyhow::bail!("Upload of {} bytes at offset {} exceeds the buffer size of {} bytes", data.len(), offset, buffer.size());
+        }
+
+        let transfer = QueueOwnershipTransfer::buffer(&self.device, buffer, QueueType::Transfer, Access::TransferWrite, dst_queue, dst_access);
+
+        let staging_offset = self.stage(data, self.staging_alignment)?;
+
+        let command_buffer = self.recording_command_buffer.as_mut().unwrap();
+        if !self.written_buffers.insert(***buffer) {
+            let barrier = vk::BufferMemoryBarrier2::default()
+                .src_stage_mask(vk::PipelineStageFlags2::ALL_TRANSFER)
+                .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags2::ALL_TRANSFER)
+                .dst_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .buffer(***buffer)
+                .offset(0)
+                .size(vk::WHOLE_SIZE);
+
+            unsafe { command_buffer.pipeline_barrier2(&vk::DependencyInfo::default().buffer_memory_barriers(slice::from_ref(&barrier))) };
+        }
+        command_buffer.copy_buffer(
+            &self.staging_buffer,
+            buffer,
+            slice::from_ref(&vk::BufferCopy::default().src_offset(staging_offset).dst_offset(offset).size(data.len() as u64))
+        );
+        command_buffer.retain(buffer.clone());
+        transfer.record_release(command_buffer);
+        //Until the acquire is recorded
+        buffer.assume_access(Access::TransferWrite);
+
+        self.pending_acquires.push(PendingAcquire {
+            queue_type: dst_queue,
+            timeline_value: None,
+            transfer
+        });
+
+        Ok(())
+    }
+
+    //Overwrites a whole mip level of the subresource layers, the previous content is discarded.
+    //The image ends up in the layout of dst_access and is owned by the destination queue.
+    pub fn upload_image(&mut self, image: &Arc<Image>, subresource: vk::ImageSubresourceLayers, data: &[u8], dst_queue: QueueType, dst_access: Access) -> Result<()> {
+        if data.is_empty() {
+            anyhow::bail!("Image upload without data");
+        }
+
+        if subresource.mip_level >= image.mip_levels() {
+            anyhow::bail!("Mip level {} is out of range, the image has {} mip levels", subresource.mip_level, image.mip_levels());
+        }
+
+        if subresource.layer_count == 0 || !subresource.base_array_layer.checked_add(subresource.layer_count).map_or(false, |end| end <= image.array_layers()) {
+            anyhow::bail!(
+                "Array layers {}..{} are out of range, the image has {} array layers",
+                subresource.base_array_layer,
+                subresource.base_array_layer.saturating_add(subresource.layer_count),
+                image.array_layers()
+            );
+        }
+
+        //Copies can only address a single aspect
+        if subresource.aspect_mask.as_raw().count_ones() != 1 || !image.aspect_mask().contains(subresource.aspect_mask) {
+            anyhow::bail!("Aspect {:?} cannot be uploaded to an image with the aspects {:?}", subresource.aspect_mask, image.aspect_mask());
+        }
+
+        let (block_size, block_width, block_height) = match format_texel_block(image.format(), subresource.aspect_mask) {
+            Some(block) => block,
+            None => anyhow::bail!("Uploads to images of format {:?} are not supported", image.format())
+        };
+
+        let extent = image.extent();
+        let mip_extent = vk::Extent3D {
+            width: (extent.width >> subresource.mip_level).max(1),
+            height: (extent.height >> subresource.mip_level).max(1),
+            depth: (extent.depth >> subresource.mip_level).max(1)
+        };
+
+        let size = u64::from(mip_extent.width.div_ceil(block_width))
+            * u64::from(mip_extent.height.div_ceil(block_height))
+            * u64::from(mip_extent.depth)
+            * u64::from(subresource.layer_count)
+            * u64::from(block_size);
+        if data.len() as u64 != size {
+            anyhow::bail!("Image upload needs {} bytes of data but got {} bytes", size, data.len());
+        }
+
+        let subresource_range = vk::ImageSubresourceRange::default()
+            .aspect_mask(subresource.aspect_mask)
+            .base_mip_level(subresource.mip_level)
+            .level_count(1)
+            .base_array_layer(subresource.base_array_layer)
+            .layer_count(subresource.layer_count);
+
+        let transfer = QueueOwnershipTransfer::image(&self.device, image, subresource_range, QueueType::Transfer, Access::TransferWrite, dst_queue, dst_access)?;
+
+        //The buffer offset of a copy has to be a multiple of the texel block size and of 4
+        let alignment = lcm(self.staging_alignment, lcm(u64::from(block_size), 4));
+        let staging_offset = self.stage(data, alignment)?;
+
+        //The previous content is discarded, earlier copies of the batch to the image only have to finish first
+        let (src_stage_mask, src_access_mask) = if self.written_images.insert(***image) {
+            (vk::PipelineStageFlags2::NONE, vk::AccessFlags2::NONE)
+        } else {
+            (vk::PipelineStageFlags2::ALL_TRANSFER, vk::AccessFlags2::TRANSFER_WRITE)
+        };
+
+        let transfer_barrier = vk::ImageMemoryBarrier2::default()
+            .src_stage_mask(src_stage_mask)
+            .src_access_mask(src_access_mask)
+            .dst_stage_mask(vk::PipelineStageFlags2::ALL_TRANSFER)
+            .dst_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(***image)
+            .subresource_range(subresource_range);
+
+        let region = vk::BufferImageCopy::default()
+            .buffer_offset(staging_offset)
+            .image_subresource(subresource)
+            .image_extent(mip_extent);
+
+        let command_buffer = self.recording_command_buffer.as_mut().unwrap();
+        unsafe { command_buffer.pipeline_barrier2(&vk::DependencyInfo::default().image_memory_barriers(slice::from_ref(&transfer_barrier))) };
+        command_buffer.copy_buffer_to_image(&self.staging_buffer, image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, slice::from_ref(&region));
+        command_buffer.retain(image.clone());
+        transfer.record_release(command_buffer);
+        //Until the acquire is recorded
+        image.assume_access(subresource_range, Access::TransferWrite)?;
+
+        self.pending_acquires.push(PendingAcquire {
+            queue_type: dst_queue,
+            timeline_value: None,
+            transfer
+        });
+
+        Ok(())
+    }
+
+    //The uploads of a batch that could not be submitted are lost, its staging memory only has to wait for the earlier submissions
+    fn abandon_batch(&mut self) {
+        if let Some(batch) = self.batches.back_mut().filter(|batch| batch.timeline_value.is_none()) {
+            batch.timeline_value = Some(self.timeline_value);
+        }
+
+        self.pending_acquires.retain(|pending_acquire| pending_acquire.timeline_value.is_some());
+    }
+
+    //Submits the recorded uploads, returns the timeline value that signals their completion.
+    //Returns the last value if nothing was recorded.
+    pub fn flush(&mut self) -> Result<u64> {
+        let command_buffer = match self.recording_command_buffer.take() {
+            Some(command_buffer) => command_buffer,
+            None => return Ok(self.timeline_value)
+        };
+        self.written_buffers.clear();
+        self.written_images.clear();
+
+        let timeline_value = self.timeline_value + 1;
+
+        let submitted = command_buffer.end().and_then(|command_buffer| {
+            unsafe {
+                self.device.transfer_queue().submit(
+                    vec![command_buffer],
+                    &SubmitDesc {
+                        signal_semaphores: &[SemaphoreSubmit::Timeline {
+                            semaphore: &self.timeline_semaphore,
+                            value: timeline_value,
+                            stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS
+                        }],
+                        ..Default::default()
+                    }
+                )
+            }
+        });
+        let pending_command_buffers = match submitted {
+            Ok(pending_command_buffers) => pending_command_buffers,
+            Err(result) => {
+                self.abandon_batch();
+                return Err(result.into());
+            }
+        };
+
+        self.timeline_value = timeline_value;
+
+        if let Some(batch) = self.batches.back_mut().filter(|batch| batch.timeline_value.is_none()) {
+            batch.timeline_value = Some(timeline_value);
+            batch.pending_command_buffers = Some(pending_command_buffers);
+        }
+
+        for pending_acquire in self.pending_acquires.iter_mut().filter(|pending_acquire| pending_acquire.timeline_value.is_none()) {
+            pending_acquire.timeline_value = Some(timeline_value);
+        }
+
+        Ok(timeline_value)
+    }
+
+    //Records the ownership acquires of every flushed upload for the queue. Returns the timeline value the submission
+    //of the command buffer has to wait on, or None if there was nothing to acquire.
+    pub fn record_acquires(&mut self, command_buffer: &mut CommandBuffer<Recording>, queue_type: QueueType) -> Option<u64> {
+        let (acquires, pending_acquires): (Vec<_>, Vec<_>) = self
+            .pending_acquires
+            .drain(..)
+            .partition(|pending_acquire| pending_acquire.queue_type == queue_type && pending_acquire.timeline_value.is_some());
+        self.pending_acquires = pending_acquires;
+
+        QueueOwnershipTransfer::record_acquires(command_buffer, acquires.iter().map(|pending_acquire| &pending_acquire.transfer));
+
+        acquires.iter().filter_map(|pending_acquire| pending_acquire.timeline_value).max()
+    }
+
+    //Signaled by the transfer queue, consumers wait on the value returned by flush
+    #[inline]
+    pub fn timeline_semaphore(&self) -> &TimelineSemaphore {
+        &self.timeline_semaphore
+    }
+
+    #[inline]
+    pub fn last_flushed_value(&self) -> u64 {
+        self.timeline_value
+    }
+}
+
+impl Drop for UploadService {
+    fn drop(&mut self) {
+        //The staging buffer has to outlive the copies of every flushed batch
+        let _ = unsafe { self.timeline_semaphore.wait_for_value(self.timeline_value, u64::MAX) };
+    }
+}
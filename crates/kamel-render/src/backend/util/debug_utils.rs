@@ -6,7 +6,7 @@ use ash::{vk, vk::Handle};
 use crate::backend::Device;
 
 pub unsafe fn set_object_name<H: Handle>(device: &Device, handle: H, name: &str) -> Result<()> {
-    if device.instance().extensions().ext_debug_utils() {
+    if let Some(debug_utils_loader) = device.instance().debug_utils_loader() {
         let object_name = CString::new(name)?;
 
         let debug_utils_object_name_info = vk::DebugUtilsObjectNameInfoEXT::default()
@@ -14,10 +14,7 @@ pub unsafe fn set_object_name<H: Handle>(device: &Device, handle: H, name: &str)
             .object_handle(handle.as_raw())
             .object_name(&object_name);
 
-        device
-            .instance()
-            .debug_utils_loader()
-            .debug_utils_set_object_name(device.loader().handle(), &debug_utils_object_name_info)?;
+        debug_utils_loader.debug_utils_set_object_name(device.loader().handle(), &debug_utils_object_name_info)?;
     }
 
     Ok(())
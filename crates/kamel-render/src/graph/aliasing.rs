@@ -0,0 +1,317 @@
+use std::{cmp::Reverse, sync::Arc};
+
+use anyhow::Result;
+use ash::vk;
+
+use crate::{
+    backend::{Device, MemoryBlock},
+    graph::ResourceHandle
+};
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct TransientResource {
+    pub(crate) handle: ResourceHandle,
+    pub(crate) memory_requirements: vk::MemoryRequirements,
+    //Positions of the first and last pass using the resource in execution order
+    pub(crate) first_pass: usize,
+    pub(crate) last_pass: usize
+}
+
+impl TransientResource {
+    #[inline]
+    fn is_image(&self) -> bool {
+        matches!(self.handle, ResourceHandle::Image(_))
+    }
+
+    #[inline]
+    fn overlaps_lifetime(&self, other: &TransientResource) -> bool {
+        self.first_pass <= other.last_pass && other.first_pass <= self.last_pass
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct Placement {
+    pub(crate) block: usize,
+    pub(crate) offset: u64
+}
+
+//Images and buffers never share a block so the buffer image granularity does not have to be respected
+pub(crate) struct BlockLayout {
+    pub(crate) memory_requirements: vk::MemoryRequirements,
+    is_image: bool,
+    resources: Vec<usize>
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TransientMemoryReport {
+    pub resource_count: usize,
+    pub block_count: usize,
+    //Size if every transient resource had its own allocation
+    pub naive_size: u64,
+    pub aliased_size: u64
+}
+
+impl TransientMemoryReport {
+    #[inline]
+    pub fn saved_size(&self) -> u64 {
+        self.naive_size.saturating_sub(self.aliased_size)
+    }
+}
+
+//None if the aligned value does not fit into a u64
+#[inline]
+fn align_up(value: u64, alignment: u64) -> Option<u64> {
+    let alignment = alignment.max(1);
+
+    Some(value.checked_add(alignment - 1)? / alignment * alignment)
+}
+
+#[inline]
+fn end(placement: Placement, resource: &TransientResource) -> u64 {
+    placement.offset.saturating_add(resource.memory_requirements.size)
+}
+
+fn find_offset(block: &BlockLayout, resources: &[TransientResource], placements: &[Placement], resource: &TransientResource) -> Option<u64> {
+    let mut occupied: Vec<_> = block
+        .resources
+        .iter()
+        .filter(|&&index| resources[index].overlaps_lifetime(resource))
+        .map(|&index| (placements[index].offset, end(placements[index], &resources[index])))
+        .collect();
+    occupied.sort_unstable();
+
+    let size = resource.memory_requirements.size;
+    let alignment = resource.memory_requirements.alignment;
+
+    let mut offset = 0;
+    for (start, end) in occupied {
+        if align_up(offset, alignment)?.checked_add(size)? <= start {
+            break;
+        }
+
+        offset = offset.max(end);
+    }
+
+    let offset = align_up(offset, alignment)?;
+    if offset.checked_add(size)? <= block.memory_requirements.size {
+        Some(offset)
+    } else {
+        None
+    }
+}
+
+//Greedy first fit, largest resources first, every block is as large as the first resource placed into it
+pub(crate) fn place_resources(resources: &[TransientResource]) -> (Vec<BlockLayout>, Vec<Placement>) {
+    let mut blocks: Vec<BlockLayout> = Vec::new();
+    let mut placements = vec![Placement::default(); resources.len()];
+
+    let mut sorted: Vec<_> = (0..resources.len()).collect();
+    sorted.sort_by_key(|&index| Reverse(resources[index].memory_requirements.size));
+
+    for index in sorted {
+        let resource = &resources[index];
+
+        let placement = blocks.iter().enumerate().find_map(|(block_index, block)| {
+            if block.is_image != resource.is_image() || block.memory_requirements.memory_type_bits & resource.memory_requirements.memory_type_bits == 0 {
+                return None;
+            }
+
+            find_offset(block, resources, &placements, resource).map(|offset| Placement { block: block_index, offset })
+        });
+
+        match placement {
+            Some(placement) => {
+                let block = &mut blocks[placement.block];
+                block.memory_requirements.memory_type_bits &= resource.memory_requirements.memory_type_bits;
+                block.memory_requirements.alignment = block.memory_requirements.alignment.max(resource.memory_requirements.alignment);
+                block.resources.push(index);
+
+                placements[index] = placement;
+            }
+            None => {
+                blocks.push(BlockLayout {
+                    memory_requirements: resource.memory_requirements,
+                    is_image: resource.is_image(),
+                    resources: vec![index]
+                });
+
+                placements[index] = Placement { block: blocks.len() - 1, offset: 0 };
+            }
+        }
+    }
+
+    (blocks, placements)
+}
+
+//Resources that occupied the same memory before the resource, its first access has to wait for their last accesses
+pub(crate) fn aliased_predecessors(blocks: &[BlockLayout], resources: &[TransientResource], placements: &[Placement], index: usize) -> Vec<usize> {
+    let resource = &resources[index];
+    let placement = placements[index];
+    let resource_end = end(placement, resource);
+
+    blocks[placement.block]
+        .resources
+        .iter()
+        .copied()
+        .filter(|&other| {
+            let other_start = placements[other].offset;
+            let other_end = end(placements[other], &resources[other]);
+
+            other != index && resources[other].last_pass < resource.first_pass && other_start < resource_end && placement.offset < other_end
+        })
+        .collect()
+}
+
+pub(crate) fn memory_report(blocks: &[BlockLayout], resources: &[TransientResource]) -> TransientMemoryReport {
+    TransientMemoryReport {
+        resource_count: resources.len(),
+        block_count: blocks.len(),
+        naive_size: resources.iter().fold(0, |size, resource| size.saturating_add(resource.memory_requirements.size)),
+        aliased_size: blocks.iter().fold(0, |size, block| size.saturating_add(block.memory_requirements.size))
+    }
+}
+
+//Memory blocks of previous executions, a block is reused once no resource of a previous execution references it anymore
+#[derive(Default)]
+pub struct TransientMemoryCache {
+    blocks: Vec<Arc<MemoryBlock>>
+}
+
+impl TransientMemoryCache {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //Idle blocks that are not reused are freed, the cache only keeps the blocks of executions that are still referenced
+    pub(crate) fn acquire(&mut self, device: &Device, layouts: &[BlockLayout]) -> Result<Vec<Arc<MemoryBlock>>> {
+        let (mut idle, in_use): (Vec<_>, Vec<_>) = self.blocks.drain(..).partition(|block| Arc::strong_count(block) == 1);
+        self.blocks = in_use;
+
+        //Smallest fitting block first
+        idle.sort_by_key(|block| block.size());
+
+        let mut acquired = Vec::with_capacity(layouts.len());
+        for layout in layouts {
+            let block = match idle.iter().position(|block| block.is_compatible(&layout.memory_requirements)) {
+                Some(index) => idle.remove(index),
+                None => Arc::new(MemoryBlock::new(device.clone(), &layout.memory_requirements)?)
+            };
+
+            self.blocks.push(block.clone());
+            acquired.push(block);
+        }
+
+        Ok(acquired)
+    }
+
+    #[inline]
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{BufferHandle, ImageHandle};
+
+    fn image(index: usize, size: u64, alignment: u64, first_pass: usize, last_pass: usize) -> TransientResource {
+        TransientResource {
+            handle: ResourceHandle::Image(ImageHandle { graph: 1, index }),
+            memory_requirements: vk::MemoryRequirements {
+                size,
+                alignment,
+                memory_type_bits: 1
+            },
+            first_pass,
+            last_pass
+        }
+    }
+
+    fn buffer(index: usize, size: u64, first_pass: usize, last_pass: usize) -> TransientResource {
+        TransientResource {
+            handle: ResourceHandle::Buffer(BufferHandle { graph: 1, index }),
+            ..image(index, size, 16, first_pass, last_pass)
+        }
+    }
+
+    #[test]
+    fn disjoint_lifetimes_share_memory() {
+        let resources = [image(0, 1024, 256, 0, 0), image(1, 1024, 256, 1, 1)];
+        let (blocks, placements) = place_resources(&resources);
+
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(placements[0].offset, 0);
+        assert_eq!(placements[1].offset, 0);
+        assert_eq!(aliased_predecessors(&blocks, &resources, &placements, 1), vec![0]);
+    }
+
+    #[test]
+    fn overlapping_lifetimes_are_placed_side_by_side() {
+        let resources = [image(0, 4096, 256, 0, 0), image(1, 1000, 256, 1, 1), image(2, 1000, 256, 1, 2)];
+        let (blocks, placements) = place_resources(&resources);
+
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(placements[1].offset, 0);
+        assert_eq!(placements[2].offset, 1024);
+
+        let report = memory_report(&blocks, &resources);
+        assert_eq!(report.naive_size, 6096);
+        assert_eq!(report.aliased_size, 4096);
+    }
+
+    #[test]
+    fn find_offset_respects_alignment_and_block_size() {
+        let resources = [image(0, 1024, 256, 0, 1), image(1, 100, 512, 1, 1), image(2, 1024, 256, 1, 1)];
+        let block = BlockLayout {
+            memory_requirements: resources[0].memory_requirements,
+            is_image: true,
+            resources: vec![0]
+        };
+        let mut placements = vec![Placement::default(); resources.len()];
+        placements[0] = Placement { block: 0, offset: 0 };
+
+        assert_eq!(find_offset(&block, &resources, &placements, &image(3, 100, 512, 2, 2)), Some(0));
+        assert_eq!(find_offset(&block, &resources, &placements, &resources[1]), None);
+
+        let block = BlockLayout {
+            memory_requirements: vk::MemoryRequirements {
+                size: 2048,
+                ..resources[0].memory_requirements
+            },
+            ..block
+        };
+        assert_eq!(find_offset(&block, &resources, &placements, &resources[1]), Some(1024));
+        assert_eq!(find_offset(&block, &resources, &placements, &resources[2]), Some(1024));
+    }
+
+    #[test]
+    fn images_and_buffers_use_separate_blocks() {
+        let resources = [image(0, 1024, 256, 0, 0), buffer(1, 1024, 1, 1)];
+        let (blocks, placements) = place_resources(&resources);
+
+        assert_eq!(blocks.len(), 2);
+        assert_ne!(placements[0].block, placements[1].block);
+    }
+
+    #[test]
+    fn incompatible_memory_types_use_separate_blocks() {
+        let mut resources = [image(0, 1024, 256, 0, 0), image(1, 1024, 256, 1, 1)];
+        resources[1].memory_requirements.memory_type_bits = 2;
+        let (blocks, _) = place_resources(&resources);
+
+        assert_eq!(blocks.len(), 2);
+    }
+
+    #[test]
+    fn huge_resources_do_not_overflow() {
+        let resources = [image(0, u64::MAX, 256, 0, 1), image(1, 16, 256, 1, 1)];
+        let (blocks, placements) = place_resources(&resources);
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(placements[1].offset, 0);
+        assert_eq!(align_up(u64::MAX - 1, 256), None);
+        assert_eq!(memory_report(&blocks, &resources).naive_size, u64::MAX);
+    }
+}
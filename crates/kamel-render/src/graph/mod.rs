@@ -0,0 +1,9 @@
+mod aliasing;
+mod pass;
+mod render_graph;
+mod resource;
+
+pub use aliasing::{TransientMemoryCache, TransientMemoryReport};
+pub use pass::*;
+pub use render_graph::*;
+pub use resource::*;
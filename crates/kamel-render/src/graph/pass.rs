@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use crate::{
+    backend::{Access, Buffer, CommandBuffer, Image, Recording},
+    graph::{BufferHandle, ImageHandle, RenderGraph, RenderGraphResources, ResourceHandle}
+};
+
+pub(crate) type ExecuteFn<'a> = Box<dyn FnOnce(&mut CommandBuffer<Recording>, &PassResources) + 'a>;
+
+pub(crate) struct Pass<'a> {
+    pub(crate) name: String,
+    pub(crate) accesses: Vec<(ResourceHandle, Access)>,
+    //Passes with side effects are never culled
+    pub(crate) side_effects: bool,
+    pub(crate) execute: Option<ExecuteFn<'a>>
+}
+
+impl Pass<'_> {
+    #[inline]
+    pub(crate) fn uses(&self, resource: ResourceHandle) -> bool {
+        self.accesses.iter().any(|&(accessed, _)| accessed == resource)
+    }
+
+    //Accesses grouped by resource in declaration order, a resource that is read and written by the pass needs a single barrier
+    pub(crate) fn resource_accesses(&self) -> Vec<(ResourceHandle, Vec<Access>)> {
+        let mut resource_accesses: Vec<(ResourceHandle, Vec<Access>)> = Vec::new();
+
+        for &(resource, access) in &self.accesses {
+            match resource_accesses.iter_mut().find(|(accessed, _)| *accessed == resource) {
+                Some((_, accesses)) => accesses.push(access),
+                None => resource_accesses.push((resource, vec![access]))
+            }
+        }
+
+        resource_accesses
+    }
+}
+
+pub struct PassBuilder<'g, 'a> {
+    graph: &'g mut RenderGraph<'a>,
+    pass: Pass<'a>
+}
+
+impl<'g, 'a> PassBuilder<'g, 'a> {
+    #[inline]
+    pub(crate) fn new(graph: &'g mut RenderGraph<'a>, name: &str) -> Self {
+        Self {
+            graph,
+            pass: Pass {
+                name: name.to_owned(),
+                accesses: Vec::new(),
+                side_effects: false,
+                execute: None
+            }
+        }
+    }
+
+    //Handles of other graphs are reported when the graph is compiled
+    fn image(mut self, handle: ImageHandle, access: Access) -> Self {
+        match self.graph.images.get_mut(handle.index) {
+            Some(image) if handle.graph == self.graph.id => {
+                image.usage |= access.image_usage();
+                self.pass.accesses.push((ResourceHandle::Image(handle), access));
+            }
+            _ => self.graph.errors.push(format!("Pass '{}' uses an image of another render graph", self.pass.name))
+        }
+
+        self
+    }
+
+    fn buffer(mut self, handle: BufferHandle, access: Access) -> Self {
+        match self.graph.buffers.get_mut(handle.index) {
+            Some(buffer) if handle.graph == self.graph.id => {
+                buffer.usage |= access.buffer_usage();
+                self.pass.accesses.push((ResourceHandle::Buffer(handle), access));
+            }
+            _ => self.graph.errors.push(format!("Pass '{}' uses a buffer of another render graph", self.pass.name))
+        }
+
+        self
+    }
+
+    #[inline]
+    pub fn read_image(self, handle: ImageHandle, access: Access) -> Self {
+        debug_assert!(!access.is_write(), "{:?} is not a read access", access);
+        self.image(handle, access)
+    }
+
+    #[inline]
+    pub fn write_image(self, handle: ImageHandle, access: Access) -> Self {
+        debug_assert!(access.is_write(), "{:?} is not a write access", access);
+        self.image(handle, access)
+    }
+
+    #[inline]
+    pub fn read_buffer(self, handle: BufferHandle, access: Access) -> Self {
+        debug_assert!(!access.is_write(), "{:?} is not a read access", access);
+        self.buffer(handle, access)
+    }
+
+    #[inline]
+    pub fn write_buffer(self, handle: BufferHandle, access: Access) -> Self {
+        debug_assert!(access.is_write(), "{:?} is not a write access", access);
+        self.buffer(handle, access)
+    }
+
+    //For passes whose results are consumed outside of the graph, like readbacks
+    #[inline]
+    pub fn side_effects(mut self) -> Self {
+        self.pass.side_effects = true;
+        self
+    }
+
+    pub fn execute(mut self, execute: impl FnOnce(&mut CommandBuffer<Recording>, &PassResources) + 'a) {
+        self.pass.execute = Some(Box::new(execute));
+        self.graph.passes.push(self.pass);
+    }
+}
+
+pub struct PassResources<'r> {
+    resources: &'r RenderGraphResources
+}
+
+impl<'r> PassResources<'r> {
+    #[inline]
+    pub(crate) fn new(resources: &'r RenderGraphResources) -> Self {
+        Self { resources }
+    }
+
+    //Panics if the image was not declared by any pass that is executed, views can be created from the returned image
+    #[inline]
+    pub fn image(&self, handle: ImageHandle) -> &Arc<Image> {
+        self.resources.image(handle).expect("Image is not used by the render graph")
+    }
+
+    #[inline]
+    pub fn buffer(&self, handle: BufferHandle) -> &Arc<Buffer> {
+        self.resources.buffer(handle).expect("Buffer is not used by the render graph")
+    }
+}
@@ -0,0 +1,626 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc
+    }
+};
+
+use anyhow::Result;
+use ash::vk;
+
+use crate::{
+    backend::{Access, AccessBarrier, AccessState, Buffer, BufferDesc, CommandBuffer, Device, Image, ImageDesc, MemoryLocation, Recording},
+    graph::{
+        aliasing::{self, TransientResource},
+        BufferHandle, BufferSource, GraphBuffer, GraphBufferDesc, GraphImage, GraphImageDesc, ImageHandle, ImageSource, Pass, PassBuilder, PassResources, RenderGraphResources,
+        ResourceHandle, TransientMemoryCache, TransientMemoryReport
+    }
+};
+
+//Identifies the graph a handle was created by, 0 is never used by a graph
+static NEXT_GRAPH_ID: AtomicU64 = AtomicU64::new(1);
+
+//Barriers needed before a pass, one per resource even if the pass accesses it in multiple ways
+fn pass_barriers(pass: &Pass, states: &mut HashMap<ResourceHandle, AccessState>, aliases: &mut HashMap<ResourceHandle, Vec<ResourceHandle>>) -> Vec<(ResourceHandle, AccessBarrier)> {
+    let mut barriers = Vec::new();
+
+    for (resource, accesses) in pass.resource_accesses() {
+        //The first access of an aliased resource has to wait for the resources that used the memory before
+        if let Some(predecessors) = aliases.remove(&resource) {
+            for predecessor in predecessors {
+                let previous = states[&predecessor];
+                states.get_mut(&resource).unwrap().alias(&previous);
+            }
+        }
+
+        if let Some(barrier) = states.get_mut(&resource).unwrap().transition_all(&accesses) {
+            barriers.push((resource, barrier));
+        }
+    }
+
+    barriers
+}
+
+//Passes are declared in submission order, the graph derives the dependencies between them from their accesses,
+//culls everything that does not contribute to an imported resource and inserts the barriers in between
+pub struct RenderGraph<'a> {
+    pub(crate) id: u64,
+    pub(crate) images: Vec<GraphImage>,
+    pub(crate) buffers: Vec<GraphBuffer>,
+    pub(crate) passes: Vec<Pass<'a>>,
+    //Invalid declarations, reported by compile
+    pub(crate) errors: Vec<String>
+}
+
+impl Default for RenderGraph<'_> {
+    fn default() -> Self {
+        Self {
+            id: NEXT_GRAPH_ID.fetch_add(1, Ordering::Relaxed),
+            images: Vec::new(),
+            buffers: Vec::new(),
+            passes: Vec::new(),
+            errors: Vec::new()
+        }
+    }
+}
+
+impl<'a> RenderGraph<'a> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    fn image_handle(&self, index: usize) -> ImageHandle {
+        ImageHandle { graph: self.id, index }
+    }
+
+    #[inline]
+    fn buffer_handle(&self, index: usize) -> BufferHandle {
+        BufferHandle { graph: self.id, index }
+    }
+
+    pub fn create_image(&mut self, name: &str, desc: &GraphImageDesc) -> ImageHandle {
+        self.images.push(GraphImage {
+            name: name.to_owned(),
+            source: ImageSource::Transient(*desc),
+            usage: vk::ImageUsageFlags::empty()
+        });
+
+        self.image_handle(self.images.len() - 1)
+    }
+
+    pub fn create_buffer(&mut self, name: &str, desc: &GraphBufferDesc) -> BufferHandle {
+        self.buffers.push(GraphBuffer {
+            name: name.to_owned(),
+            source: BufferSource::Transient(*desc),
+            usage: vk::BufferUsageFlags::empty()
+        });
+
+        self.buffer_handle(self.buffers.len() - 1)
+    }
+
+    //The graph starts from the state tracked by the image and keeps it in sync. The image is transitioned to the final access
+    //after the last pass, like Access::Present for swapchain images.
+    pub fn import_image(&mut self, name: &str, image: Arc<Image>, final_access: Option<Access>) -> ImageHandle {
+        let usage = image.usage();
+
+        self.images.push(GraphImage {
+            name: name.to_owned(),
+            source: ImageSource::Imported { image, final_access },
+            usage
+        });
+
+        self.image_handle(self.images.len() - 1)
+    }
+
+    //Like import_image, the state tracked by the buffer is used and updated
+    pub fn import_buffer(&mut self, name: &str, buffer: Arc<Buffer>, final_access: Option<Access>) -> BufferHandle {
+        let usage = buffer.usage();
+
+        self.buffers.push(GraphBuffer {
+            name: name.to_owned(),
+            source: BufferSource::Imported { buffer, final_access },
+            usage
+        });
+
+        self.buffer_handle(self.buffers.len() - 1)
+    }
+
+    #[inline]
+    pub fn add_pass<'g>(&'g mut self, name: &str) -> PassBuilder<'g, 'a> {
+        PassBuilder::new(self, name)
+    }
+
+    #[inline]
+    fn is_imported(&self, resource: ResourceHandle) -> bool {
+        match resource {
+            ResourceHandle::Image(handle) => self.images[handle.index].is_imported(),
+            ResourceHandle::Buffer(handle) => self.buffers[handle.index].is_imported()
+        }
+    }
+
+    #[inline]
+    fn resource_name(&self, resource: ResourceHandle) -> &str {
+        match resource {
+            ResourceHandle::Image(handle) => &self.images[handle.index].name,
+            ResourceHandle::Buffer(handle) => &self.buffers[handle.index].name
+        }
+    }
+
+    //Returns the producers (read after write and write after write dependencies) and the predecessors of every pass.
+    //Only producers keep a pass alive, the predecessors additionally contain the write after read dependencies that order passes.
+    fn dependencies(&self) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+        let mut producers = vec![Vec::new(); self.passes.len()];
+        let mut predecessors = vec![Vec::new(); self.passes.len()];
+        let mut last_writers = HashMap::<ResourceHandle, usize>::new();
+        let mut readers = HashMap::<ResourceHandle, Vec<usize>>::new();
+
+        for (index, pass) in self.passes.iter().enumerate() {
+            for &(resource, access) in &pass.accesses {
+                if let Some(&writer) = last_writers.get(&resource) {
+                    producers[index].push(writer);
+                }
+
+                if access.is_write() {
+                    if let Some(readers) = readers.remove(&resource) {
+                        predecessors[index].extend(readers);
+                    }
+
+                    last_writers.insert(resource, index);
+                } else {
+                    readers.entry(resource).or_default().push(index);
+                }
+            }
+
+            producers[index].retain(|&producer| producer != index);
+            producers[index].sort_unstable();
+            producers[index].dedup();
+
+            predecessors[index].extend_from_slice(&producers[index]);
+            predecessors[index].retain(|&predecessor| predecessor != index);
+            predecessors[index].sort_unstable();
+            predecessors[index].dedup();
+        }
+
+        (producers, predecessors)
+    }
+
+    //Returns the indices of the passes that have to be executed in execution order
+    fn compile(&self) -> Result<Vec<usize>> {
+        if !self.errors.is_empty() {
+            anyhow::bail!("Invalid render graph: {}", self.errors.join(", "));
+        }
+
+        let (producers, predecessors) = self.dependencies();
+
+        let mut alive = vec![false; self.passes.len()];
+        let mut stack: Vec<_> = self
+            .passes
+            .iter()
+            .enumerate()
+            .filter(|(_, pass)| pass.side_effects || pass.accesses.iter().any(|&(resource, access)| access.is_write() && self.is_imported(resource)))
+            .map(|(index, _)| index)
+            .collect();
+
+        while let Some(index) = stack.pop() {
+            if !alive[index] {
+                alive[index] = true;
+                stack.extend(&producers[index]);
+            }
+        }
+
+        let mut in_degrees = vec![0; self.passes.len()];
+        let mut successors = vec![Vec::new(); self.passes.len()];
+        for index in (0..self.passes.len()).filter(|&index| alive[index]) {
+            //Culled readers no longer have to run before a write
+            for &predecessor in predecessors[index].iter().filter(|&&predecessor| alive[predecessor]) {
+                in_degrees[index] += 1;
+                successors[predecessor].push(index);
+            }
+        }
+
+        //Ties are broken by declaration order to keep the execution order predictable
+        let mut ready: BinaryHeap<_> = (0..self.passes.len()).filter(|&index| alive[index] && in_degrees[index] == 0).map(Reverse).collect();
+        let mut order = Vec::new();
+
+        while let Some(Reverse(index)) = ready.pop() {
+            order.push(index);
+
+            for &successor in &successors[index] {
+                in_degrees[successor] -= 1;
+                if in_degrees[successor] == 0 {
+                    ready.push(Reverse(successor));
+                }
+            }
+        }
+
+        let mut written = HashSet::new();
+        for &index in &order {
+            let pass = &self.passes[index];
+
+            for &(resource, access) in &pass.accesses {
+                if access.is_write() {
+                    written.insert(resource);
+                } else if !self.is_imported(resource) && !written.contains(&resource) {
+                    anyhow::bail!("Pass '{}' reads transient resource '{}' before it was written", pass.name, self.resource_name(resource));
+                }
+            }
+        }
+
+        Ok(order)
+    }
+
+    #[inline]
+    fn transient_image_desc(&self, handle: ImageHandle) -> Option<ImageDesc> {
+        let image = &self.images[handle.index];
+
+        match &image.source {
+            ImageSource::Transient(desc) => {
+                Some(ImageDesc {
+                    image_type: desc.image_type,
+                    format: desc.format,
+                    extent: desc.extent,
+                    mip_levels: desc.mip_levels,
+                    array_layers: desc.array_layers,
+                    samples: desc.samples,
+                    usage: image.usage,
+                    memory_location: MemoryLocation::GpuOnly,
+                    label: Some(&image.name)
+                })
+            }
+            ImageSource::Imported { .. } => None
+        }
+    }
+
+    #[inline]
+    fn transient_buffer_desc(&self, handle: BufferHandle) -> Option<BufferDesc> {
+        let buffer = &self.buffers[handle.index];
+
+        match &buffer.source {
+            BufferSource::Transient(desc) => {
+                Some(BufferDesc {
+                    size: desc.size,
+                    usage: buffer.usage,
+                    memory_location: MemoryLocation::GpuOnly,
+                    label: Some(&buffer.name)
+                })
+            }
+            BufferSource::Imported { .. } => None
+        }
+    }
+
+    //Places the transient resources of the executed passes into shared memory blocks, resources whose lifetimes do not overlap
+    //may share memory. Returns the resources that previously occupied the memory of each resource.
+    fn allocate_transient_resources(
+        &self,
+        device: &Device,
+        cache: &mut TransientMemoryCache,
+        order: &[usize],
+        resources: &mut RenderGraphResources
+    ) -> Result<HashMap<ResourceHandle, Vec<ResourceHandle>>> {
+        let mut lifetimes = HashMap::<ResourceHandle, (usize, usize)>::new();
+        for (position, &index) in order.iter().enumerate() {
+            for &(resource, _) in &self.passes[index].accesses {
+                if !self.is_imported(resource) {
+                    lifetimes.entry(resource).and_modify(|(_, last_pass)| *last_pass = position).or_insert((position, position));
+                }
+            }
+        }
+
+        let mut lifetimes: Vec<_> = lifetimes.into_iter().collect();
+        lifetimes.sort_unstable_by_key(|&(resource, _)| resource);
+
+        let transient_resources = lifetimes
+            .into_iter()
+            .map(|(handle, (first_pass, last_pass))| {
+                let memory_requirements = match handle {
+                    ResourceHandle::Image(image) => Image::memory_requirements(device, &self.transient_image_desc(image).unwrap())?,
+                    ResourceHandle::Buffer(buffer) => Buffer::memory_requirements(device, &self.transient_buffer_desc(buffer).unwrap())?
+                };
+
+                Ok(TransientResource {
+                    handle,
+                    memory_requirements,
+                    first_pass,
+                    last_pass
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let (blocks, placements) = aliasing::place_resources(&transient_resources);
+        resources.memory_report = aliasing::memory_report(&blocks, &transient_resources);
+
+        let memory_blocks = cache.acquire(device, &blocks)?;
+
+        let mut aliases = HashMap::new();
+
+        for (index, resource) in transient_resources.iter().enumerate() {
+            let placement = placements[index];
+            let memory_block = memory_blocks[placement.block].clone();
+
+            match resource.handle {
+                ResourceHandle::Image(handle) => {
+                    let image = Image::new_aliased(device.clone(), &self.transient_image_desc(handle).unwrap(), memory_block, placement.offset)?;
+                    resources.images[handle.index] = Some(Arc::new(image));
+                }
+                ResourceHandle::Buffer(handle) => {
+                    let buffer = Buffer::new_aliased(device.clone(), &self.transient_buffer_desc(handle).unwrap(), memory_block, placement.offset)?;
+                    resources.buffers[handle.index] = Some(Arc::new(buffer));
+                }
+            }
+
+            let predecessors = aliasing::aliased_predecessors(&blocks, &transient_resources, &placements, index);
+            if !predecessors.is_empty() {
+                aliases.insert(resource.handle, predecessors.into_iter().map(|predecessor| transient_resources[predecessor].handle).collect());
+            }
+        }
+
+        Ok(aliases)
+    }
+
+    //Records all passes that are not culled, the returned resources have to outlive the command buffer's execution.
+    //Transient resources are placed into memory blocks of the cache that are no longer used by previous executions.
+    pub fn execute(mut self, command_buffer: &mut CommandBuffer<Recording>, cache: &mut TransientMemoryCache) -> Result<RenderGraphResources> {
+        let order = self.compile()?;
+        let device = command_buffer.device().clone();
+
+        let mut resources = RenderGraphResources {
+            graph: self.id,
+            images: vec![None; self.images.len()],
+            buffers: vec![None; self.buffers.len()],
+            memory_report: TransientMemoryReport::default()
+        };
+
+        let mut states = HashMap::new();
+
+        //Imported resources are always available so they reach their final access even if no pass uses them
+        for (index, image) in self.images.iter().enumerate() {
+            let handle = ResourceHandle::Image(self.image_handle(index));
+
+            match &image.source {
+                ImageSource::Transient(_) => states.insert(handle, AccessState::image(Access::Nothing)),
+                ImageSource::Imported { image, .. } => {
+                    //Subresources in different states are moved to the general layout since the graph tracks the whole image
+                    let state = match image.uniform_state() {
+                        Some(state) => state,
+                        None => {
+                            command_buffer.transition(image, Access::General);
+                            AccessState::image(Access::General)
+                        }
+                    };
+
+                    resources.images[index] = Some(image.clone());
+                    states.insert(handle, state)
+                }
+            };
+        }
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            let handle = ResourceHandle::Buffer(self.buffer_handle(index));
+
+            match &buffer.source {
+                BufferSource::Transient(_) => states.insert(handle, AccessState::buffer(Access::Nothing)),
+                BufferSource::Imported { buffer, .. } => {
+                    resources.buffers[index] = Some(buffer.clone());
+                    states.insert(handle, buffer.state())
+                }
+            };
+        }
+
+        let mut aliases = self.allocate_transient_resources(&device, cache, &order, &mut resources)?;
+
+        for index in order {
+            let pass = &mut self.passes[index];
+
+            let mut image_memory_barriers = Vec::new();
+            let mut buffer_memory_barriers = Vec::new();
+
+            for (resource, barrier) in pass_barriers(pass, &mut states, &mut aliases) {
+                match resource {
+                    ResourceHandle::Image(handle) => {
+                        let image = resources.images[handle.index].as_ref().unwrap();
+                        image_memory_barriers.push(barrier.image_memory_barrier(***image, image.full_subresource_range()));
+                    }
+                    ResourceHandle::Buffer(handle) => {
+                        let buffer = resources.buffers[handle.index].as_ref().unwrap();
+                        buffer_memory_barriers.push(barrier.buffer_memory_barrier(***buffer, 0, vk::WHOLE_SIZE));
+                    }
+                }
+            }
+
+            if !image_memory_barriers.is_empty() || !buffer_memory_barriers.is_empty() {
+                //The barriers only reference resources owned or imported by the graph
+                unsafe {
+                    command_buffer.pipeline_barrier2(
+                        &vk::DependencyInfo::default()
+                            .image_memory_barriers(&image_memory_barriers)
+                            .buffer_memory_barriers(&buffer_memory_barriers)
+                    )
+                };
+            }
+
+            if let Some(execute) = pass.execute.take() {
+                execute(command_buffer, &PassResources::new(&resources));
+            }
+        }
+
+        let mut image_memory_barriers = Vec::new();
+        let mut buffer_memory_barriers = Vec::new();
+
+        for (index, image) in self.images.iter().enumerate() {
+            if let ImageSource::Imported { image, final_access } = &image.source {
+                let state = states.get_mut(&ResourceHandle::Image(self.image_handle(index))).unwrap();
+
+                if let Some(barrier) = final_access.and_then(|final_access| state.transition(final_access)) {
+                    image_memory_barriers.push(barrier.image_memory_barrier(***image, image.full_subresource_range()));
+                }
+
+                //Keeps the state tracked by the image in sync for CommandBuffer::transition and later graphs
+                image.assume_range_state(image.full_range(), *state);
+            }
+        }
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            if let BufferSource::Imported { buffer, final_access } = &buffer.source {
+                let state = states.get_mut(&ResourceHandle::Buffer(self.buffer_handle(index))).unwrap();
+
+                if let Some(barrier) = final_access.and_then(|final_access| state.transition(final_access)) {
+                    buffer_memory_barriers.push(barrier.buffer_memory_barrier(***buffer, 0, vk::WHOLE_SIZE));
+                }
+
+                buffer.assume_state(*state);
+            }
+        }
+
+        if !image_memory_barriers.is_empty() || !buffer_memory_barriers.is_empty() {
+            //The barriers only reference resources owned or imported by the graph
+            unsafe {
+                command_buffer.pipeline_barrier2(
+                    &vk::DependencyInfo::default()
+                        .image_memory_barriers(&image_memory_barriers)
+                        .buffer_memory_barriers(&buffer_memory_barriers)
+                )
+            };
+        }
+
+        Ok(resources)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ImageType;
+
+    const IMAGE_DESC: GraphImageDesc = GraphImageDesc {
+        image_type: ImageType::Tex2d,
+        format: vk::Format::R8G8B8A8_UNORM,
+        extent: vk::Extent3D { width: 64, height: 64, depth: 1 },
+        mip_levels: 1,
+        array_layers: 1,
+        samples: vk::SampleCountFlags::TYPE_1
+    };
+
+    fn barriers(graph: &RenderGraph) -> Vec<Vec<(ResourceHandle, AccessBarrier)>> {
+        let mut states: HashMap<_, _> = (0..graph.images.len())
+            .map(|index| (ResourceHandle::Image(graph.image_handle(index)), AccessState::image(Access::Nothing)))
+            .collect();
+        let mut aliases = HashMap::new();
+
+        graph
+            .compile()
+            .unwrap()
+            .into_iter()
+            .map(|index| pass_barriers(&graph.passes[index], &mut states, &mut aliases))
+            .collect()
+    }
+
+    #[test]
+    fn culls_unused_passes() {
+        let mut graph = RenderGraph::new();
+        let used = graph.create_image("used", &IMAGE_DESC);
+        let unused = graph.create_image("unused", &IMAGE_DESC);
+
+        graph.add_pass("write used").write_image(used, Access::ComputeShaderWrite).execute(|_, _| {});
+        graph.add_pass("write unused").write_image(unused, Access::ComputeShaderWrite).execute(|_, _| {});
+        graph.add_pass("read used").read_image(used, Access::ComputeShaderRead).side_effects().execute(|_, _| {});
+
+        assert_eq!(graph.compile().unwrap(), vec![0, 2]);
+    }
+
+    #[test]
+    fn culls_readers_before_writes() {
+        let mut graph = RenderGraph::new();
+        let image = graph.create_image("image", &IMAGE_DESC);
+
+        graph.add_pass("write").write_image(image, Access::ComputeShaderWrite).execute(|_, _| {});
+        graph.add_pass("read").read_image(image, Access::ComputeShaderRead).execute(|_, _| {});
+        graph.add_pass("overwrite").write_image(image, Access::ComputeShaderWrite).execute(|_, _| {});
+        graph.add_pass("consume").read_image(image, Access::ComputeShaderRead).side_effects().execute(|_, _| {});
+
+        assert_eq!(graph.compile().unwrap(), vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn orders_by_dependencies() {
+        let mut graph = RenderGraph::new();
+        let a = graph.create_image("a", &IMAGE_DESC);
+        let b = graph.create_image("b", &IMAGE_DESC);
+
+        graph.add_pass("write a").write_image(a, Access::ComputeShaderWrite).execute(|_, _| {});
+        graph.add_pass("read a").read_image(a, Access::ComputeShaderRead).side_effects().execute(|_, _| {});
+        graph.add_pass("write b").write_image(b, Access::ComputeShaderWrite).execute(|_, _| {});
+        graph
+            .add_pass("overwrite a")
+            .read_image(b, Access::ComputeShaderRead)
+            .write_image(a, Access::ComputeShaderWrite)
+            .side_effects()
+            .execute(|_, _| {});
+
+        assert_eq!(graph.compile().unwrap(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn rejects_reads_before_writes() {
+        let mut graph = RenderGraph::new();
+        let image = graph.create_image("image", &IMAGE_DESC);
+
+        graph.add_pass("read").read_image(image, Access::ComputeShaderRead).side_effects().execute(|_, _| {});
+
+        assert!(graph.compile().is_err());
+    }
+
+    #[test]
+    fn rejects_handles_of_other_graphs() {
+        let mut other = RenderGraph::new();
+        let foreign = other.create_image("foreign", &IMAGE_DESC);
+
+        let mut graph = RenderGraph::new();
+        graph.create_image("image", &IMAGE_DESC);
+        graph.add_pass("write").write_image(foreign, Access::ComputeShaderWrite).side_effects().execute(|_, _| {});
+
+        assert!(graph.passes[0].accesses.is_empty());
+        assert!(graph.compile().is_err());
+    }
+
+    #[test]
+    fn layout_transition_between_passes() {
+        let mut graph = RenderGraph::new();
+        let image = graph.create_image("image", &IMAGE_DESC);
+
+        graph.add_pass("render").write_image(image, Access::ColorAttachmentWrite).execute(|_, _| {});
+        graph.add_pass("sample").read_image(image, Access::FragmentShaderRead).side_effects().execute(|_, _| {});
+
+        let barriers = barriers(&graph);
+
+        assert_eq!(barriers[1].len(), 1);
+        let (_, barrier) = barriers[1][0];
+        assert_eq!(barrier.src_stage_mask, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT);
+        assert_eq!(barrier.dst_stage_mask, vk::PipelineStageFlags2::FRAGMENT_SHADER);
+        assert_eq!(barrier.old_layout, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        assert_eq!(barrier.new_layout, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+    }
+
+    #[test]
+    fn merges_accesses_of_a_pass() {
+        let mut graph = RenderGraph::new();
+        let image = graph.create_image("image", &IMAGE_DESC);
+
+        graph.add_pass("clear").write_image(image, Access::ComputeShaderWrite).execute(|_, _| {});
+        graph
+            .add_pass("accumulate")
+            .read_image(image, Access::ComputeShaderReadStorage)
+            .write_image(image, Access::ComputeShaderWrite)
+            .side_effects()
+            .execute(|_, _| {});
+
+        let barriers = barriers(&graph);
+
+        assert_eq!(barriers[1].len(), 1);
+        let (_, barrier) = barriers[1][0];
+        assert_eq!(barrier.dst_access_mask, vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE);
+        assert_eq!(barrier.old_layout, vk::ImageLayout::GENERAL);
+        assert_eq!(barrier.new_layout, vk::ImageLayout::GENERAL);
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use crate::{
+    backend::{Access, Buffer, Image, ImageType},
+    graph::TransientMemoryReport
+};
+
+//Handles are only valid for the graph that created them
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ImageHandle {
+    pub(crate) graph: u64,
+    pub(crate) index: usize
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BufferHandle {
+    pub(crate) graph: u64,
+    pub(crate) index: usize
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum ResourceHandle {
+    Image(ImageHandle),
+    Buffer(BufferHandle)
+}
+
+//Usage flags are derived from the accesses of the passes
+#[derive(Copy, Clone, Debug)]
+pub struct GraphImageDesc {
+    pub image_type: ImageType,
+    pub format: vk::Format,
+    pub extent: vk::Extent3D,
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    pub samples: vk::SampleCountFlags
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct GraphBufferDesc {
+    pub size: u64
+}
+
+pub(crate) enum ImageSource {
+    Transient(GraphImageDesc),
+    Imported { image: Arc<Image>, final_access: Option<Access> }
+}
+
+pub(crate) struct GraphImage {
+    pub(crate) name: String,
+    pub(crate) source: ImageSource,
+    pub(crate) usage: vk::ImageUsageFlags
+}
+
+pub(crate) enum BufferSource {
+    Transient(GraphBufferDesc),
+    Imported { buffer: Arc<Buffer>, final_access: Option<Access> }
+}
+
+pub(crate) struct GraphBuffer {
+    pub(crate) name: String,
+    pub(crate) source: BufferSource,
+    pub(crate) usage: vk::BufferUsageFlags
+}
+
+impl GraphImage {
+    #[inline]
+    pub(crate) fn is_imported(&self) -> bool {
+        matches!(self.source, ImageSource::Imported { .. })
+    }
+}
+
+impl GraphBuffer {
+    #[inline]
+    pub(crate) fn is_imported(&self) -> bool {
+        matches!(self.source, BufferSource::Imported { .. })
+    }
+}
+
+//Transient resources have to be kept alive until the command buffer the graph was recorded into completed
+#[derive(Default)]
+pub struct RenderGraphResources {
+    pub(crate) graph: u64,
+    pub(crate) images: Vec<Option<Arc<Image>>>,
+    pub(crate) buffers: Vec<Option<Arc<Buffer>>>,
+    pub(crate) memory_report: TransientMemoryReport
+}
+
+impl RenderGraphResources {
+    //Returns None for transient resources that were only used by culled passes and for handles of other graphs
+    #[inline]
+    pub fn image(&self, handle: ImageHandle) -> Option<&Arc<Image>> {
+        self.images.get(handle.index).filter(|_| handle.graph == self.graph).and_then(Option::as_ref)
+    }
+
+    #[inline]
+    pub fn buffer(&self, handle: BufferHandle) -> Option<&Arc<Buffer>> {
+        self.buffers.get(handle.index).filter(|_| handle.graph == self.graph).and_then(Option::as_ref)
+    }
+
+    //Memory used by the transient resources of this frame compared to giving every resource its own allocation
+    #[inline]
+    pub fn memory_report(&self) -> &TransientMemoryReport {
+        &self.memory_report
+    }
+}